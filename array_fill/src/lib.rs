@@ -49,7 +49,7 @@ impl ToTokens for ExprRepeat {
 ///
 /// and
 ///
-/// ```
+/// ```ignore
 /// let arr: [u8; 64] = std::default::Default();
 /// ```
 ///
@@ -58,13 +58,13 @@ impl ToTokens for ExprRepeat {
 ///
 /// This macro, use like
 ///
-/// ```
+/// ```ignore
 /// let arr: [Option<Vec<u8>>; 128] = array_fill![None; 128];
 /// ```
 ///
 /// will expand to
 ///
-/// ```
+/// ```ignore
 /// let arr: [Option<Vec<u8>>; 128] = [None, None, /* ... */, None, None];
 /// ```
 ///
@@ -1,8 +1,10 @@
 use std::mem::size_of;
 
+pub use cond_bit_field::cond_bit_field;
+
 use thiserror::Error;
 
-/// The error type for `BitStream`'s read operations
+/// The error type for `BitStream`'s read operations and `BitWriter`'s write operations
 #[derive(Error, Debug)]
 pub enum BitStreamError {
     /// The `BitStream` has not enough bits for the requested size.
@@ -12,6 +14,20 @@ pub enum BitStreamError {
     /// The requested size doesn't fit into the result type.
     #[error("Requested size too large for result type")]
     TooLarge,
+
+    /// The value to write can't be represented in the requested size.
+    #[error("Value doesn't fit into requested size")]
+    Overflow,
+
+    /// A value required by the layout is missing,
+    /// for example a `None` field when its condition holds.
+    #[error("Missing value required by the layout")]
+    MissingValue,
+
+    /// The value read is not allowed by the syntax,
+    /// for example a fixed pattern doesn't match.
+    #[error("Invalid value for the syntax")]
+    InvalidValue,
 }
 
 pub type Result<T> = ::std::result::Result<T, BitStreamError>;
@@ -32,7 +48,59 @@ pub trait BitField<'a>: Sized {
     fn read(stream: &mut BitStream, args: Self::Args) -> Result<Self>;
 }
 
+/// The `BitFieldWrite` trait defines how to `write` into a `BitWriter`
+///
+/// It's the counterpart of `BitField`, writing a value with the same `Args`
+/// should produce the bits that `BitField::read` consumes.
+pub trait BitFieldWrite<'a> {
+    /// Type of extra arguments for `write`.
+    ///
+    /// Should be the same as `BitField::Args` if the type implements both traits.
+    type Args;
+
+    /// Writes into a `BitWriter`.
+    fn write(&self, writer: &mut BitWriter, args: Self::Args) -> Result<()>;
+}
+
+impl<'a, T: BitFieldWrite<'a> + ?Sized> BitFieldWrite<'a> for &T {
+    type Args = T::Args;
+
+    fn write(&self, writer: &mut BitWriter, args: Self::Args) -> Result<()> {
+        (**self).write(writer, args)
+    }
+}
+
+/// Binds struct fields in the `write` implementations generated by `cond_bit_field!`.
+///
+/// `(&FieldValue(&field)).get()` copies `Copy` values and borrows everything else,
+/// so conditions see the same types as when reading, without cloning any field.
+#[doc(hidden)]
+pub mod field_value {
+    pub struct FieldValue<'a, T>(pub &'a T);
+
+    pub trait CopyFieldValue<T> {
+        fn get(&self) -> T;
+    }
+
+    impl<'a, T: Copy> CopyFieldValue<T> for FieldValue<'a, T> {
+        fn get(&self) -> T {
+            *self.0
+        }
+    }
+
+    pub trait RefFieldValue<'a, T> {
+        fn get(&self) -> &'a T;
+    }
+
+    impl<'a, T> RefFieldValue<'a, T> for &FieldValue<'a, T> {
+        fn get(&self) -> &'a T {
+            self.0
+        }
+    }
+}
+
 /// A stream that can be read bit by bit
+#[derive(Clone)]
 pub struct BitStream<'a> {
     data: &'a [u8],
     offset: usize,
@@ -43,7 +111,7 @@ pub struct BitStream<'a> {
 impl<'a> BitStream<'a> {
     /// Creates a new `BitStream`.
     pub fn new(slice: &'a [u8]) -> Self {
-        let buf = if slice.len() != 0 { slice[0] } else { 0 };
+        let buf = if !slice.is_empty() { slice[0] } else { 0 };
         Self {
             data: slice,
             offset: 0,
@@ -62,6 +130,21 @@ impl<'a> BitStream<'a> {
         (self.data.len() - self.offset) * 8 - self.pos as usize
     }
 
+    /// Returns whether there is more data before the `rbsp_trailing_bits`.
+    ///
+    /// The last `1` bit in the stream is treated as `rbsp_stop_one_bit`.
+    ///
+    /// § 7.2 Specification of syntax functions, categories, and descriptors
+    pub fn more_rbsp_data(&self) -> bool {
+        let last = match self.data.iter().rposition(|byte| *byte != 0) {
+            Some(last) => last,
+            None => return false,
+        };
+
+        let stop_bit = last * 8 + 7 - self.data[last].trailing_zeros() as usize;
+        self.offset * 8 + (self.pos as usize) < stop_bit
+    }
+
     /// Skip (throw away) `bit_count` bits.
     pub fn skip(&mut self, bit_count: usize) -> Result<()> {
         let pos_overflow = self.pos as usize + bit_count;
@@ -99,6 +182,15 @@ impl<'a> BitStream<'a> {
         T::read(self, args)
     }
 
+    /// Reads `len` bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<Box<[u8]>> {
+        let mut data = Vec::with_capacity(len);
+        for _ in 0..len {
+            data.push(self.read(8)?);
+        }
+        Ok(data.into_boxed_slice())
+    }
+
     /// Reads all remaining bytes
     ///
     /// The stream must be byte aligned when `read_all` was called.
@@ -111,14 +203,154 @@ impl<'a> BitStream<'a> {
             return Box::new([]);
         }
 
-        let mut data = Vec::with_capacity(self.data.len() - self.offset);
-        data.copy_from_slice(&self.data[self.offset..]);
+        let data = self.data[self.offset..].to_vec();
         self.offset = self.data.len();
         self.pos = 0;
         data.into_boxed_slice()
     }
 }
 
+/// A stream that can be written bit by bit
+#[derive(Default)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    byte: u8,
+    pos: u8,
+}
+
+impl BitWriter {
+    /// Creates a new `BitWriter`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the `BitWriter` is currently byte aligned
+    pub fn byte_aligned(&self) -> bool {
+        self.pos == 0
+    }
+
+    /// Returns the written bit count.
+    pub fn len(&self) -> usize {
+        self.data.len() * 8 + self.pos as usize
+    }
+
+    /// Returns whether nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes one bit.
+    ///
+    /// `true` is written as `1`, `false` as `0`
+    pub fn write_bit(&mut self, value: bool) -> Result<()> {
+        self.byte |= (value as u8) << (7 - self.pos);
+        self.pos += 1;
+
+        if self.pos == 8 {
+            self.data.push(self.byte);
+            self.byte = 0;
+            self.pos = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Skip `bit_count` bits by writing `0`s.
+    pub fn skip(&mut self, bit_count: usize) -> Result<()> {
+        for _ in 0..bit_count {
+            self.write_bit(false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `0`s until the `BitWriter` is byte aligned.
+    pub fn byte_align(&mut self) -> Result<()> {
+        while !self.byte_aligned() {
+            self.write_bit(false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the lowest `size` bits of `value`, most significant bit first.
+    ///
+    /// Returns `Overflow` if `value` doesn't fit into `size` bits.
+    pub fn write_unsigned(&mut self, value: u128, size: u8) -> Result<()> {
+        if size > 128 {
+            return Err(BitStreamError::TooLarge);
+        }
+
+        if size < 128 && value >> size != 0 {
+            return Err(BitStreamError::Overflow);
+        }
+
+        for i in (0..size).rev() {
+            self.write_bit((value >> i) & 0b1 == 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `value` as a `size` bits two's complement integer.
+    ///
+    /// Returns `Overflow` if `value` doesn't fit into `size` bits.
+    pub fn write_signed(&mut self, value: i128, size: u8) -> Result<()> {
+        if size > 128 {
+            return Err(BitStreamError::TooLarge);
+        }
+
+        if size == 0 {
+            return if value == 0 {
+                Ok(())
+            } else {
+                Err(BitStreamError::Overflow)
+            };
+        }
+
+        // All bits above the sign bit must equal to the sign bit
+        let high = value >> (size - 1);
+        if high != 0 && high != -1 {
+            return Err(BitStreamError::Overflow);
+        }
+
+        for i in (0..size).rev() {
+            self.write_bit((value >> i) & 0b1 == 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a `BitFieldWrite`.
+    pub fn write<'b, T: BitFieldWrite<'b>>(&mut self, value: &T, args: T::Args) -> Result<()> {
+        value.write(self, args)
+    }
+
+    /// Returns the written bytes.
+    ///
+    /// The last byte is padded with `0`s if the `BitWriter` is not byte aligned.
+    pub fn into_inner(mut self) -> Box<[u8]> {
+        if !self.byte_aligned() {
+            self.data.push(self.byte);
+        }
+        self.data.into_boxed_slice()
+    }
+}
+
+impl<'a> BitField<'a> for bool {
+    type Args = ();
+
+    fn read(stream: &mut BitStream, _: ()) -> Result<Self> {
+        stream.read_bit()
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for bool {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        writer.write_bit(*self)
+    }
+}
+
 macro_rules! impl_bit_field_for_signed {
     ($ty: ty) => {
         impl<'a> BitField<'a> for $ty {
@@ -138,6 +370,18 @@ macro_rules! impl_bit_field_for_signed {
                 Ok(result)
             }
         }
+
+        impl<'a> BitFieldWrite<'a> for $ty {
+            type Args = u8;
+
+            fn write(&self, writer: &mut BitWriter, size: u8) -> Result<()> {
+                if size as usize > size_of::<$ty>() * 8 {
+                    return Err(BitStreamError::TooLarge);
+                }
+
+                writer.write_signed(*self as i128, size)
+            }
+        }
     };
 }
 
@@ -166,6 +410,18 @@ macro_rules! impl_bit_field_for_unsigned {
                 Ok(result)
             }
         }
+
+        impl<'a> BitFieldWrite<'a> for $ty {
+            type Args = u8;
+
+            fn write(&self, writer: &mut BitWriter, size: u8) -> Result<()> {
+                if size as usize > size_of::<$ty>() * 8 {
+                    return Err(BitStreamError::TooLarge);
+                }
+
+                writer.write_unsigned(*self as u128, size)
+            }
+        }
     };
 }
 
@@ -179,187 +435,314 @@ impl_bit_field_for_unsigned!(usize);
 #[cfg(test)]
 mod tests {
     use crate as bit_stream;
-    use cond_bit_field::bit_field;
-
-    #[cfg(test)]
-    mod test {
-        use super::*;
-
-        #[bit_field]
-        fn Foo(reader: &mut BitStream) {
-            #[field]
-            let foo: u8;
-        }
-    }
-
-    #[bit_field]
-    pub fn Simple() {
-        let a: bool;
-        let b: i1;
-        let _ = 10;
-        let c: u1;
-        let d: i4;
-        let e: u4;
-        let _ = 10;
-        let f: i7;
-        let g: u7;
-        let h: i14;
-        let i: u14;
-        let _ = 10;
-        let j: i18;
-        let k: u18;
-        let _ = 10;
-    }
-
-    #[bit_field]
-    pub fn IfWithoutElse() {
-        let a: bool;
-        let _ = 10;
-
-        if a {
-            let _ = 10;
-            let b: u3;
-            let _ = 10;
-            let c: i15;
-            let _ = 10;
-        }
-
-        let _ = 10;
-    }
-
-    #[bit_field]
-    pub fn IfElse() {
-        let a: bool;
-        let _ = 10;
-
-        if a {
-            let _ = 10;
-            let b: u3;
-            let _ = 10;
-            let c: i15;
-            let _ = 10;
-        } else {
-            let _ = 10;
-            let d: i4;
-            let _ = 10;
-            let e: u10;
-            let _ = 10;
-        }
-
-        let _ = 10;
-        let z: bool;
-    }
-
-    #[bit_field]
-    pub fn IfElseIf() {
-        let a: bool;
-        let _ = 10;
-
-        if a {
-            let _ = 10;
-            let b: u3;
-            let _ = 10;
-            let c: i15;
-            let _ = 10;
-        } else if !a {
-            let _ = 10;
-            let d: i4;
-            let _ = 10;
-            let e: u10;
-            let _ = 10;
-        }
-
-        let _ = 10;
-        let z: bool;
-    }
-
-    #[bit_field]
-    pub fn IfElseIfElse() {
-        let a: bool;
-        let _ = 10;
-
-        if a {
-            let _ = 10;
-            let b: u3;
-            let _ = 10;
-            let c: i15;
-            let _ = 10;
-        } else if !a {
-            let _ = 10;
-            let d: i4;
-            let _ = 10;
-            let e: u10;
-            let _ = 10;
-        } else {
-            let f: bool;
-            let _ = 10;
-            let g: i33;
-            let _ = 10;
-        }
-
-        let _ = 10;
-        let z: bool;
-    }
-
-    #[bit_field]
-    pub fn IfElseIfElseIf() {
-        let a: bool;
-        let b: bool;
-        let _ = 10;
-
-        if a {
-            let c: u3;
-            let _ = 10;
-            let d: i15;
-            let _ = 10;
-        } else if !a {
-            let _ = 10;
-            let e: i4;
-            let _ = 10;
-            let f: u10;
-        } else if b {
-            let g: bool;
-            let _ = 10;
-            let h: i33;
-            let _ = 10;
-        }
-
-        let z: bool;
-    }
-
-    #[bit_field]
-    pub fn RecursiveIf() {
-        let a: bool;
-        let b: bool;
-        let _ = 10;
-
-        if a {
-            let c: u3;
-            let _ = 10;
-            let d: i15;
-            let _ = 10;
-
-            if !a {
-                let e: i4;
-                let _ = 10;
-                let f: u10;
-                let _ = 10;
+    use crate::{cond_bit_field, BitStream, BitStreamError, BitWriter};
+
+    cond_bit_field! {
+        struct Foo {
+            foo: u8;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct Simple {
+            a: bool;
+            b: i1;
+            _: 10;
+            c: u1;
+            d: i4;
+            e: u4;
+            _: 10;
+            f: i7;
+            g: u7;
+            h: i14;
+            i: u14;
+            _: 10;
+            j: i18;
+            k: u18;
+            _: 10;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct IfWithoutElse {
+            a: bool;
+            _: 10;
+
+            if a {
+                _: 10;
+                b: u3;
+                _: 10;
+                c: i15;
+                _: 10;
+            }
+
+            _: 10;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct IfElse {
+            a: bool;
+            _: 10;
+
+            if a {
+                _: 10;
+                b: u3;
+                _: 10;
+                c: i15;
+                _: 10;
+            } else {
+                _: 10;
+                d: i4;
+                _: 10;
+                e: u10;
+                _: 10;
+            }
+
+            _: 10;
+            z: bool;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct IfElseIf {
+            a: bool;
+            _: 10;
+
+            if a {
+                _: 10;
+                b: u3;
+                _: 10;
+                c: i15;
+                _: 10;
+            } else if !a {
+                _: 10;
+                d: i4;
+                _: 10;
+                e: u10;
+                _: 10;
+            }
+
+            _: 10;
+            z: bool;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct IfElseIfElse {
+            a: bool;
+            _: 10;
+
+            if a {
+                _: 10;
+                b: u3;
+                _: 10;
+                c: i15;
+                _: 10;
+            } else if !a {
+                _: 10;
+                d: i4;
+                _: 10;
+                e: u10;
+                _: 10;
+            } else {
+                f: bool;
+                _: 10;
+                g: i33;
+                _: 10;
+            }
+
+            _: 10;
+            z: bool;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct IfElseIfElseIf {
+            a: bool;
+            b: bool;
+            _: 10;
+
+            if a {
+                c: u3;
+                _: 10;
+                d: i15;
+                _: 10;
+            } else if !a {
+                _: 10;
+                e: i4;
+                _: 10;
+                f: u10;
             } else if b {
-                let g: bool;
-                let _ = 10;
-                let h: i33;
-                let _ = 10;
+                g: bool;
+                _: 10;
+                h: i33;
+                _: 10;
             }
 
-            let _ = 10;
-            let z: bool;
+            z: bool;
+        }
+    }
+
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct RecursiveIf {
+            a: bool;
+            b: bool;
+            _: 10;
+
+            if a {
+                c: u3;
+                _: 10;
+                d: i15;
+                _: 10;
+
+                if !a {
+                    e: i4;
+                    _: 10;
+                    f: u10;
+                    _: 10;
+                } else if b {
+                    g: bool;
+                    _: 10;
+                    h: i33;
+                    _: 10;
+                }
+
+                _: 10;
+                z: bool;
+            }
         }
     }
 
-    #[bit_field]
-    pub fn EmptyForLoop() {
-        if true {
-            for _ in 0..100 {}
+    cond_bit_field! {
+        #[allow(dead_code)]
+        pub struct EmptyForLoop {
+            if true {
+                for _ in 0..100 {}
+            }
         }
     }
+
+    #[test]
+    fn read() {
+        let data = [0xffu8; 16];
+        let mut stream = BitStream::new(&data);
+        let foo: Foo = stream.read(()).unwrap();
+        assert_eq!(foo.foo, 0xff);
+
+        let data = [0u8; 16];
+        let if_else: IfElse = BitStream::new(&data).read(()).unwrap();
+        assert!(!if_else.a);
+        assert_eq!(if_else.b, None);
+        assert_eq!(if_else.d, Some(0));
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write_bit(true).unwrap();
+        writer.write_unsigned(5, 3).unwrap();
+        writer.write_signed(-3, 5).unwrap();
+        writer.skip(2).unwrap();
+        writer.write_unsigned(0x1_2345, 17).unwrap();
+        assert_eq!(writer.len(), 28);
+        writer.byte_align().unwrap();
+        assert!(writer.byte_aligned());
+
+        let data = writer.into_inner();
+        assert_eq!(data.len(), 4);
+
+        let mut stream = BitStream::new(&data);
+        assert!(stream.read_bit().unwrap());
+        assert_eq!(stream.read::<u8>(3).unwrap(), 5);
+        assert_eq!(stream.read::<i16>(5).unwrap(), -3);
+        stream.skip(2).unwrap();
+        assert_eq!(stream.read::<u32>(17).unwrap(), 0x1_2345);
+    }
+
+    #[test]
+    fn read_bytes() {
+        let data = [0b1010_1010, 0b1100_1100, 0b1111_0000];
+
+        let mut stream = BitStream::new(&data);
+        assert_eq!(&*stream.read_bytes(2).unwrap(), &data[..2]);
+        assert_eq!(&*stream.read_all(), &data[2..]);
+
+        let mut stream = BitStream::new(&data);
+        stream.skip(4).unwrap();
+        assert_eq!(&*stream.read_bytes(2).unwrap(), &[0b1010_1100, 0b1100_1111]);
+        assert!(matches!(
+            stream.read_bytes(1),
+            Err(BitStreamError::NotEnoughData)
+        ));
+    }
+
+    #[test]
+    fn write_overflow() {
+        let mut writer = BitWriter::new();
+        assert!(matches!(
+            writer.write_unsigned(8, 3),
+            Err(BitStreamError::Overflow)
+        ));
+        assert!(matches!(
+            writer.write_signed(-5, 3),
+            Err(BitStreamError::Overflow)
+        ));
+        assert!(matches!(
+            writer.write_signed(4, 3),
+            Err(BitStreamError::Overflow)
+        ));
+        assert!(matches!(
+            writer.write_unsigned(1, 129),
+            Err(BitStreamError::TooLarge)
+        ));
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn integer_round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write(&5u8, 3).unwrap();
+        writer.write(&-3i16, 5).unwrap();
+        writer.write(&0x1_2345u32, 17).unwrap();
+        assert!(matches!(
+            writer.write(&1u8, 9),
+            Err(BitStreamError::TooLarge)
+        ));
+        assert!(matches!(
+            writer.write(&-1i8, 9),
+            Err(BitStreamError::TooLarge)
+        ));
+        assert_eq!(writer.len(), 25);
+
+        let data = writer.into_inner();
+        let mut stream = BitStream::new(&data);
+        assert_eq!(stream.read::<u8>(3).unwrap(), 5);
+        assert_eq!(stream.read::<i16>(5).unwrap(), -3);
+        assert_eq!(stream.read::<u32>(17).unwrap(), 0x1_2345);
+    }
+
+    #[test]
+    fn bool_round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write(&true, ()).unwrap();
+        writer.write(&false, ()).unwrap();
+        writer.write(&true, ()).unwrap();
+        assert_eq!(writer.len(), 3);
+
+        let data = writer.into_inner();
+        assert_eq!(&*data, &[0b1010_0000]);
+
+        let mut stream = BitStream::new(&data);
+        assert!(stream.read::<bool>(()).unwrap());
+        assert!(!stream.read::<bool>(()).unwrap());
+        assert!(stream.read::<bool>(()).unwrap());
+    }
 }
@@ -39,6 +39,23 @@ impl ExprBlock {
             unshadow.to_backup(self, tokens);
         });
     }
+
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        self.brace_token.surround(tokens, |tokens| {
+            for stmt in &self.stmts {
+                stmt.to_write_tokens(tokens);
+            }
+        });
+    }
+
+    pub fn to_write_take(&self, tokens: &mut TokenStream, unshadow: &Unshadow) {
+        self.brace_token.surround(tokens, |tokens| {
+            unshadow.to_take(self, tokens);
+            for stmt in &self.stmts {
+                stmt.to_write_tokens(tokens);
+            }
+        });
+    }
 }
 
 impl Parse for ExprBlock {
@@ -53,7 +70,7 @@ impl Parse for ExprBlock {
 }
 
 impl FlatFields for ExprBlock {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         Box::new(self.stmts.iter().flat_map(|x| x.flat_fields()))
     }
 }
@@ -116,9 +133,38 @@ impl Unshadow {
         }
     }
 
+    /// Pushes clones of the values into the `Vec`s, used by loops that can `break` early.
+    ///
+    /// Values are cloned because following statements may still use them.
+    pub fn to_push(&self, fields: &impl FlatFields, tokens: &mut TokenStream) {
+        for field in fields.flat_fields() {
+            let src = &field.ident;
+            let (_, dest) = self.map.get(src).unwrap();
+            tokens.extend(quote! {#dest.push(#src.clone());});
+        }
+    }
+
     pub fn to_restore(&self, tokens: &mut TokenStream) {
         for (dest, (_, src)) in self.map.iter() {
             tokens.extend(quote! {let #dest = #src;})
         }
     }
+
+    /// Declares an iterator for each field, only used by loops when writing.
+    pub fn to_write_dec(&self, tokens: &mut TokenStream) {
+        for (src, (_, ident)) in self.map.iter() {
+            tokens.extend(quote! {
+              #[allow(non_snake_case)]
+              let mut #ident = #src.iter();
+            });
+        }
+    }
+
+    pub fn to_take(&self, fields: &impl FlatFields, tokens: &mut TokenStream) {
+        for field in fields.flat_fields() {
+            let dest = &field.ident;
+            let (field, iter) = self.map.get(dest).unwrap();
+            field.ty.to_take(tokens, dest, iter);
+        }
+    }
 }
@@ -11,6 +11,8 @@ use crate::{block::ExprBlock,
             traits::{FieldIter, FlatFields},
             ty::{ComplexType, Type}};
 
+// Tokens are kept like in `syn`'s syntax tree, even if they aren't printed
+#[allow(dead_code)]
 pub struct Skip {
     pub underscore_token: token::Underscore,
     pub colon_token: token::Colon,
@@ -30,7 +32,7 @@ impl Parse for Skip {
 }
 
 impl FlatFields for Skip {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         Box::new(iter::empty())
     }
 }
@@ -42,6 +44,7 @@ impl ToTokens for Skip {
     }
 }
 
+#[allow(dead_code)]
 pub struct ParseParen<T> {
     pub paren_token: token::Paren,
     pub content: T,
@@ -97,13 +100,28 @@ pub struct Field {
 }
 
 impl Field {
+    /// Returns whether the next tokens are a field (`[vis] ident: Type`),
+    /// instead of a statement like `ident += 1;` or `ident::function();`.
+    pub fn peek(input: ParseStream) -> bool {
+        let ahead = input.fork();
+        ahead.parse::<Visibility>().is_ok()
+            && ahead.parse::<Ident>().is_ok()
+            && ahead.peek(Token![:])
+            && !ahead.peek(Token![::])
+    }
+
     pub fn into_option(mut self) -> Self {
-        if self.default == None {
+        if self.default.is_none() {
             self.ty = ComplexType::Option(Box::new(self.ty));
         }
         self
     }
 
+    pub fn into_vec(mut self) -> Self {
+        self.ty = ComplexType::Vec(Box::new(self.ty));
+        self
+    }
+
     pub fn to_initializer(&self) -> TokenStream {
         match self.ty.inner_most() {
             Type::Bool { .. } => quote! {stream.read_bit()?},
@@ -123,6 +141,33 @@ impl Field {
             }
         }
     }
+
+    pub fn to_writer(&self) -> TokenStream {
+        let ident = &self.ident;
+        match self.ty.inner_most() {
+            Type::Bool { .. } => quote! {stream.write_bit(#ident)?},
+            Type::Number { size, .. } => {
+                if let Some(params) = &self.params {
+                    quote! {stream.write(&#ident, #params)?}
+                } else {
+                    quote! {stream.write(&#ident, #size)?}
+                }
+            }
+            Type::Struct(_) => {
+                if let Some(params) = &self.params {
+                    quote! {stream.write(&#ident, (#params))?}
+                } else {
+                    quote! {stream.write(&#ident, ())?}
+                }
+            }
+        }
+    }
+
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let writer = self.to_writer();
+        let semicolon_token = &self.semicolon_token;
+        tokens.extend(quote! {#writer #semicolon_token});
+    }
 }
 
 impl Parse for Field {
@@ -164,7 +209,7 @@ impl Parse for Field {
 }
 
 impl FlatFields for Field {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         Box::new(iter::once(self.clone()))
     }
 }
@@ -217,7 +262,7 @@ impl Parse for Struct {
                 let segments = &x.path.segments;
                 if segments.len() == 1 {
                     let first = segments.first().unwrap();
-                    if first.ident.to_string() == "extra_args" {
+                    if first.ident == "extra_args" {
                         extra_args_attribute = Some(x.clone());
                         return false;
                     }
@@ -289,7 +334,12 @@ impl ToTokens for Struct {
         });
 
         let initializers = &fields.stmts;
-        let field_names = fields.flat_fields().map(|x| x.ident);
+        let field_names = fields.flat_fields().map(|x| x.ident).collect::<Vec<_>>();
+
+        let mut writers = TokenStream::new();
+        for stmt in &fields.stmts {
+            stmt.to_write_tokens(&mut writers);
+        }
 
         let mut arg_types = Vec::<TokenStream>::new();
         let mut arg_names = Vec::<&Ident>::new();
@@ -323,6 +373,23 @@ impl ToTokens for Struct {
                     })
                 }
             }
+
+            // Walks the same layout as `read`, but takes field values from `self`.
+            // `Copy` fields are copied so conditions see the same types as when reading,
+            // other fields are borrowed.
+            impl<'a> bit_stream::BitFieldWrite<'a> for #ident {
+                type Args = (#(#arg_types),*);
+
+                fn write(&self, stream: &mut bit_stream::BitWriter, args: Self::Args) -> bit_stream::Result<()> {
+                    #[allow(unused_imports)]
+                    use bit_stream::field_value::{CopyFieldValue as _, RefFieldValue as _};
+
+                    #arg_destruction
+                    #(let #field_names = (&bit_stream::field_value::FieldValue(&self.#field_names)).get();)*
+                    #writers
+                    Ok(())
+                }
+            }
         });
     }
 }
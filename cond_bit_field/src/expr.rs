@@ -1,18 +1,17 @@
 use std::iter;
 
-use proc_macro2::TokenStream;
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
 use quote::{ToTokens, TokenStreamExt};
 use syn::{braced,
           parse::{Parse, ParseStream},
-          token, Attribute, Label, Pat, Result, Token};
+          token, Attribute, Error, Label, Lifetime, Pat, Result, Token};
 
 use crate::{block::{ExprBlock, Unshadow},
             data::{Field, Skip},
             r#if::ExprIf,
             r#match::ExprMatch,
             syn_private,
-            traits::{FieldIter, FlatFields},
-            ty::ComplexType};
+            traits::{FieldIter, FlatFields}};
 
 pub enum Expr {
     Block(ExprBlock),
@@ -20,8 +19,46 @@ pub enum Expr {
     ForLoop(ExprForLoop),
     If(ExprIf),
     Local(syn::Local),
+    Loop(ExprLoop),
     Match(ExprMatch),
     Skip(Skip),
+    Stmt(Stmt),
+    While(ExprWhile),
+}
+
+/// Any other statement, like assignments, function calls or `break`.
+///
+/// They are emitted as is, in both `read` and `write`.
+pub struct Stmt {
+    pub attrs: Vec<Attribute>,
+    pub stmt: syn::Stmt,
+}
+
+impl Parse for Stmt {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Stmt {
+            attrs: input.call(Attribute::parse_outer)?,
+            stmt: input.parse()?,
+        })
+    }
+}
+
+impl ToTokens for Stmt {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
+        self.stmt.to_tokens(tokens);
+    }
+}
+
+/// Removes the `#[write_cond(...)]` attribute from `attrs` and returns its argument.
+pub fn take_write_cond(attrs: &mut Vec<Attribute>) -> Result<Option<Box<syn::Expr>>> {
+    match attrs
+        .iter()
+        .position(|attr| attr.path.is_ident("write_cond"))
+    {
+        Some(index) => Ok(Some(Box::new(attrs.remove(index).parse_args()?))),
+        None => Ok(None),
+    }
 }
 
 impl Expr {
@@ -30,7 +67,10 @@ impl Expr {
             Expr::Local(syn::Local { attrs, .. })
             | Expr::If(ExprIf { attrs, .. })
             | Expr::ForLoop(ExprForLoop { attrs, .. })
+            | Expr::Loop(ExprLoop { attrs, .. })
+            | Expr::While(ExprWhile { attrs, .. })
             | Expr::Match(ExprMatch { attrs, .. })
+            | Expr::Stmt(Stmt { attrs, .. })
             | Expr::Block(ExprBlock { attrs, .. })
             | Expr::Field(Field { attrs, .. }) => std::mem::replace(attrs, new),
             Self::Skip(_) => Vec::new(),
@@ -42,10 +82,24 @@ impl Expr {
             return Ok(Self::Block(input.parse()?));
         }
 
-        if input.peek(token::For) {
+        // Labeled loops
+        let ahead = input.fork();
+        if input.peek(Lifetime) {
+            let _: Label = ahead.parse()?;
+        }
+
+        if ahead.peek(token::For) {
             return Ok(Self::ForLoop(input.parse()?));
         }
 
+        if ahead.peek(token::While) {
+            return Ok(Self::While(input.parse()?));
+        }
+
+        if ahead.peek(token::Loop) {
+            return Ok(Self::Loop(input.parse()?));
+        }
+
         if input.peek(token::If) {
             return Ok(Self::If(input.parse()?));
         }
@@ -66,7 +120,28 @@ impl Expr {
             return Ok(Self::Skip(input.parse()?));
         }
 
-        Ok(Expr::Field(input.parse()?))
+        if Field::peek(input) {
+            return Ok(Self::Field(input.parse()?));
+        }
+
+        Ok(Self::Stmt(input.parse()?))
+    }
+}
+
+impl Expr {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Self::Block(block) => block.to_write_tokens(tokens),
+            Self::Field(field) => field.to_write_tokens(tokens),
+            Self::ForLoop(expr_for_loop) => expr_for_loop.to_write_tokens(tokens),
+            Self::If(expr_if) => expr_if.to_write_tokens(tokens),
+            Self::Local(expr_let) => expr_let.to_tokens(tokens),
+            Self::Loop(expr_loop) => expr_loop.to_write_tokens(tokens),
+            Self::Match(expr_match) => expr_match.to_write_tokens(tokens),
+            Self::Skip(skip) => skip.to_tokens(tokens),
+            Self::Stmt(stmt) => stmt.to_tokens(tokens),
+            Self::While(expr_while) => expr_while.to_write_tokens(tokens),
+        }
     }
 }
 
@@ -76,20 +151,29 @@ impl Parse for Expr {
         let mut atom = Self::unary_expr(input)?;
         attrs.extend(atom.replace_attrs(Vec::new()));
         atom.replace_attrs(attrs);
+        match &mut atom {
+            Self::If(expr_if) => expr_if.take_write_cond()?,
+            Self::While(expr_while) => {
+                expr_while.write_cond = take_write_cond(&mut expr_while.attrs)?
+            }
+            _ => {}
+        }
         Ok(atom)
     }
 }
 
 impl FlatFields for Expr {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         match self {
             Self::Block(block) => block.flat_fields(),
             Self::Field(field) => field.flat_fields(),
             Self::ForLoop(expr_for_loop) => expr_for_loop.flat_fields(),
             Self::If(expr_if) => expr_if.flat_fields(),
-            Self::Local(..) => Box::new(iter::empty()),
+            Self::Local(..) | Self::Stmt(..) => Box::new(iter::empty()),
+            Self::Loop(expr_loop) => expr_loop.flat_fields(),
             Self::Match(expr_match) => expr_match.flat_fields(),
             Self::Skip(skip) => skip.flat_fields(),
+            Self::While(expr_while) => expr_while.flat_fields(),
         }
     }
 }
@@ -102,8 +186,11 @@ impl ToTokens for Expr {
             Self::ForLoop(expr_for_loop) => expr_for_loop.to_tokens(tokens),
             Self::If(expr_if) => expr_if.to_tokens(tokens),
             Self::Local(expr_let) => expr_let.to_tokens(tokens),
+            Self::Loop(expr_loop) => expr_loop.to_tokens(tokens),
             Self::Match(expr_match) => expr_match.to_tokens(tokens),
             Self::Skip(skip) => skip.to_tokens(tokens),
+            Self::Stmt(stmt) => stmt.to_tokens(tokens),
+            Self::While(expr_while) => expr_while.to_tokens(tokens),
         }
     }
 }
@@ -153,12 +240,8 @@ impl Parse for ExprForLoop {
 }
 
 impl FlatFields for ExprForLoop {
-    fn flat_fields<'a>(&'a self) -> FieldIter {
-        Box::new(self.body.flat_fields().map(|x| {
-            let mut x = x;
-            x.ty = ComplexType::Vec(Box::new(x.ty.clone()));
-            x
-        }))
+    fn flat_fields(&self) -> FieldIter<'_> {
+        Box::new(self.body.flat_fields().map(Field::into_vec))
     }
 }
 
@@ -183,3 +266,314 @@ impl ToTokens for ExprForLoop {
         unshadow.to_restore(tokens);
     }
 }
+
+impl ExprForLoop {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+        unshadow.to_write_dec(tokens);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.label.to_tokens(tokens);
+        self.for_token.to_tokens(tokens);
+        self.pat.to_tokens(tokens);
+        self.in_token.to_tokens(tokens);
+        syn_private::printing::wrap_bare_struct(tokens, &self.expr);
+
+        self.body.brace_token.surround(tokens, |tokens| {
+            syn_private::printing::inner_attrs_to_tokens(&self.attrs, tokens);
+            unshadow.to_take(&self.body, tokens);
+            for stmt in &self.body.stmts {
+                stmt.to_write_tokens(tokens);
+            }
+        });
+    }
+}
+
+/// Parses `{ #![inner_attrs] stmts }` of a loop body,
+/// returns the inner attributes and the body.
+fn parse_loop_body(input: ParseStream) -> Result<(Vec<Attribute>, ExprBlock)> {
+    let content;
+    let brace_token = braced!(content in input);
+    let inner_attrs = content.call(Attribute::parse_inner)?;
+    let stmts = content.call(ExprBlock::parse_within)?;
+
+    Ok((
+        inner_attrs,
+        ExprBlock {
+            attrs: Vec::new(),
+            brace_token,
+            stmts,
+        },
+    ))
+}
+
+/// Returns the span of the first `break` or `continue` in `tokens` that targets the loop
+/// labeled `label`: unlabeled ones outside of nested loops, and ones with `label`.
+fn find_loop_exit(tokens: TokenStream, label: Option<&Lifetime>, nested: bool) -> Option<Span> {
+    let mut tokens = tokens.into_iter().peekable();
+    // Whether the next `{ ... }` is the body of a nested loop
+    let mut loop_body_next = false;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "break" || ident == "continue" => {
+                let target = match tokens.peek() {
+                    Some(TokenTree::Punct(punct)) if punct.as_char() == '\'' => {
+                        tokens.next();
+                        match tokens.next() {
+                            Some(TokenTree::Ident(name)) => Some(name),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let exits = match (target, label) {
+                    (None, _) => !nested,
+                    (Some(target), Some(label)) => target == label.ident,
+                    (Some(_), None) => false,
+                };
+                if exits {
+                    return Some(ident.span());
+                }
+            }
+            TokenTree::Ident(ident) if ident == "for" || ident == "loop" || ident == "while" => {
+                loop_body_next = true;
+            }
+            TokenTree::Group(group) => {
+                let brace = group.delimiter() == Delimiter::Brace;
+                let span = find_loop_exit(group.stream(), label, nested || loop_body_next && brace);
+                if span.is_some() {
+                    return span;
+                }
+                if brace {
+                    loop_body_next = false;
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Values of a statement are pushed after it, so a `break` or `continue` inside a statement
+/// that declares fields would drop the values that statement has read in that iteration.
+fn check_loop_body(label: &Option<Label>, body: &ExprBlock) -> Result<()> {
+    let label = label.as_ref().map(|label| &label.name);
+    for stmt in &body.stmts {
+        if stmt.flat_fields().next().is_none() {
+            continue;
+        }
+
+        if let Some(span) = find_loop_exit(stmt.to_token_stream(), label, false) {
+            return Err(Error::new(
+                span,
+                "`break` and `continue` can't be in a statement that declares fields of the loop, \
+                 move the fields out of this statement",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `while` and `loop` bodies can be left by `break` at any statement,
+/// so values are pushed after each statement, instead of at the end of the body.
+fn loop_body_to_tokens(
+    attrs: &[Attribute],
+    body: &ExprBlock,
+    unshadow: &Unshadow,
+    tokens: &mut TokenStream,
+) {
+    body.brace_token.surround(tokens, |tokens| {
+        syn_private::printing::inner_attrs_to_tokens(attrs, tokens);
+        for stmt in &body.stmts {
+            stmt.to_tokens(tokens);
+            unshadow.to_push(stmt, tokens);
+        }
+    });
+}
+
+/// Reverse of `loop_body_to_tokens`, takes values before each statement.
+fn loop_body_to_write_tokens(
+    attrs: &[Attribute],
+    body: &ExprBlock,
+    unshadow: &Unshadow,
+    tokens: &mut TokenStream,
+) {
+    body.brace_token.surround(tokens, |tokens| {
+        syn_private::printing::inner_attrs_to_tokens(attrs, tokens);
+        for stmt in &body.stmts {
+            unshadow.to_take(stmt, tokens);
+            stmt.to_write_tokens(tokens);
+        }
+    });
+}
+
+pub struct ExprWhile {
+    pub attrs: Vec<Attribute>,
+    pub label: Option<Label>,
+    pub while_token: Token![while],
+    pub cond: Box<syn::Expr>,
+    /// Extended: condition used by `BitFieldWrite::write` instead of `cond`,
+    /// from `#[write_cond(...)]` attribute.
+    pub write_cond: Option<Box<syn::Expr>>,
+    pub body: ExprBlock,
+}
+
+impl Parse for ExprWhile {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let outer_attrs = input.call(Attribute::parse_outer)?;
+        let label: Option<Label> = input.parse()?;
+        let while_token: Token![while] = input.parse()?;
+        let cond = input.call(syn::Expr::parse_without_eager_brace)?;
+        let (inner_attrs, body) = parse_loop_body(input)?;
+        check_loop_body(&label, &body)?;
+
+        Ok(ExprWhile {
+            attrs: syn_private::private::attrs(outer_attrs, inner_attrs),
+            label,
+            while_token,
+            cond: Box::new(cond),
+            write_cond: None,
+            body,
+        })
+    }
+}
+
+impl FlatFields for ExprWhile {
+    fn flat_fields(&self) -> FieldIter<'_> {
+        Box::new(self.body.flat_fields().map(Field::into_vec))
+    }
+}
+
+impl ToTokens for ExprWhile {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+        unshadow.to_dec(tokens);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.label.to_tokens(tokens);
+        self.while_token.to_tokens(tokens);
+        syn_private::printing::wrap_bare_struct(tokens, &self.cond);
+        loop_body_to_tokens(&self.attrs, &self.body, &unshadow, tokens);
+
+        unshadow.to_restore(tokens);
+    }
+}
+
+impl ExprWhile {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+        unshadow.to_write_dec(tokens);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.label.to_tokens(tokens);
+        self.while_token.to_tokens(tokens);
+        let cond = self.write_cond.as_ref().unwrap_or(&self.cond);
+        syn_private::printing::wrap_bare_struct(tokens, cond);
+        loop_body_to_write_tokens(&self.attrs, &self.body, &unshadow, tokens);
+    }
+}
+
+pub struct ExprLoop {
+    pub attrs: Vec<Attribute>,
+    pub label: Option<Label>,
+    pub loop_token: Token![loop],
+    pub body: ExprBlock,
+}
+
+impl Parse for ExprLoop {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let outer_attrs = input.call(Attribute::parse_outer)?;
+        let label: Option<Label> = input.parse()?;
+        let loop_token: Token![loop] = input.parse()?;
+        let (inner_attrs, body) = parse_loop_body(input)?;
+        check_loop_body(&label, &body)?;
+
+        Ok(ExprLoop {
+            attrs: syn_private::private::attrs(outer_attrs, inner_attrs),
+            label,
+            loop_token,
+            body,
+        })
+    }
+}
+
+impl FlatFields for ExprLoop {
+    fn flat_fields(&self) -> FieldIter<'_> {
+        Box::new(self.body.flat_fields().map(Field::into_vec))
+    }
+}
+
+impl ToTokens for ExprLoop {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+        unshadow.to_dec(tokens);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.label.to_tokens(tokens);
+        self.loop_token.to_tokens(tokens);
+        loop_body_to_tokens(&self.attrs, &self.body, &unshadow, tokens);
+
+        unshadow.to_restore(tokens);
+    }
+}
+
+impl ExprLoop {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+        unshadow.to_write_dec(tokens);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.label.to_tokens(tokens);
+        self.loop_token.to_tokens(tokens);
+        loop_body_to_write_tokens(&self.attrs, &self.body, &unshadow, tokens);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse::Parser;
+
+    use super::Expr;
+    use crate::data::Struct;
+
+    fn parse(input: &str) -> syn::Result<Struct> {
+        syn::parse_str(input)
+    }
+
+    fn parse_expr(input: &str) -> Expr {
+        Expr::unary_expr.parse_str(input).unwrap()
+    }
+
+    #[test]
+    fn field_or_stmt() {
+        assert!(matches!(parse_expr("a: u2;"), Expr::Field(_)));
+        assert!(matches!(parse_expr("pub(crate) a: u2;"), Expr::Field(_)));
+        assert!(matches!(parse_expr("let mut a = 0;"), Expr::Local(_)));
+        // Assignments and expression statements
+        assert!(matches!(parse_expr("a += 1;"), Expr::Stmt(_)));
+        assert!(matches!(parse_expr("a = b;"), Expr::Stmt(_)));
+        assert!(matches!(parse_expr("a::check(b);"), Expr::Stmt(_)));
+        assert!(matches!(parse_expr("assert!(a < 3);"), Expr::Stmt(_)));
+    }
+
+    #[test]
+    fn loop_exit_between_fields() {
+        assert!(parse("struct A { loop { a: u2; if a == 0 { break; } b: bool; } }").is_ok());
+        assert!(parse("struct A { while true { a: u2; if a == 0 { continue; } } }").is_ok());
+        // Exits of nested loops
+        assert!(parse("struct A { loop { for _ in 0..2 { a: u2; break; } break; } }").is_ok());
+        assert!(parse("struct A { 'a: loop { loop { b: u1; break; } break 'a; } }").is_ok());
+    }
+
+    #[test]
+    fn loop_exit_with_fields() {
+        let message = |input| parse(input).err().unwrap().to_string();
+        assert!(
+            message("struct A { loop { a: u2; if a == 0 { b: bool; break; } } }")
+                .starts_with("`break` and `continue` can't be in a statement that declares fields")
+        );
+        assert!(parse("struct A { while true { if true { a: u2; continue; } } }").is_err());
+        // Labeled exit of the outer loop from a nested loop
+        assert!(parse("struct A { 'a: loop { loop { b: u1; break 'a; } } }").is_err());
+    }
+}
@@ -5,6 +5,7 @@ use syn::{parse::{Parse, ParseStream},
 
 use crate::{block::{ExprBlock, Unshadow},
             data::Field,
+            expr,
             traits::{FieldIter, FlatFields}};
 
 pub enum ElseIf {
@@ -26,7 +27,7 @@ impl Parse for ElseIf {
 }
 
 impl FlatFields for ElseIf {
-    fn flat_fields<'a>(&'a self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         // All `if`s in `if {} else if {} else if {}` are treated as in the same level
         // So don't wrap another `ComplexType::Option` around them
         match self {
@@ -40,16 +41,30 @@ pub struct ExprIf {
     pub attrs: Vec<Attribute>,
     pub if_token: token::If,
     pub cond: Box<syn::Expr>,
+    /// Extended: condition used by `BitFieldWrite::write` instead of `cond`,
+    /// from `#[write_cond(...)]` attribute.
+    ///
+    /// Required when `cond` depends on the `BitStream`,
+    /// for example `if stream.more_rbsp_data() {}`.
+    pub write_cond: Option<Box<syn::Expr>>,
     pub then_branch: ExprBlock,
     pub else_branch: Option<(token::Else, Box<ElseIf>)>,
 }
 
+impl ExprIf {
+    pub fn take_write_cond(&mut self) -> Result<()> {
+        self.write_cond = expr::take_write_cond(&mut self.attrs)?;
+        Ok(())
+    }
+}
+
 impl Parse for ExprIf {
     fn parse(input: ParseStream) -> Result<Self> {
         Ok(ExprIf {
             attrs: input.call(Attribute::parse_outer)?,
             if_token: input.parse()?,
             cond: Box::new(input.call(syn::Expr::parse_without_eager_brace)?),
+            write_cond: None,
             then_branch: input.parse()?,
             else_branch: {
                 if let Some(else_token) = input.parse::<Option<token::Else>>()? {
@@ -63,7 +78,7 @@ impl Parse for ExprIf {
 }
 
 impl FlatFields for ExprIf {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         let mut iterators: Vec<FieldIter> = vec![Box::new(
             self.then_branch.flat_fields().map(Field::into_option),
         )];
@@ -121,3 +136,47 @@ impl ToTokens for ExprIf {
         unshadow.to_restore(tokens);
     }
 }
+
+impl ExprIf {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let mut expr_if = self;
+
+        let unshadow = Unshadow::new(self);
+
+        loop {
+            let Self {
+                attrs,
+                if_token,
+                cond,
+                write_cond,
+                then_branch,
+                else_branch,
+            } = expr_if;
+
+            let cond = write_cond.as_ref().unwrap_or(cond);
+            tokens.extend(quote! {
+              #(#attrs)* #if_token #cond
+            });
+
+            then_branch.to_write_take(tokens, &unshadow);
+
+            match else_branch {
+                Some((else_token, else_block)) => {
+                    else_token.to_tokens(tokens);
+
+                    match else_block.as_ref() {
+                        ElseIf::Block(block) => {
+                            block.to_write_take(tokens, &unshadow);
+                            break;
+                        }
+                        ElseIf::If(nested) => {
+                            expr_if = nested;
+                            continue;
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -39,17 +39,39 @@ impl Arm {
         }
         self.comma.to_tokens(tokens);
     }
+
+    pub fn to_write_take(&self, tokens: &mut TokenStream, unshadow: &Unshadow) {
+        tokens.append_all(&self.attrs);
+        self.pat.to_tokens(tokens);
+        if let Some((if_token, guard)) = &self.guard {
+            if_token.to_tokens(tokens);
+            guard.to_tokens(tokens);
+        }
+        self.fat_arrow_token.to_tokens(tokens);
+        match &*self.body {
+            Expr::Block(block) => block.to_write_take(tokens, unshadow),
+            _ => token::Brace::default().surround(tokens, |tokens| {
+                unshadow.to_take(self, tokens);
+                self.body.to_write_tokens(tokens);
+            }),
+        }
+        self.comma.to_tokens(tokens);
+    }
 }
 
 // https://docs.rs/syn/1.0.60/src/syn/expr.rs.html#1049
 pub fn requires_terminator(expr: &Expr) -> bool {
     // see https://github.com/rust-lang/rust/blob/2679c38fc/src/librustc_ast/util/classify.rs#L7-L25
-    match expr {
-        Expr::Block(..) | Expr::If(..) | Expr::Match(..) | Expr::ForLoop(..) | Expr::Field(..) => {
-            false
-        }
-        _ => true,
-    }
+    !matches!(
+        expr,
+        Expr::Block(..)
+            | Expr::If(..)
+            | Expr::Match(..)
+            | Expr::ForLoop(..)
+            | Expr::While(..)
+            | Expr::Loop(..)
+            | Expr::Field(..)
+    )
 }
 
 impl Parse for Arm {
@@ -85,7 +107,7 @@ impl Parse for Arm {
 }
 
 impl FlatFields for Arm {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         Box::new(self.body.flat_fields().map(Field::into_option))
     }
 }
@@ -124,7 +146,7 @@ impl Parse for ExprMatch {
 }
 
 impl FlatFields for ExprMatch {
-    fn flat_fields(&self) -> FieldIter {
+    fn flat_fields(&self) -> FieldIter<'_> {
         Box::new(self.arms.iter().flat_map(|x| x.flat_fields()))
     }
 }
@@ -154,3 +176,24 @@ impl ToTokens for ExprMatch {
         unshadow.to_restore(tokens);
     }
 }
+
+impl ExprMatch {
+    pub fn to_write_tokens(&self, tokens: &mut TokenStream) {
+        let unshadow = Unshadow::new(self);
+
+        syn_private::printing::outer_attrs_to_tokens(&self.attrs, tokens);
+        self.match_token.to_tokens(tokens);
+        syn_private::printing::wrap_bare_struct(tokens, &self.expr);
+        self.brace_token.surround(tokens, |tokens| {
+            syn_private::printing::inner_attrs_to_tokens(&self.attrs, tokens);
+            for (i, arm) in self.arms.iter().enumerate() {
+                arm.to_write_take(tokens, &unshadow);
+                let is_last = i == self.arms.len() - 1;
+                if !is_last && requires_terminator(&arm.body) && arm.comma.is_none() {
+                    <Token![,]>::default().to_tokens(tokens);
+                }
+            }
+        });
+        tokens.extend(quote! {;});
+    }
+}
@@ -0,0 +1,81 @@
+//! Helpers copied from `syn` 1.0, they are not exported by `syn`.
+
+pub mod pat {
+    pub mod parsing {
+        use syn::{parse::ParseStream, punctuated::Punctuated, Pat, PatOr, Result, Token};
+
+        // https://docs.rs/syn/1.0.60/src/syn/pat.rs.html#773
+        pub fn multi_pat_with_leading_vert(input: ParseStream) -> Result<Pat> {
+            let leading_vert: Option<Token![|]> = input.parse()?;
+            multi_pat_impl(input, leading_vert)
+        }
+
+        fn multi_pat_impl(input: ParseStream, leading_vert: Option<Token![|]>) -> Result<Pat> {
+            let mut pat: Pat = input.parse()?;
+            if leading_vert.is_some()
+                || input.peek(Token![|]) && !input.peek(Token![||]) && !input.peek(Token![|=])
+            {
+                let mut cases = Punctuated::new();
+                cases.push_value(pat);
+                while input.peek(Token![|]) && !input.peek(Token![||]) && !input.peek(Token![|=]) {
+                    let punct = input.parse()?;
+                    cases.push_punct(punct);
+                    let pat: Pat = input.parse()?;
+                    cases.push_value(pat);
+                }
+                pat = Pat::Or(PatOr {
+                    attrs: Vec::new(),
+                    leading_vert,
+                    cases,
+                });
+            }
+            Ok(pat)
+        }
+    }
+}
+
+pub mod private {
+    use syn::Attribute;
+
+    // https://docs.rs/syn/1.0.60/src/syn/attr.rs.html#512
+    pub fn attrs(outer: Vec<Attribute>, inner: Vec<Attribute>) -> Vec<Attribute> {
+        let mut attrs = outer;
+        attrs.extend(inner);
+        attrs
+    }
+}
+
+pub mod printing {
+    use proc_macro2::TokenStream;
+    use quote::ToTokens;
+    use syn::{AttrStyle, Attribute, Expr};
+
+    // https://docs.rs/syn/1.0.60/src/syn/expr.rs.html#2929
+    pub fn outer_attrs_to_tokens(attrs: &[Attribute], tokens: &mut TokenStream) {
+        for attr in attrs {
+            if let AttrStyle::Outer = attr.style {
+                attr.to_tokens(tokens);
+            }
+        }
+    }
+
+    // https://docs.rs/syn/1.0.60/src/syn/expr.rs.html#2937
+    pub fn inner_attrs_to_tokens(attrs: &[Attribute], tokens: &mut TokenStream) {
+        for attr in attrs {
+            if let AttrStyle::Inner(_) = attr.style {
+                attr.to_tokens(tokens);
+            }
+        }
+    }
+
+    // https://docs.rs/syn/1.0.60/src/syn/expr.rs.html#2906
+    pub fn wrap_bare_struct(tokens: &mut TokenStream, e: &Expr) {
+        if let Expr::Struct(_) = *e {
+            syn::token::Paren::default().surround(tokens, |tokens| {
+                e.to_tokens(tokens);
+            });
+        } else {
+            e.to_tokens(tokens);
+        }
+    }
+}
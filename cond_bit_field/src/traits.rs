@@ -0,0 +1,8 @@
+use crate::data::Field;
+
+pub type FieldIter<'a> = Box<dyn Iterator<Item = Field> + 'a>;
+
+/// Collects the fields declared in a syntax node and all its children.
+pub trait FlatFields {
+    fn flat_fields(&self) -> FieldIter<'_>;
+}
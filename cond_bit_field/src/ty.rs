@@ -2,7 +2,7 @@ use proc_macro2::{Ident, Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned, ToTokens, TokenStreamExt};
 use syn::{parse::{Parse, ParseStream},
           spanned::Spanned,
-          Error, PathArguments, Result, Type, TypePath};
+          Error, PathArguments, Result, TypePath};
 
 #[derive(Clone)]
 pub enum SimpleFieldType {
@@ -11,22 +11,24 @@ pub enum SimpleFieldType {
     Struct(TypePath),
 }
 
+pub type Type = SimpleFieldType;
+
 impl SimpleFieldType {
-    pub fn parse(ty: &Type) -> syn::Result<Self> {
+    pub fn parse(ty: &syn::Type) -> syn::Result<Self> {
         let type_path = match ty {
-            Type::Path(type_path) => Ok(type_path.clone()),
+            syn::Type::Path(type_path) => Ok(type_path.clone()),
             _ => Err(syn::Error::new_spanned(
                 ty,
                 "field's type must be a type path",
             )),
         }?;
 
-        if type_path.qself != None {
+        if type_path.qself.is_some() {
             return Ok(SimpleFieldType::Struct(type_path));
         }
 
         let path = &type_path.path;
-        if path.leading_colon != None {
+        if path.leading_colon.is_some() {
             return Ok(SimpleFieldType::Struct(type_path));
         }
 
@@ -69,6 +71,13 @@ impl SimpleFieldType {
     }
 }
 
+impl Parse for SimpleFieldType {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ty: syn::Type = input.parse()?;
+        Self::parse(&ty)
+    }
+}
+
 impl ToTokens for SimpleFieldType {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
@@ -145,6 +154,29 @@ impl ComplexType {
             },
         }
     }
+
+    /// Reverse of `to_backup`, used when writing.
+    ///
+    /// Shadows `ident` with the value for current branch or iteration,
+    /// `iter` is the iterator created by `Unshadow::to_write_dec` for `Vec`s.
+    pub fn to_take(&self, tokens: &mut TokenStream, ident: &Ident, iter: &Ident) {
+        match self {
+            Self::Simple(_) => {}
+            Self::Vec(_) => tokens.extend(quote! {
+                let #ident = (&bit_stream::field_value::FieldValue(
+                    #iter.next().ok_or(bit_stream::BitStreamError::MissingValue)?
+                )).get();
+            }),
+            Self::Option(inner) => match &**inner {
+                Self::Option(_) => {}
+                _ => tokens.extend(quote! {
+                    let #ident = (&bit_stream::field_value::FieldValue(
+                        #ident.as_ref().ok_or(bit_stream::BitStreamError::MissingValue)?
+                    )).get();
+                }),
+            },
+        }
+    }
 }
 
 impl ToTokens for ComplexType {
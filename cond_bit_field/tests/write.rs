@@ -0,0 +1,291 @@
+use bit_stream::{cond_bit_field, BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter};
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct If {
+        a: bool;
+
+        if a {
+            b: u3;
+        } else {
+            c: u5;
+        }
+
+        d: u2;
+    }
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct Match {
+        kind: u2;
+
+        match kind {
+            0 => {}
+            1 => {
+                a: u4;
+            }
+            _ => {
+                b: bool;
+                c: u3;
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct For {
+        count: u2;
+
+        for _ in 0..count {
+            a: u3;
+
+            if a > 3 {
+                b: bool;
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct WriteCond {
+        a: u4;
+
+        #[write_cond(b.is_some())]
+        if stream.remaining() != 0 {
+            b: u4;
+        }
+    }
+}
+
+/// Writes `value`, checks the written bits, then reads them back.
+fn round_trip<T>(value: &T, data: &[u8], len: usize)
+where
+    T: for<'a> BitField<'a, Args = ()> + for<'a> BitFieldWrite<'a, Args = ()> + PartialEq,
+    T: std::fmt::Debug,
+{
+    let mut writer = BitWriter::new();
+    writer.write(value, ()).unwrap();
+    assert_eq!(writer.len(), len);
+    assert_eq!(&*writer.into_inner(), data);
+
+    let read: T = BitStream::new(data).read(()).unwrap();
+    assert_eq!(&read, value);
+}
+
+fn write<T: for<'a> BitFieldWrite<'a, Args = ()>>(value: &T) -> bit_stream::Result<()> {
+    BitWriter::new().write(value, ())
+}
+
+#[test]
+fn write_if() {
+    let value = If {
+        a: true,
+        b: Some(5),
+        c: None,
+        d: 2,
+    };
+    round_trip(&value, &[0b1101_1000], 6);
+
+    let value = If {
+        a: false,
+        b: None,
+        c: Some(17),
+        d: 1,
+    };
+    round_trip(&value, &[0b0100_0101], 8);
+
+    // The field of the taken branch is missing
+    let value = If {
+        a: true,
+        b: None,
+        c: Some(17),
+        d: 1,
+    };
+    assert!(matches!(write(&value), Err(BitStreamError::MissingValue)));
+}
+
+#[test]
+fn write_match() {
+    let value = Match {
+        kind: 0,
+        a: None,
+        b: None,
+        c: None,
+    };
+    round_trip(&value, &[0], 2);
+
+    let value = Match {
+        kind: 1,
+        a: Some(9),
+        b: None,
+        c: None,
+    };
+    round_trip(&value, &[0b0110_0100], 6);
+
+    let value = Match {
+        kind: 3,
+        a: None,
+        b: Some(true),
+        c: Some(2),
+    };
+    round_trip(&value, &[0b1110_1000], 6);
+
+    let value = Match {
+        kind: 2,
+        a: None,
+        b: Some(true),
+        c: None,
+    };
+    assert!(matches!(write(&value), Err(BitStreamError::MissingValue)));
+}
+
+#[test]
+fn write_for() {
+    let value = For {
+        count: 2,
+        a: vec![5, 1],
+        b: vec![Some(true), None],
+    };
+    round_trip(&value, &[0b1010_1100, 0b1000_0000], 9);
+
+    // One iteration more than values
+    let value = For {
+        count: 3,
+        a: vec![5, 1],
+        b: vec![Some(true), None, None],
+    };
+    assert!(matches!(write(&value), Err(BitStreamError::MissingValue)));
+}
+
+#[test]
+fn write_cond() {
+    let value = WriteCond { a: 10, b: Some(3) };
+    round_trip(&value, &[0b1010_0011], 8);
+
+    // `write_cond` skips the fields that are read until the end of the stream
+    let mut writer = BitWriter::new();
+    writer.write(&WriteCond { a: 10, b: None }, ()).unwrap();
+    assert_eq!(writer.len(), 4);
+    assert_eq!(&*writer.into_inner(), &[0b1010_0000]);
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct Loop {
+        loop {
+            a: u2;
+            if a == 0 {
+                break;
+            }
+            b: bool;
+        }
+    }
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct While {
+        let mut count = 0;
+        while count < 3 {
+            a: u2;
+            count += 1;
+            if a == 3 {
+                break;
+            }
+            b: u2;
+        }
+    }
+}
+
+#[test]
+fn write_loop() {
+    // The last iteration breaks before `b`
+    let value = Loop {
+        a: vec![2, 1, 0],
+        b: vec![true, false],
+    };
+    round_trip(&value, &[0b1010_1000], 8);
+
+    let value = Loop {
+        a: vec![2, 1],
+        b: vec![true, false],
+    };
+    assert!(matches!(write(&value), Err(BitStreamError::MissingValue)));
+}
+
+#[test]
+fn write_while() {
+    let value = While {
+        a: vec![1, 3],
+        b: vec![2],
+    };
+    round_trip(&value, &[0b0110_1100], 6);
+
+    // Ends by its condition
+    let value = While {
+        a: vec![1, 2, 0],
+        b: vec![1, 1, 1],
+    };
+    round_trip(&value, &[0b0101_1001, 0b0001_0000], 12);
+}
+
+fn check_total(total: u8, a: u8) {
+    assert_eq!(total, a);
+}
+
+cond_bit_field! {
+    #[derive(Debug, PartialEq)]
+    struct Stmts {
+        let mut total = 0;
+        a: u2;
+        // Runs after `a` is read or written
+        total += a;
+        check_total(total, a);
+        b: u2;
+        total += b;
+
+        if total > 3 {
+            c: u3;
+        }
+
+        total = b;
+        assert!(total < 4);
+
+        if total == 0 {
+            d: bool;
+        }
+    }
+}
+
+#[test]
+fn write_stmts() {
+    let value = Stmts {
+        a: 3,
+        b: 1,
+        c: Some(5),
+        d: None,
+    };
+    round_trip(&value, &[0b1101_1010], 7);
+
+    let value = Stmts {
+        a: 1,
+        b: 0,
+        c: None,
+        d: Some(true),
+    };
+    round_trip(&value, &[0b0100_1000], 5);
+
+    // `total` is 3, so `c` isn't written even if present
+    let mut writer = BitWriter::new();
+    let value = Stmts {
+        a: 2,
+        b: 1,
+        c: Some(5),
+        d: None,
+    };
+    writer.write(&value, ()).unwrap();
+    assert_eq!(writer.len(), 4);
+}
@@ -0,0 +1,440 @@
+use bit_stream::BitStreamError;
+use serde::Serialize;
+
+use crate::{nal_unit::{Caption, NalUnitHeader, SliceHeader},
+            NalUnit, NalUnitPayload, PictureOrderCount, RefPicLists};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Decoder, NalUnitStream, NalUnitStreamError};
+
+/// NAL units of an access unit in decoding order, containing one primary coded picture
+///
+/// § 7.4.1.2.3 Order of NAL units and coded pictures and association to access units
+#[derive(Clone, Debug, Serialize)]
+pub struct AccessUnit {
+    pub nal_units: Vec<NalUnit>,
+    /// Picture order count of the primary coded picture, `None` if the access unit has no
+    /// coded slice of a primary coded picture, which only happens at the end of a truncated
+    /// stream.
+    pub picture_order_count: Option<PictureOrderCount>,
+    /// Reference picture lists of the coded slices and slice data partitions A of the primary
+    /// coded picture, in decoding order
+    pub ref_pic_lists: Vec<RefPicLists>,
+}
+
+impl AccessUnit {
+    /// Returns the slice header of the first VCL NAL unit of the primary coded picture
+    pub fn primary_slice_header(&self) -> Option<&SliceHeader> {
+        self.nal_units.iter().find_map(primary_slice_header)
+    }
+
+    /// Returns caption byte pairs from the ATSC A/53 `cc_data` of all SEI NAL units, in
+    /// decoding order.
+    pub fn captions(&self) -> Result<Vec<Caption>, BitStreamError> {
+        let mut captions = Vec::new();
+        for unit in &self.nal_units {
+            if let NalUnitPayload::SupplementalEnhancementInformation(sei) = &unit.payload {
+                captions.extend(sei.captions()?);
+            }
+        }
+        Ok(captions)
+    }
+}
+
+/// Returns the slice header of a coded slice or slice data partition A of a primary coded
+/// picture, `None` for other NAL units
+///
+/// Slices with redundant_pic_cnt greater than 0 belong to redundant coded pictures.
+///
+/// § 7.4.3 Slice header semantics
+pub fn primary_slice_header(unit: &NalUnit) -> Option<&SliceHeader> {
+    let slice_header = match &unit.payload {
+        NalUnitPayload::Slice(slice) | NalUnitPayload::IdrSlice(slice) => &slice.slice_header,
+        NalUnitPayload::SliceDataPartitionA(partition) => &partition.slice_header,
+        _ => return None,
+    };
+    match slice_header.redundant_pic_cnt {
+        Some(redundant_pic_cnt) if redundant_pic_cnt.0 > 0 => None,
+        _ => Some(slice_header),
+    }
+}
+
+/// Returns whether the coded slice with `header` is the first VCL NAL unit of a new primary
+/// coded picture, when the previous VCL NAL unit of a primary coded picture has
+/// `previous_header`.
+///
+/// § 7.4.1.2.4 Detection of the first VCL NAL unit of a primary coded picture
+pub fn is_first_vcl_nal_unit(
+    (previous_nal_unit_header, previous_header): (&NalUnitHeader, &SliceHeader),
+    (nal_unit_header, header): (&NalUnitHeader, &SliceHeader),
+) -> bool {
+    // pic_order_cnt_lsb and delta_pic_order_cnt_bottom are only present for
+    // pic_order_cnt_type 0, delta_pic_order_cnt_0 and delta_pic_order_cnt_1 for
+    // pic_order_cnt_type 1
+    previous_header.frame_num != header.frame_num
+        || previous_header.pic_parameter_set_id != header.pic_parameter_set_id
+        || previous_header.field_pic_flag != header.field_pic_flag
+        || previous_header.bottom_field_flag != header.bottom_field_flag
+        || (previous_nal_unit_header.ref_idc == 0) != (nal_unit_header.ref_idc == 0)
+        || previous_header.pic_order_cnt_lsb != header.pic_order_cnt_lsb
+        || previous_header.delta_pic_order_cnt_bottom != header.delta_pic_order_cnt_bottom
+        || previous_header.delta_pic_order_cnt_0 != header.delta_pic_order_cnt_0
+        || previous_header.delta_pic_order_cnt_1 != header.delta_pic_order_cnt_1
+        || previous_header.idr_pic_id != header.idr_pic_id
+}
+
+/// Groups the NAL units of a byte stream into access units.
+///
+/// The first of any access unit delimiter, SEI, sequence parameter set (and its extension),
+/// picture parameter set or NAL unit with nal_unit_type 14 to 18 after the last VCL NAL unit of
+/// a primary coded picture, or the first VCL NAL unit of a new primary coded picture, starts a
+/// new access unit. An end of sequence or end of stream NAL unit ends the current access unit.
+/// Other NAL units belong to the current access unit.
+///
+/// A NAL unit that fails to decode is yielded as an error and skipped, an invalid byte stream
+/// ends the iteration after the error.
+///
+/// § 7.4.1.2.3 Order of NAL units and coded pictures and association to access units
+#[cfg(not(target_arch = "wasm32"))]
+pub struct AccessUnitStream {
+    nal_unit_stream: NalUnitStream,
+    decoder: Decoder,
+    /// The access unit being grouped
+    current: AccessUnit,
+    /// Index in `current` of the last VCL NAL unit of the primary coded picture
+    last_primary_index: Option<usize>,
+    /// NAL units after the last VCL NAL unit of `current`, which belong to the next access unit
+    /// unless a VCL NAL unit of the same primary coded picture follows them
+    pending: Vec<NalUnit>,
+    finished: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AccessUnitStream {
+    pub fn new(byte_stream: Box<[u8]>) -> Self {
+        Self {
+            nal_unit_stream: NalUnitStream::new(byte_stream),
+            decoder: Decoder::new(),
+            current: AccessUnit {
+                nal_units: vec![],
+                picture_order_count: None,
+                ref_pic_lists: vec![],
+            },
+            last_primary_index: None,
+            pending: vec![],
+            finished: false,
+        }
+    }
+
+    /// The decoder with the parameter sets received so far
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Ends `current` and starts a new access unit with the pending NAL units
+    fn take_current(&mut self) -> AccessUnit {
+        self.last_primary_index = None;
+        let nal_units = std::mem::take(&mut self.pending);
+        std::mem::replace(
+            &mut self.current,
+            AccessUnit {
+                nal_units,
+                picture_order_count: None,
+                ref_pic_lists: vec![],
+            },
+        )
+    }
+
+    /// Adds `unit` to `current`, returns the previous access unit if `unit` starts a new one.
+    fn push(&mut self, unit: NalUnit) -> Result<Option<AccessUnit>, NalUnitStreamError> {
+        let mut result = None;
+
+        if let Some(header) = primary_slice_header(&unit) {
+            let first = match self.last_primary_index {
+                Some(index) => {
+                    let previous = &self.current.nal_units[index];
+                    // Unwrap: `last_primary_index` always points to a primary coded slice
+                    let previous_header = primary_slice_header(previous).unwrap();
+                    is_first_vcl_nal_unit(
+                        (&previous.header, previous_header),
+                        (&unit.header, header),
+                    )
+                }
+                None => true,
+            };
+
+            if first {
+                let picture_order_count = self.decoder.start_picture(&unit.header, header)?;
+                if self.last_primary_index.is_some() {
+                    result = Some(self.take_current());
+                }
+                self.current.picture_order_count = Some(picture_order_count);
+            }
+            let ref_pic_lists = self.decoder.decode_ref_pic_lists(header)?;
+            self.current.ref_pic_lists.push(ref_pic_lists);
+            self.current.nal_units.append(&mut self.pending);
+            self.last_primary_index = Some(self.current.nal_units.len());
+            self.current.nal_units.push(unit);
+            return Ok(result);
+        }
+
+        match unit.header.ty {
+            6..=9 | 13..=18 => {
+                // An access unit delimiter is always the first NAL unit of an access unit
+                if unit.header.ty == 9 && self.last_primary_index.is_some() {
+                    result = Some(self.take_current());
+                }
+                if self.last_primary_index.is_some() {
+                    self.pending.push(unit);
+                } else {
+                    self.current.nal_units.push(unit);
+                }
+            }
+            _ => {
+                let end = matches!(unit.header.ty, 10 | 11);
+                self.current.nal_units.append(&mut self.pending);
+                self.current.nal_units.push(unit);
+                // End of sequence and end of stream are the last NAL units of an access unit
+                if end {
+                    result = Some(self.take_current());
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Iterator for AccessUnitStream {
+    type Item = Result<AccessUnit, NalUnitStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.nal_unit_stream.next(&mut self.decoder) {
+                Ok(Some(unit)) => match self.push(unit) {
+                    Ok(Some(access_unit)) => return Some(Ok(access_unit)),
+                    Ok(None) => {}
+                    Err(err) => return Some(Err(err)),
+                },
+                Ok(None) => self.finished = true,
+                Err(err @ NalUnitStreamError::PayloadError(_)) => return Some(Err(err)),
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        self.current.nal_units.append(&mut self.pending);
+        if self.current.nal_units.is_empty() {
+            return None;
+        }
+        Some(Ok(self.take_current()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::BitWriter;
+
+    use crate::{nal_unit::{Caption, CcType, SequenceParameterSet, UnsignedExpGolombCode},
+                test::{test_sequence_parameter_set, SliceHeaderBuilder, PPS_DATA},
+                AccessUnitStream};
+
+    fn start_nal_unit(writer: &mut BitWriter, header: u8) {
+        for byte in [0u8, 0, 0, 1, header] {
+            writer.write(&byte, 8).unwrap();
+        }
+    }
+
+    fn rbsp_trailing_bits(writer: &mut BitWriter) {
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+    }
+
+    /// Coded slice of a 2x1 macroblock picture, P slices only have skipped macroblocks and I
+    /// slices have I_16x16_0_0_0 macroblocks
+    fn slice(writer: &mut BitWriter, header: u8, first_mb_in_slice: u64, frame_num: u8) {
+        let (ty, ref_idc) = (header & 0x1f, header >> 5);
+        let idr = ty == 5;
+        let mut builder = SliceHeaderBuilder::new(ty, ref_idc, if idr { 7 } else { 5 }, frame_num);
+        builder.first_mb_in_slice = first_mb_in_slice;
+        start_nal_unit(writer, builder.nal_unit_header_byte());
+        builder.write(writer);
+
+        if idr {
+            for _ in first_mb_in_slice..2 {
+                // mb_type, intra_chroma_pred_mode, mb_qp_delta and the coeff_token of
+                // Intra16x16DCLevel
+                writer.write(&UnsignedExpGolombCode(1), ()).unwrap();
+                for _ in 0..3 {
+                    writer.write_bit(true).unwrap();
+                }
+            }
+        } else {
+            // mb_skip_run
+            writer
+                .write(&UnsignedExpGolombCode(2 - first_mb_in_slice), ())
+                .unwrap();
+        }
+        rbsp_trailing_bits(writer);
+    }
+
+    /// SEI with one user_data_registered_itu_t_t35 message with a field 1 caption byte pair
+    fn sei_caption(writer: &mut BitWriter, cc_data_1: u8, cc_data_2: u8) {
+        start_nal_unit(writer, 0x06);
+        #[rustfmt::skip]
+        let data = [
+            // user_data_registered_itu_t_t35, ATSC, "GA94", cc_data
+            4, 14, 0xb5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03,
+            // process_cc_data_flag, cc_count 1, em_data
+            0xc1, 0xff,
+            // field 1 pair, marker_bits, rbsp_trailing_bits
+            0xfc, cc_data_1, cc_data_2, 0xff, 0x80,
+        ];
+        for byte in data {
+            writer.write(&byte, 8).unwrap();
+        }
+    }
+
+    /// The SPS of `test_sequence_parameter_set` resized to 2x1 macroblocks
+    fn sequence_parameter_set() -> SequenceParameterSet {
+        let mut sps = test_sequence_parameter_set();
+        sps.pic_width_in_mbs_minus1 = UnsignedExpGolombCode(1);
+        sps.pic_height_in_map_units_minus1 = UnsignedExpGolombCode(0);
+        sps.frame_cropping_flag = false;
+        sps.frame_crop_left_offset = None;
+        sps.frame_crop_right_offset = None;
+        sps.frame_crop_top_offset = None;
+        sps.frame_crop_bottom_offset = None;
+        sps
+    }
+
+    #[test]
+    fn access_unit_stream() {
+        let sps = sequence_parameter_set();
+        let mut writer = BitWriter::new();
+        // Access unit delimiter, primary_pic_type 0
+        start_nal_unit(&mut writer, 0x09);
+        writer.write(&0u8, 3).unwrap();
+        rbsp_trailing_bits(&mut writer);
+        start_nal_unit(&mut writer, 0x67);
+        writer.write(&sps, ()).unwrap();
+        rbsp_trailing_bits(&mut writer);
+        start_nal_unit(&mut writer, 0x68);
+        for byte in PPS_DATA {
+            writer.write(&byte, 8).unwrap();
+        }
+        slice(&mut writer, 0x65, 0, 0);
+        // A picture with two slices
+        slice(&mut writer, 0x41, 0, 1);
+        slice(&mut writer, 0x41, 1, 1);
+        // The PPS starts the next access unit
+        start_nal_unit(&mut writer, 0x68);
+        for byte in PPS_DATA {
+            writer.write(&byte, 8).unwrap();
+        }
+        slice(&mut writer, 0x01, 0, 2);
+        // Only nal_ref_idc differs from the previous non-reference picture
+        slice(&mut writer, 0x41, 0, 2);
+        start_nal_unit(&mut writer, 0x0b);
+
+        let access_units = AccessUnitStream::new(writer.into_inner())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let summary: Vec<_> = access_units
+            .iter()
+            .map(|x| {
+                let types: Vec<_> = x.nal_units.iter().map(|x| x.header.ty).collect();
+                (types, x.picture_order_count.unwrap().pic_order_cnt())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (vec![9, 7, 8, 5], 0),
+                (vec![1, 1], 2),
+                (vec![8, 1], 3),
+                (vec![1, 11], 4),
+            ]
+        );
+        assert_eq!(access_units[1].primary_slice_header().unwrap().frame_num, 1);
+        // Both slices of the second picture refer to the IDR picture
+        for lists in &access_units[1].ref_pic_lists {
+            let picture = lists.ref_pic_list0[0].unwrap();
+            assert_eq!((picture.frame_num, picture.pic_order_cnt), (0, Some(0)));
+        }
+    }
+
+    #[test]
+    fn access_unit_end_of_sequence() {
+        let mut writer = BitWriter::new();
+        start_nal_unit(&mut writer, 0x67);
+        writer.write(&sequence_parameter_set(), ()).unwrap();
+        rbsp_trailing_bits(&mut writer);
+        start_nal_unit(&mut writer, 0x68);
+        for byte in PPS_DATA {
+            writer.write(&byte, 8).unwrap();
+        }
+        slice(&mut writer, 0x65, 0, 0);
+        start_nal_unit(&mut writer, 0x0a);
+        // Would continue the previous picture if the end of sequence didn't end it
+        slice(&mut writer, 0x65, 0, 0);
+        start_nal_unit(&mut writer, 0x0b);
+        start_nal_unit(&mut writer, 0x09);
+        writer.write(&0u8, 3).unwrap();
+        rbsp_trailing_bits(&mut writer);
+
+        let access_units = AccessUnitStream::new(writer.into_inner())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let types: Vec<Vec<_>> = access_units
+            .iter()
+            .map(|x| x.nal_units.iter().map(|x| x.header.ty).collect())
+            .collect();
+        assert_eq!(types, [vec![7, 8, 5, 10], vec![5, 11], vec![9]]);
+        assert!(access_units[1].picture_order_count.is_some());
+    }
+
+    #[test]
+    fn access_unit_captions() {
+        let mut writer = BitWriter::new();
+        start_nal_unit(&mut writer, 0x67);
+        writer.write(&sequence_parameter_set(), ()).unwrap();
+        rbsp_trailing_bits(&mut writer);
+        start_nal_unit(&mut writer, 0x68);
+        for byte in PPS_DATA {
+            writer.write(&byte, 8).unwrap();
+        }
+        sei_caption(&mut writer, 0x94, 0x2c);
+        sei_caption(&mut writer, 0x94, 0x20);
+        slice(&mut writer, 0x65, 0, 0);
+        sei_caption(&mut writer, 0xc1, 0xc2);
+        slice(&mut writer, 0x41, 0, 1);
+
+        let access_units = AccessUnitStream::new(writer.into_inner())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let captions: Vec<Vec<_>> = access_units
+            .iter()
+            .map(|x| {
+                x.captions()
+                    .unwrap()
+                    .into_iter()
+                    .map(|x| (x.cc_data_1, x.cc_data_2))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            captions,
+            [vec![(0x94, 0x2c), (0x94, 0x20)], vec![(0xc1, 0xc2)]]
+        );
+        assert_eq!(
+            access_units[1].captions().unwrap(),
+            vec![Caption {
+                cc_type: CcType::Ntsc608Field1,
+                cc_data_1: 0xc1,
+                cc_data_2: 0xc2,
+            }]
+        );
+    }
+}
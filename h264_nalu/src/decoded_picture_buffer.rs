@@ -0,0 +1,1031 @@
+use bit_stream::{BitStreamError, Result};
+use serde::Serialize;
+
+use crate::{nal_unit::{DecRefPicMarking, MemoryManagementControlOperation, NalUnitHeader,
+                       RefPicListModificationOperation, SequenceParameterSet, SliceHeader,
+                       SliceTypeName},
+            PictureOrderCount};
+
+/// Reference marking of a decoded field
+///
+/// § 8.2.5 Decoded reference picture marking process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ReferenceMarking {
+    Unused,
+    ShortTerm,
+    LongTerm,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DecodedField {
+    /// TopFieldOrderCnt or BottomFieldOrderCnt, `None` for a non-existing frame
+    pub field_order_cnt: Option<i64>,
+    pub marking: ReferenceMarking,
+}
+
+/// A reference frame, complementary reference field pair or non-paired reference field in the
+/// decoded picture buffer
+#[derive(Clone, Debug, Serialize)]
+pub struct DecodedFrame {
+    pub frame_num: u64,
+    /// LongTermFrameIdx, `None` unless a field is marked as used for long-term reference
+    pub long_term_frame_idx: Option<u64>,
+    /// Inferred for a gap in frame_num
+    ///
+    /// § 8.2.5.2 Decoding process for gaps in frame_num
+    pub non_existing: bool,
+    pub top_field: Option<DecodedField>,
+    pub bottom_field: Option<DecodedField>,
+}
+
+impl DecodedFrame {
+    fn field(&self, bottom_field: bool) -> Option<&DecodedField> {
+        if bottom_field {
+            self.bottom_field.as_ref()
+        } else {
+            self.top_field.as_ref()
+        }
+    }
+
+    fn field_mut(&mut self, bottom_field: bool) -> &mut Option<DecodedField> {
+        if bottom_field {
+            &mut self.bottom_field
+        } else {
+            &mut self.top_field
+        }
+    }
+
+    fn fields(&self) -> impl Iterator<Item = &DecodedField> {
+        self.top_field.iter().chain(self.bottom_field.iter())
+    }
+
+    /// Returns whether the field of `bottom_field` parity, or both fields for `None`, are
+    /// marked with `marking`
+    fn is_marked(&self, bottom_field: Option<bool>, marking: ReferenceMarking) -> bool {
+        let is_field_marked = |bottom_field| {
+            self.field(bottom_field)
+                .is_some_and(|field| field.marking == marking)
+        };
+        match bottom_field {
+            Some(bottom_field) => is_field_marked(bottom_field),
+            None => is_field_marked(false) && is_field_marked(true),
+        }
+    }
+
+    fn has_marking(&self, marking: ReferenceMarking) -> bool {
+        self.fields().any(|field| field.marking == marking)
+    }
+
+    pub fn is_reference(&self) -> bool {
+        self.fields()
+            .any(|field| field.marking != ReferenceMarking::Unused)
+    }
+
+    /// Marks the field of `bottom_field` parity, or both fields for `None`, having `marking` as
+    /// unused for reference
+    fn unmark(&mut self, bottom_field: Option<bool>, marking: ReferenceMarking) {
+        for bottom in [false, true] {
+            if bottom_field.is_some_and(|x| x != bottom) {
+                continue;
+            }
+            if let Some(field) = self.field_mut(bottom) {
+                if field.marking == marking {
+                    field.marking = ReferenceMarking::Unused;
+                }
+            }
+        }
+        if !self.has_marking(ReferenceMarking::LongTerm) {
+            self.long_term_frame_idx = None;
+        }
+    }
+
+    /// PicOrderCnt( ) of the field of `bottom_field` parity, or the smaller one of the fields
+    /// marked with `marking` for `None`
+    fn pic_order_cnt(&self, bottom_field: Option<bool>, marking: ReferenceMarking) -> Option<i64> {
+        match bottom_field {
+            Some(bottom_field) => self.field(bottom_field)?.field_order_cnt,
+            None => self
+                .fields()
+                .filter(|field| field.marking == marking)
+                .filter_map(|field| field.field_order_cnt)
+                .min(),
+        }
+    }
+}
+
+/// An entry of RefPicList0 or RefPicList1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ReferencePicture {
+    pub frame_num: u64,
+    /// `None` for a frame or complementary field pair, the parity of a field otherwise
+    pub bottom_field: Option<bool>,
+    /// `None` for a short-term reference picture
+    pub long_term_frame_idx: Option<u64>,
+    /// PicOrderCnt( ), `None` for a non-existing frame
+    pub pic_order_cnt: Option<i64>,
+    pub non_existing: bool,
+}
+
+/// RefPicList0 and RefPicList1 of a slice, with num_ref_idx_l0_active_minus1 + 1 and
+/// num_ref_idx_l1_active_minus1 + 1 entries. "no reference picture" entries are `None`.
+///
+/// § 8.2.4 Decoding process for reference picture lists construction
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RefPicLists {
+    pub ref_pic_list0: Vec<Option<ReferencePicture>>,
+    pub ref_pic_list1: Vec<Option<ReferencePicture>>,
+}
+
+/// A frame or field in `DecodedPictureBuffer::frames`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PictureIndex {
+    index: usize,
+    bottom_field: Option<bool>,
+}
+
+/// The picture being decoded
+#[derive(Clone, Debug)]
+struct CurrentPicture {
+    frame_num: u64,
+    /// MaxFrameNum
+    max_frame_num: u64,
+    max_num_ref_frames: u64,
+    /// `None` for a frame
+    bottom_field: Option<bool>,
+    picture_order_count: PictureOrderCount,
+    reference: bool,
+    idr: bool,
+    dec_ref_pic_marking: Option<DecRefPicMarking>,
+    /// Index of the first field when the current picture is the second field of a
+    /// complementary reference field pair
+    first_field_index: Option<usize>,
+}
+
+/// A reference field that can be the first field of a complementary reference field pair
+#[derive(Clone, Copy, Debug)]
+struct FirstField {
+    index: usize,
+    frame_num: u64,
+    bottom_field: bool,
+}
+
+/// Reference pictures of a bitstream, without sample values or output order
+///
+/// `start_picture` must be called with the first slice of each primary coded picture in
+/// decoding order, the previous picture is marked when the next one starts or
+/// `finish_picture` is called.
+///
+/// § 8.2.4 Decoding process for reference picture lists construction
+///
+/// § 8.2.5 Decoded reference picture marking process
+#[derive(Clone, Debug, Default)]
+pub struct DecodedPictureBuffer {
+    frames: Vec<DecodedFrame>,
+    current: Option<CurrentPicture>,
+    first_field: Option<FirstField>,
+    /// PrevRefFrameNum
+    prev_ref_frame_num: u64,
+}
+
+impl DecodedPictureBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frames with at least one field marked as used for reference, in decoding order
+    pub fn frames(&self) -> &[DecodedFrame] {
+        &self.frames
+    }
+
+    /// Finishes the previous picture, then infers the frames for a gap in frame_num before the
+    /// picture containing the slice with `header`.
+    ///
+    /// Frames are inferred even when gaps_in_frame_num_value_allowed_flag is 0, as the
+    /// decoder should infer an unintentional picture loss.
+    ///
+    /// The inferred frames aren't passed to [`PictureOrderCountDecoder`], so they have no field
+    /// order counts. The picture order count of the next pictures doesn't depend on them, as
+    /// frame_num wraps at most once across a gap and FrameNumOffset is the same either way.
+    ///
+    /// § 8.2.5.2 Decoding process for gaps in frame_num
+    ///
+    /// [`PictureOrderCountDecoder`]: crate::PictureOrderCountDecoder
+    pub fn start_picture(
+        &mut self,
+        seq_parameter_set: &SequenceParameterSet,
+        nal_unit_header: &NalUnitHeader,
+        header: &SliceHeader,
+        picture_order_count: PictureOrderCount,
+    ) {
+        self.finish_picture();
+
+        let frame_num = header.frame_num as u64;
+        let max_frame_num = seq_parameter_set.max_frame_num();
+        let max_num_ref_frames = seq_parameter_set.max_num_ref_frames.0;
+        let bottom_field = header.bottom_field_flag;
+        let reference = nal_unit_header.ref_idc != 0;
+        let idr = header.idr_pic_id.is_some();
+
+        // Only the picture right after a first field can be its second field
+        let first_field_index = self
+            .first_field
+            .take()
+            .filter(|first_field| {
+                reference
+                    && first_field.frame_num == frame_num
+                    && bottom_field == Some(!first_field.bottom_field)
+            })
+            .map(|first_field| first_field.index);
+
+        if idr {
+            self.prev_ref_frame_num = 0;
+        } else if frame_num != self.prev_ref_frame_num
+            && frame_num != (self.prev_ref_frame_num + 1) % max_frame_num
+        {
+            let mut unused_short_term_frame_num = (self.prev_ref_frame_num + 1) % max_frame_num;
+            while unused_short_term_frame_num != frame_num {
+                self.sliding_window(
+                    unused_short_term_frame_num,
+                    max_frame_num,
+                    max_num_ref_frames,
+                );
+                let field = DecodedField {
+                    field_order_cnt: None,
+                    marking: ReferenceMarking::ShortTerm,
+                };
+                self.frames.push(DecodedFrame {
+                    frame_num: unused_short_term_frame_num,
+                    long_term_frame_idx: None,
+                    non_existing: true,
+                    top_field: Some(field),
+                    bottom_field: Some(field),
+                });
+                self.frames.retain(DecodedFrame::is_reference);
+                self.prev_ref_frame_num = unused_short_term_frame_num;
+                unused_short_term_frame_num = (unused_short_term_frame_num + 1) % max_frame_num;
+            }
+        }
+
+        self.current = Some(CurrentPicture {
+            frame_num,
+            max_frame_num,
+            max_num_ref_frames,
+            bottom_field,
+            picture_order_count,
+            reference,
+            idr,
+            dec_ref_pic_marking: header.dec_ref_pic_marking.clone(),
+            first_field_index,
+        });
+    }
+
+    /// Marks the reference pictures after decoding the current picture, and stores it if it's
+    /// a reference picture.
+    ///
+    /// § 8.2.5.1 Sequence of operations for decoded reference picture marking process
+    pub fn finish_picture(&mut self) {
+        let mut current = match self.current.take() {
+            Some(current) => current,
+            None => return,
+        };
+        if !current.reference {
+            return;
+        }
+
+        let mut frame_num = current.frame_num;
+        let mut long_term_frame_idx = None;
+        let dec_ref_pic_marking = current.dec_ref_pic_marking.take();
+        if current.idr {
+            // Both fields of an IDR picture are IDR pictures
+            if current.first_field_index.is_none() {
+                self.frames.clear();
+            }
+            if dec_ref_pic_marking
+                .as_ref()
+                .is_some_and(|x| x.long_term_reference_flag == Some(true))
+            {
+                long_term_frame_idx = Some(0);
+            }
+        } else if let Some(operations) =
+            dec_ref_pic_marking.and_then(|x| x.memory_management_control_operations)
+        {
+            for operation in &operations {
+                self.apply_memory_management_control_operation(&current, operation);
+                match operation.memory_management_control_operation.0 {
+                    5 => {
+                        // § 8.2.1 Decoding process for picture order count, the picture is
+                        // inferred to have had frame_num equal to 0. The first field, if any,
+                        // is no longer a reference picture.
+                        frame_num = 0;
+                        current.first_field_index = None;
+                    }
+                    6 => long_term_frame_idx = operation.long_term_frame_idx.map(|x| x.0),
+                    _ => {}
+                }
+            }
+        } else if !current
+            .first_field_index
+            .is_some_and(|index| self.frames[index].has_marking(ReferenceMarking::ShortTerm))
+        {
+            self.sliding_window(
+                current.frame_num,
+                current.max_frame_num,
+                current.max_num_ref_frames,
+            );
+        }
+
+        let marking = if long_term_frame_idx.is_some() {
+            ReferenceMarking::LongTerm
+        } else {
+            ReferenceMarking::ShortTerm
+        };
+        let field = |field_order_cnt| DecodedField {
+            field_order_cnt: Some(field_order_cnt),
+            marking,
+        };
+        let top_field = current.picture_order_count.top_field_order_cnt.map(field);
+        let bottom_field = current
+            .picture_order_count
+            .bottom_field_order_cnt
+            .map(field);
+
+        match current.first_field_index {
+            Some(index) => {
+                let frame = &mut self.frames[index];
+                frame.top_field = frame.top_field.or(top_field);
+                frame.bottom_field = frame.bottom_field.or(bottom_field);
+                frame.long_term_frame_idx = frame.long_term_frame_idx.or(long_term_frame_idx);
+            }
+            None => self.frames.push(DecodedFrame {
+                frame_num,
+                long_term_frame_idx,
+                non_existing: false,
+                top_field,
+                bottom_field,
+            }),
+        }
+        self.frames.retain(DecodedFrame::is_reference);
+
+        if let (Some(bottom_field), None) = (current.bottom_field, current.first_field_index) {
+            self.first_field = Some(FirstField {
+                index: self.frames.len() - 1,
+                frame_num,
+                bottom_field,
+            });
+        }
+        self.prev_ref_frame_num = frame_num;
+    }
+
+    /// § 8.2.5.3 Sliding window decoded reference picture marking process
+    fn sliding_window(&mut self, frame_num: u64, max_frame_num: u64, max_num_ref_frames: u64) {
+        loop {
+            let num_short_term = self
+                .frames
+                .iter()
+                .filter(|frame| frame.has_marking(ReferenceMarking::ShortTerm))
+                .count() as u64;
+            let num_long_term = self
+                .frames
+                .iter()
+                .filter(|frame| frame.has_marking(ReferenceMarking::LongTerm))
+                .count() as u64;
+            if num_short_term == 0 || num_short_term + num_long_term < max_num_ref_frames.max(1) {
+                return;
+            }
+
+            // Unwrap: `num_short_term` isn't 0
+            let frame = self
+                .frames
+                .iter_mut()
+                .filter(|frame| frame.has_marking(ReferenceMarking::ShortTerm))
+                .min_by_key(|frame| frame_num_wrap(frame, frame_num, max_frame_num))
+                .unwrap();
+            frame.unmark(None, ReferenceMarking::ShortTerm);
+        }
+    }
+
+    /// § 8.2.5.4 Adaptive memory control decoded reference picture marking process
+    fn apply_memory_management_control_operation(
+        &mut self,
+        current: &CurrentPicture,
+        operation: &MemoryManagementControlOperation,
+    ) {
+        let numbers = PictureNumbers::new(current);
+        // (8-39)
+        let pic_num_x = || {
+            numbers.curr_pic_num
+                - (operation
+                    .difference_of_pic_nums_minus1
+                    .map_or(0, |x| x.0 as i64)
+                    + 1)
+        };
+
+        match operation.memory_management_control_operation.0 {
+            // § 8.2.5.4.1 Marking process of a short-term reference picture as "unused for
+            // reference"
+            1 => {
+                if let Some(picture) = numbers.find_short_term(&self.frames, pic_num_x()) {
+                    self.frames[picture.index]
+                        .unmark(picture.bottom_field, ReferenceMarking::ShortTerm);
+                }
+            }
+            // § 8.2.5.4.2 Marking process of a long-term reference picture as "unused for
+            // reference"
+            2 => {
+                let long_term_pic_num = operation.long_term_pic_num.map_or(0, |x| x.0 as i64);
+                if let Some(picture) = numbers.find_long_term(&self.frames, long_term_pic_num) {
+                    self.frames[picture.index]
+                        .unmark(picture.bottom_field, ReferenceMarking::LongTerm);
+                }
+            }
+            // § 8.2.5.4.3 Assignment process of a LongTermFrameIdx to a short-term reference
+            // picture
+            3 => {
+                let long_term_frame_idx = operation.long_term_frame_idx.map_or(0, |x| x.0);
+                if let Some(picture) = numbers.find_short_term(&self.frames, pic_num_x()) {
+                    self.unmark_long_term_frame_idx(long_term_frame_idx, Some(picture.index));
+
+                    let frame = &mut self.frames[picture.index];
+                    for bottom_field in [false, true] {
+                        if picture.bottom_field.is_some_and(|x| x != bottom_field) {
+                            continue;
+                        }
+                        if let Some(field) = frame.field_mut(bottom_field) {
+                            field.marking = ReferenceMarking::LongTerm;
+                        }
+                    }
+                    frame.long_term_frame_idx = Some(long_term_frame_idx);
+                }
+            }
+            // § 8.2.5.4.4 Marking process of long-term reference pictures having
+            // LongTermFrameIdx greater than max_long_term_frame_idx_plus1 − 1
+            4 => {
+                let max_long_term_frame_idx_plus1 =
+                    operation.max_long_term_frame_idx_plus1.map_or(0, |x| x.0);
+                for frame in &mut self.frames {
+                    if frame
+                        .long_term_frame_idx
+                        .is_some_and(|x| x + 1 > max_long_term_frame_idx_plus1)
+                    {
+                        frame.unmark(None, ReferenceMarking::LongTerm);
+                    }
+                }
+            }
+            // § 8.2.5.4.5 Marking process of all reference pictures as "unused for reference"
+            5 => self.frames.clear(),
+            // § 8.2.5.4.6 Process for assigning a long-term frame index to the current picture
+            6 => {
+                let long_term_frame_idx = operation.long_term_frame_idx.map_or(0, |x| x.0);
+                self.unmark_long_term_frame_idx(long_term_frame_idx, current.first_field_index);
+            }
+            _ => {}
+        }
+    }
+
+    /// Marks the long-term reference pictures with `long_term_frame_idx` as unused for
+    /// reference, except the fields of the frame with `except_index`
+    fn unmark_long_term_frame_idx(
+        &mut self,
+        long_term_frame_idx: u64,
+        except_index: Option<usize>,
+    ) {
+        for (index, frame) in self.frames.iter_mut().enumerate() {
+            if Some(index) != except_index && frame.long_term_frame_idx == Some(long_term_frame_idx)
+            {
+                frame.unmark(None, ReferenceMarking::LongTerm);
+            }
+        }
+    }
+
+    /// Constructs RefPicList0 and RefPicList1 of a slice of the current picture, with
+    /// num_ref_idx_l0_active_minus1 + 1 and num_ref_idx_l1_active_minus1 + 1 entries.
+    ///
+    /// Returns `InvalidValue` if a list would have more than 32 entries.
+    ///
+    /// § 8.2.4 Decoding process for reference picture lists construction
+    pub fn ref_pic_lists(
+        &self,
+        header: &SliceHeader,
+        num_ref_idx_l0_active: usize,
+        num_ref_idx_l1_active: usize,
+    ) -> Result<RefPicLists> {
+        // § 7.4.3 Slice header semantics
+        if num_ref_idx_l0_active > 32 || num_ref_idx_l1_active > 32 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        let current = self.current.as_ref().ok_or(BitStreamError::MissingValue)?;
+        let numbers = PictureNumbers::new(current);
+
+        let slice_type_name = header.slice_type_name()?;
+        let (mut ref_pic_list0, mut ref_pic_list1) = match slice_type_name {
+            SliceTypeName::P | SliceTypeName::SP => (self.initial_p_ref_pic_list(&numbers), vec![]),
+            SliceTypeName::B => self.initial_b_ref_pic_lists(current, &numbers),
+            SliceTypeName::I | SliceTypeName::SI => return Ok(RefPicLists::default()),
+        };
+
+        let modification = header.ref_pic_list_modification.as_ref();
+        modify_ref_pic_list(
+            &self.frames,
+            &numbers,
+            &mut ref_pic_list0,
+            num_ref_idx_l0_active,
+            modification.and_then(|x| x.ref_pic_list_modification_l0.as_deref()),
+        )?;
+        if slice_type_name == SliceTypeName::B {
+            modify_ref_pic_list(
+                &self.frames,
+                &numbers,
+                &mut ref_pic_list1,
+                num_ref_idx_l1_active,
+                modification.and_then(|x| x.ref_pic_list_modification_l1.as_deref()),
+            )?;
+        }
+
+        let to_reference_picture =
+            |picture: Option<PictureIndex>| picture.map(|picture| self.reference_picture(picture));
+        Ok(RefPicLists {
+            ref_pic_list0: ref_pic_list0
+                .into_iter()
+                .map(to_reference_picture)
+                .collect(),
+            ref_pic_list1: ref_pic_list1
+                .into_iter()
+                .map(to_reference_picture)
+                .collect(),
+        })
+    }
+
+    /// § 8.2.4.2.1 Initialization process for the reference picture list for P and SP slices
+    /// in frames
+    ///
+    /// § 8.2.4.2.2 Initialization process for the reference picture list for P and SP slices
+    /// in fields
+    fn initial_p_ref_pic_list(&self, numbers: &PictureNumbers) -> Vec<Option<PictureIndex>> {
+        let mut short_term = self.frame_indices(numbers.bottom_field, ReferenceMarking::ShortTerm);
+        // Descending PicNum, or FrameNumWrap for fields
+        short_term.sort_by_key(|&index| {
+            -frame_num_wrap(
+                &self.frames[index],
+                numbers.frame_num,
+                numbers.max_frame_num,
+            )
+        });
+        let long_term = self.long_term_frame_indices(numbers.bottom_field);
+
+        let mut list = self.fields(
+            numbers.bottom_field,
+            &short_term,
+            ReferenceMarking::ShortTerm,
+        );
+        list.extend(self.fields(numbers.bottom_field, &long_term, ReferenceMarking::LongTerm));
+        list.into_iter().map(Some).collect()
+    }
+
+    /// § 8.2.4.2.3 Initialization process for reference picture lists for B slices in frames
+    ///
+    /// § 8.2.4.2.4 Initialization process for reference picture lists for B slices in fields
+    fn initial_b_ref_pic_lists(
+        &self,
+        current: &CurrentPicture,
+        numbers: &PictureNumbers,
+    ) -> (Vec<Option<PictureIndex>>, Vec<Option<PictureIndex>>) {
+        let short_term = self.frame_indices(numbers.bottom_field, ReferenceMarking::ShortTerm);
+        let pic_order_cnt =
+            |index: &usize| self.frames[*index].pic_order_cnt(None, ReferenceMarking::ShortTerm);
+        let curr_pic_order_cnt = Some(current.picture_order_count.pic_order_cnt());
+
+        // Pictures before the current one, the first field of the current frame is before it
+        let (mut before, mut after): (Vec<_>, Vec<_>) =
+            short_term
+                .into_iter()
+                .partition(|index| match numbers.bottom_field {
+                    Some(_) => pic_order_cnt(index) <= curr_pic_order_cnt,
+                    None => pic_order_cnt(index) < curr_pic_order_cnt,
+                });
+        before.sort_by_key(|index| std::cmp::Reverse(pic_order_cnt(index)));
+        after.sort_by_key(pic_order_cnt);
+        let long_term = self.long_term_frame_indices(numbers.bottom_field);
+
+        let list = |first: &[usize], second: &[usize]| {
+            let short_term: Vec<_> = first.iter().chain(second).copied().collect();
+            let mut list = self.fields(
+                numbers.bottom_field,
+                &short_term,
+                ReferenceMarking::ShortTerm,
+            );
+            list.extend(self.fields(numbers.bottom_field, &long_term, ReferenceMarking::LongTerm));
+            list.into_iter().map(Some).collect::<Vec<_>>()
+        };
+        let ref_pic_list0 = list(&before, &after);
+        let mut ref_pic_list1 = list(&after, &before);
+        if ref_pic_list1.len() > 1 && ref_pic_list1 == ref_pic_list0 {
+            ref_pic_list1.swap(0, 1);
+        }
+        (ref_pic_list0, ref_pic_list1)
+    }
+
+    /// Indices of the frames with both fields marked with `marking` when decoding a frame, or
+    /// with any field marked with `marking` when decoding a field
+    fn frame_indices(&self, bottom_field: Option<bool>, marking: ReferenceMarking) -> Vec<usize> {
+        (0..self.frames.len())
+            .filter(|&index| {
+                let frame = &self.frames[index];
+                match bottom_field {
+                    Some(_) => frame.has_marking(marking),
+                    None => frame.is_marked(None, marking),
+                }
+            })
+            .collect()
+    }
+
+    /// Indices of the long-term reference frames in ascending LongTermPicNum, or
+    /// LongTermFrameIdx for fields
+    fn long_term_frame_indices(&self, bottom_field: Option<bool>) -> Vec<usize> {
+        let mut indices = self.frame_indices(bottom_field, ReferenceMarking::LongTerm);
+        indices.sort_by_key(|&index| self.frames[index].long_term_frame_idx);
+        indices
+    }
+
+    /// The frames of `indices` when decoding a frame, or their fields marked with `marking`
+    /// alternating from the same parity as the current field when decoding a field
+    ///
+    /// § 8.2.4.2.5 Initialization process for reference picture lists in fields
+    fn fields(
+        &self,
+        bottom_field: Option<bool>,
+        indices: &[usize],
+        marking: ReferenceMarking,
+    ) -> Vec<PictureIndex> {
+        let bottom_field = match bottom_field {
+            Some(bottom_field) => bottom_field,
+            None => {
+                return indices
+                    .iter()
+                    .map(|&index| PictureIndex {
+                        index,
+                        bottom_field: None,
+                    })
+                    .collect()
+            }
+        };
+
+        let parity = |bottom_field| {
+            indices
+                .iter()
+                .filter(move |&&index| self.frames[index].is_marked(Some(bottom_field), marking))
+                .map(move |&index| PictureIndex {
+                    index,
+                    bottom_field: Some(bottom_field),
+                })
+        };
+        let mut same_parity = parity(bottom_field);
+        let mut opposite_parity = parity(!bottom_field);
+        let mut fields = vec![];
+        loop {
+            match (same_parity.next(), opposite_parity.next()) {
+                (None, None) => return fields,
+                (same, opposite) => fields.extend(same.into_iter().chain(opposite)),
+            }
+        }
+    }
+
+    fn reference_picture(&self, picture: PictureIndex) -> ReferencePicture {
+        let frame = &self.frames[picture.index];
+        let long_term = frame.is_marked(picture.bottom_field, ReferenceMarking::LongTerm);
+        let marking = if long_term {
+            ReferenceMarking::LongTerm
+        } else {
+            ReferenceMarking::ShortTerm
+        };
+        ReferencePicture {
+            frame_num: frame.frame_num,
+            bottom_field: picture.bottom_field,
+            long_term_frame_idx: frame.long_term_frame_idx.filter(|_| long_term),
+            pic_order_cnt: frame.pic_order_cnt(picture.bottom_field, marking),
+            non_existing: frame.non_existing,
+        }
+    }
+}
+
+/// FrameNumWrap (8-27)
+fn frame_num_wrap(frame: &DecodedFrame, frame_num: u64, max_frame_num: u64) -> i64 {
+    if frame.frame_num > frame_num {
+        frame.frame_num as i64 - max_frame_num as i64
+    } else {
+        frame.frame_num as i64
+    }
+}
+
+/// Picture numbers relative to the current picture
+///
+/// § 8.2.4.1 Decoding process for picture numbers
+struct PictureNumbers {
+    frame_num: u64,
+    max_frame_num: u64,
+    /// `None` for a frame
+    bottom_field: Option<bool>,
+    /// CurrPicNum
+    curr_pic_num: i64,
+    /// MaxPicNum
+    max_pic_num: i64,
+}
+
+impl PictureNumbers {
+    fn new(current: &CurrentPicture) -> Self {
+        let (curr_pic_num, max_pic_num) = match current.bottom_field {
+            Some(_) => (
+                2 * current.frame_num as i64 + 1,
+                2 * current.max_frame_num as i64,
+            ),
+            None => (current.frame_num as i64, current.max_frame_num as i64),
+        };
+        Self {
+            frame_num: current.frame_num,
+            max_frame_num: current.max_frame_num,
+            bottom_field: current.bottom_field,
+            curr_pic_num,
+            max_pic_num,
+        }
+    }
+
+    /// The pictures that can be referenced by the current picture, frames when decoding a
+    /// frame and fields when decoding a field
+    fn pictures<'a>(
+        &'a self,
+        frames: &'a [DecodedFrame],
+        marking: ReferenceMarking,
+    ) -> impl Iterator<Item = PictureIndex> + 'a {
+        let parities: &[Option<bool>] = match self.bottom_field {
+            Some(_) => &[Some(false), Some(true)],
+            None => &[None],
+        };
+        (0..frames.len()).flat_map(move |index| {
+            parities
+                .iter()
+                .map(move |&bottom_field| PictureIndex {
+                    index,
+                    bottom_field,
+                })
+                .filter(move |picture| frames[index].is_marked(picture.bottom_field, marking))
+        })
+    }
+
+    /// (8-28) to (8-33), 2 * x + 1 for a field of the same parity and 2 * x for the opposite
+    fn field_number(&self, picture: PictureIndex, x: i64) -> i64 {
+        match (self.bottom_field, picture.bottom_field) {
+            (Some(bottom_field), Some(picture_bottom_field)) => {
+                2 * x + (bottom_field == picture_bottom_field) as i64
+            }
+            _ => x,
+        }
+    }
+
+    /// PicNum
+    fn pic_num(&self, frames: &[DecodedFrame], picture: PictureIndex) -> i64 {
+        let frame_num_wrap =
+            frame_num_wrap(&frames[picture.index], self.frame_num, self.max_frame_num);
+        self.field_number(picture, frame_num_wrap)
+    }
+
+    /// LongTermPicNum
+    fn long_term_pic_num(&self, frames: &[DecodedFrame], picture: PictureIndex) -> i64 {
+        let long_term_frame_idx = frames[picture.index].long_term_frame_idx.unwrap_or(0);
+        self.field_number(picture, long_term_frame_idx as i64)
+    }
+
+    fn find_short_term(&self, frames: &[DecodedFrame], pic_num: i64) -> Option<PictureIndex> {
+        self.pictures(frames, ReferenceMarking::ShortTerm)
+            .find(|&picture| self.pic_num(frames, picture) == pic_num)
+    }
+
+    fn find_long_term(
+        &self,
+        frames: &[DecodedFrame],
+        long_term_pic_num: i64,
+    ) -> Option<PictureIndex> {
+        self.pictures(frames, ReferenceMarking::LongTerm)
+            .find(|&picture| self.long_term_pic_num(frames, picture) == long_term_pic_num)
+    }
+}
+
+/// Truncates or pads `list` to `num_ref_idx_active` entries, then applies `operations`.
+///
+/// § 8.2.4.3 Modification process for reference picture lists
+fn modify_ref_pic_list(
+    frames: &[DecodedFrame],
+    numbers: &PictureNumbers,
+    list: &mut Vec<Option<PictureIndex>>,
+    num_ref_idx_active: usize,
+    operations: Option<&[RefPicListModificationOperation]>,
+) -> Result<()> {
+    list.resize(num_ref_idx_active, None);
+
+    let mut pic_num_pred = numbers.curr_pic_num;
+    for (ref_idx, operation) in operations.unwrap_or_default().iter().enumerate() {
+        let picture = match operation.modification_of_pic_nums_idc.0 {
+            // § 8.2.4.3.1 Modification process of reference picture lists for short-term
+            // reference pictures
+            idc @ (0 | 1) => {
+                let abs_diff_pic_num = operation
+                    .abs_diff_pic_num_minus1
+                    .ok_or(BitStreamError::MissingValue)?
+                    .0 as i64
+                    + 1;
+                // (8-34) and (8-35)
+                let pic_num_no_wrap = if idc == 0 {
+                    (pic_num_pred - abs_diff_pic_num).rem_euclid(numbers.max_pic_num)
+                } else {
+                    (pic_num_pred + abs_diff_pic_num).rem_euclid(numbers.max_pic_num)
+                };
+                pic_num_pred = pic_num_no_wrap;
+                // (8-36)
+                let pic_num = if pic_num_no_wrap > numbers.curr_pic_num {
+                    pic_num_no_wrap - numbers.max_pic_num
+                } else {
+                    pic_num_no_wrap
+                };
+                numbers.find_short_term(frames, pic_num)
+            }
+            // § 8.2.4.3.2 Modification process of reference picture lists for long-term
+            // reference pictures
+            2 => {
+                let long_term_pic_num = operation
+                    .long_term_pic_num
+                    .ok_or(BitStreamError::MissingValue)?
+                    .0 as i64;
+                numbers.find_long_term(frames, long_term_pic_num)
+            }
+            3 => break,
+            _ => return Err(BitStreamError::InvalidValue),
+        };
+        let picture = picture.ok_or(BitStreamError::InvalidValue)?;
+        if ref_idx >= num_ref_idx_active {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        // (8-37) and (8-38), the list is one entry longer during the modification
+        list.insert(ref_idx, Some(picture));
+        let mut n_idx = ref_idx + 1;
+        for c_idx in ref_idx + 1..list.len() {
+            if list[c_idx] != Some(picture) {
+                list[n_idx] = list[c_idx];
+                n_idx += 1;
+            }
+        }
+        list.truncate(num_ref_idx_active);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::BitStreamError;
+
+    use crate::{nal_unit::UnsignedExpGolombCode,
+                test::{test_decoder, test_sequence_parameter_set, SliceHeaderBuilder},
+                Decoder, RefPicLists, ReferencePicture};
+
+    /// Starts decoding a picture with a slice of `slice_type`, with 3 active reference indices
+    /// in each list
+    fn decode(
+        decoder: &mut Decoder,
+        (ty, ref_idc): (u8, u8),
+        slice_type: u64,
+        frame_num: u8,
+        bottom_field: Option<bool>,
+        modification: &[u64],
+        memory_management_control_operations: &[u64],
+    ) -> RefPicLists {
+        let mut builder = SliceHeaderBuilder::new(ty, ref_idc, slice_type, frame_num);
+        builder.bottom_field_flag = bottom_field;
+        builder.num_ref_idx_active_minus1 = Some(2);
+        builder.ref_pic_list_modification_l0 = modification.to_vec();
+        builder.memory_management_control_operations =
+            memory_management_control_operations.to_vec();
+        let (header, slice_header) = builder.read(decoder);
+        decoder.start_picture(&header, &slice_header).unwrap();
+        decoder.decode_ref_pic_lists(&slice_header).unwrap()
+    }
+
+    fn frames(list: &[Option<ReferencePicture>]) -> Vec<Option<(u64, bool)>> {
+        list.iter()
+            .map(|x| x.map(|x| (x.frame_num, x.long_term_frame_idx.is_some())))
+            .collect()
+    }
+
+    fn fields(list: &[Option<ReferencePicture>]) -> Vec<Option<(u64, bool)>> {
+        list.iter()
+            .map(|x| x.map(|x| (x.frame_num, x.bottom_field.unwrap())))
+            .collect()
+    }
+
+    #[test]
+    fn ref_pic_lists() {
+        let mut sps = test_sequence_parameter_set();
+        sps.max_num_ref_frames = UnsignedExpGolombCode(3);
+
+        // Frames, pic_order_cnt_type 2, MaxFrameNum 16
+        let mut decoder = test_decoder(sps.clone());
+        let lists = decode(&mut decoder, (5, 3), 7, 0, None, &[], &[]);
+        assert!(lists.ref_pic_list0.is_empty());
+        let lists = decode(&mut decoder, (1, 2), 5, 1, None, &[], &[]);
+        assert_eq!(frames(&lists.ref_pic_list0), [Some((0, false)), None, None]);
+        assert!(lists.ref_pic_list1.is_empty());
+        decode(&mut decoder, (1, 2), 5, 2, None, &[], &[]);
+        let lists = decode(&mut decoder, (1, 2), 5, 3, None, &[], &[]);
+        assert_eq!(
+            frames(&lists.ref_pic_list0),
+            [Some((2, false)), Some((1, false)), Some((0, false))]
+        );
+        // The sliding window removes frame_num 0 then 1, then PicNum 3 is moved to the front,
+        // PicNum 4 is marked as unused for reference and PicNum 2 as long-term
+        decode(&mut decoder, (1, 2), 5, 4, None, &[], &[]);
+        let lists = decode(
+            &mut decoder,
+            (1, 2),
+            5,
+            5,
+            None,
+            &[0, 1, 3],
+            &[1, 0, 3, 2, 0, 0],
+        );
+        assert_eq!(
+            frames(&lists.ref_pic_list0),
+            [Some((3, false)), Some((4, false)), Some((2, false))]
+        );
+        let lists = decode(&mut decoder, (1, 2), 5, 6, None, &[], &[]);
+        assert_eq!(
+            frames(&lists.ref_pic_list0),
+            [Some((5, false)), Some((3, false)), Some((2, true))]
+        );
+        // frame_num 7 and 8 are inferred, the sliding window removes 3, 5 then 6
+        let lists = decode(&mut decoder, (1, 2), 5, 9, None, &[], &[]);
+        assert_eq!(
+            frames(&lists.ref_pic_list0),
+            [Some((8, false)), Some((7, false)), Some((2, true))]
+        );
+        assert!(lists.ref_pic_list0[0].unwrap().non_existing);
+        assert_eq!(decoder.decoded_picture_buffer().frames().len(), 3);
+        // Both lists only have pictures before PicOrderCnt 19, so the first two entries of
+        // RefPicList1 are switched
+        let lists = decode(&mut decoder, (1, 0), 6, 10, None, &[], &[]);
+        assert_eq!(
+            frames(&lists.ref_pic_list0),
+            [Some((9, false)), Some((8, false)), Some((2, true))]
+        );
+        assert_eq!(
+            frames(&lists.ref_pic_list1),
+            [Some((8, false)), Some((9, false)), Some((2, true))]
+        );
+
+        // Fields
+        sps.frame_mbs_only_flag = false;
+        sps.mb_adaptive_frame_field_flag = false;
+        let mut decoder = test_decoder(sps);
+        decode(&mut decoder, (5, 3), 7, 0, Some(false), &[], &[]);
+        // The first field of the same frame can be referenced
+        let lists = decode(&mut decoder, (1, 2), 5, 0, Some(true), &[], &[]);
+        assert_eq!(fields(&lists.ref_pic_list0), [Some((0, false)), None, None]);
+        let lists = decode(&mut decoder, (1, 2), 5, 1, Some(false), &[], &[]);
+        assert_eq!(
+            fields(&lists.ref_pic_list0),
+            [Some((0, false)), Some((0, true)), None]
+        );
+        // Fields alternate from the same parity, the top field of frame_num 1 doesn't have a
+        // bottom field to pair with
+        let lists = decode(&mut decoder, (1, 2), 5, 1, Some(true), &[], &[]);
+        assert_eq!(
+            fields(&lists.ref_pic_list0),
+            [Some((0, true)), Some((1, false)), Some((0, false))]
+        );
+    }
+
+    #[test]
+    fn invalid_slices() {
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        decode(&mut decoder, (5, 3), 7, 0, None, &[], &[]);
+
+        // A slice referring to a missing PPS changes neither the picture order count state nor
+        // the decoded picture buffer
+        let (header, mut slice_header) = SliceHeaderBuilder::new(1, 2, 5, 5).read(&decoder);
+        slice_header.pic_parameter_set_id = UnsignedExpGolombCode(1);
+        let result = decoder.start_picture(&header, &slice_header);
+        assert!(matches!(result, Err(BitStreamError::MissingValue)));
+        assert!(decoder.decoded_picture_buffer().frames().is_empty());
+
+        let mut builder = SliceHeaderBuilder::new(1, 2, 5, 1);
+        builder.num_ref_idx_active_minus1 = Some(32);
+        let (header, slice_header) = builder.read(&decoder);
+        let picture_order_count = decoder.start_picture(&header, &slice_header).unwrap();
+        assert_eq!(picture_order_count.pic_order_cnt(), 2);
+        assert_eq!(decoder.decoded_picture_buffer().frames().len(), 1);
+
+        // num_ref_idx_l0_active_minus1 is in range 0 to 31
+        let result = decoder.decode_ref_pic_lists(&slice_header);
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+        let result = decoder
+            .decoded_picture_buffer()
+            .ref_pic_lists(&slice_header, 33, 1);
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+}
@@ -1,10 +1,25 @@
+use std::collections::HashMap;
+
 use array_fill::array_fill;
 
-use crate::nal_unit::{PictureParameterSet, SequenceParameterSet, UnsignedExpGolombCode};
+use bit_stream::{BitStreamError, Result};
+
+use crate::{nal_unit::{NalUnitHeader, PictureParameterSet, SequenceParameterSet,
+                       SequenceParameterSetExtension, SliceHeader, SubsetSequenceParameterSet,
+                       UnsignedExpGolombCode},
+            DecodedPictureBuffer, PictureOrderCount, PictureOrderCountDecoder, RefPicLists};
 
 pub struct Decoder {
     picture_parameter_sets: [Option<PictureParameterSet>; 256],
     sequence_parameter_sets: [Option<SequenceParameterSet>; 32],
+    sequence_parameter_set_extensions: [Option<SequenceParameterSetExtension>; 32],
+    subset_sequence_parameter_sets: [Option<SubsetSequenceParameterSet>; 32],
+    active_sequence_parameter_set_id: Option<UnsignedExpGolombCode>,
+    active_picture_parameter_set_id: Option<UnsignedExpGolombCode>,
+    /// pic_parameter_set_id of the last slice data partition A with each slice_id
+    slice_data_partition_pic_parameter_set_ids: HashMap<u64, UnsignedExpGolombCode>,
+    picture_order_count_decoder: PictureOrderCountDecoder,
+    decoded_picture_buffer: DecodedPictureBuffer,
 }
 
 impl Decoder {
@@ -12,6 +27,13 @@ impl Decoder {
         Self {
             picture_parameter_sets: array_fill![None; 256],
             sequence_parameter_sets: array_fill![None; 32],
+            sequence_parameter_set_extensions: array_fill![None; 32],
+            subset_sequence_parameter_sets: array_fill![None; 32],
+            active_sequence_parameter_set_id: None,
+            active_picture_parameter_set_id: None,
+            slice_data_partition_pic_parameter_set_ids: HashMap::new(),
+            picture_order_count_decoder: PictureOrderCountDecoder::new(),
+            decoded_picture_buffer: DecodedPictureBuffer::new(),
         }
     }
 
@@ -21,8 +43,79 @@ impl Decoder {
     }
 
     pub fn set_sequence_parameter_set(&mut self, sequence_parameter_set: SequenceParameterSet) {
-        let id = sequence_parameter_set.seq_parameter_set_id.0 as usize;
-        self.sequence_parameter_sets[id] = Some(sequence_parameter_set);
+        let id = sequence_parameter_set.seq_parameter_set_id;
+        self.sequence_parameter_sets[id.0 as usize] = Some(sequence_parameter_set);
+
+        // Until a slice activates one, assume the first received
+        // sequence parameter set will be activated
+        if self.active_sequence_parameter_set_id.is_none() {
+            self.active_sequence_parameter_set_id = Some(id);
+        }
+    }
+
+    pub fn set_sequence_parameter_set_extension(
+        &mut self,
+        sequence_parameter_set_extension: SequenceParameterSetExtension,
+    ) {
+        let id = sequence_parameter_set_extension.seq_parameter_set_id.0 as usize;
+        self.sequence_parameter_set_extensions[id] = Some(sequence_parameter_set_extension);
+    }
+
+    /// Subset sequence parameter sets share the `seq_parameter_set_id` value space with
+    /// sequence parameter sets, but are stored separately and don't replace each other.
+    ///
+    /// § 7.4.2.1.3 Subset sequence parameter set RBSP semantics
+    pub fn set_subset_sequence_parameter_set(
+        &mut self,
+        subset_sequence_parameter_set: SubsetSequenceParameterSet,
+    ) {
+        let id = subset_sequence_parameter_set
+            .seq_parameter_set_data
+            .seq_parameter_set_id
+            .0 as usize;
+        self.subset_sequence_parameter_sets[id] = Some(subset_sequence_parameter_set);
+    }
+
+    /// § 7.4.1.2.1 Order of sequence and picture parameter set RBSPs and their activation
+    pub fn activate_sequence_parameter_set(&mut self, id: UnsignedExpGolombCode) {
+        self.active_sequence_parameter_set_id = Some(id);
+    }
+
+    /// Activates the picture parameter set referred to by a coded slice or slice data partition A,
+    /// and the sequence parameter set it refers to.
+    ///
+    /// § 7.4.1.2.1 Order of sequence and picture parameter set RBSPs and their activation
+    pub fn activate_picture_parameter_set(&mut self, id: UnsignedExpGolombCode) {
+        self.active_picture_parameter_set_id = Some(id);
+        if let Some(picture_parameter_set) = self.find_picture_parameter_set(id) {
+            let seq_parameter_set_id = picture_parameter_set.seq_parameter_set_id;
+            self.activate_sequence_parameter_set(seq_parameter_set_id);
+        }
+    }
+
+    /// Records the picture parameter set referred to by a slice data partition A, which slice
+    /// data partitions B and C with the same `slice_id` use.
+    ///
+    /// § 7.4.2.9.2 Slice data partition B RBSP semantics
+    pub fn set_slice_data_partition(
+        &mut self,
+        slice_id: UnsignedExpGolombCode,
+        pic_parameter_set_id: UnsignedExpGolombCode,
+    ) {
+        self.slice_data_partition_pic_parameter_set_ids
+            .insert(slice_id.0, pic_parameter_set_id);
+    }
+
+    /// Finds the picture parameter set referred to by the last slice data partition A with
+    /// `slice_id`.
+    pub fn find_slice_data_partition_picture_parameter_set(
+        &self,
+        slice_id: UnsignedExpGolombCode,
+    ) -> Option<&PictureParameterSet> {
+        let id = *self
+            .slice_data_partition_pic_parameter_set_ids
+            .get(&slice_id.0)?;
+        self.find_picture_parameter_set(id)
     }
 
     pub fn find_picture_parameter_set(
@@ -38,4 +131,157 @@ impl Decoder {
     ) -> Option<&SequenceParameterSet> {
         self.sequence_parameter_sets[id.0 as usize].as_ref()
     }
+
+    pub fn find_sequence_parameter_set_extension(
+        &self,
+        id: UnsignedExpGolombCode,
+    ) -> Option<&SequenceParameterSetExtension> {
+        self.sequence_parameter_set_extensions[id.0 as usize].as_ref()
+    }
+
+    pub fn find_subset_sequence_parameter_set(
+        &self,
+        id: UnsignedExpGolombCode,
+    ) -> Option<&SubsetSequenceParameterSet> {
+        self.subset_sequence_parameter_sets[id.0 as usize].as_ref()
+    }
+
+    /// Finds the sequence parameter set referred to by a coded slice in a NAL unit with `header`.
+    ///
+    /// Coded slice extensions (nal_unit_type 20 and 21) refer to subset sequence parameter sets.
+    ///
+    /// § H.7.4.1.2.1 Order of MVC sequence parameter set RBSPs and picture parameter set RBSPs and their activation
+    pub fn find_slice_sequence_parameter_set(
+        &self,
+        header: &NalUnitHeader,
+        id: UnsignedExpGolombCode,
+    ) -> Option<&SequenceParameterSet> {
+        match header.ty {
+            20 | 21 => self
+                .find_subset_sequence_parameter_set(id)
+                .map(|x| &x.seq_parameter_set_data),
+            _ => self.find_sequence_parameter_set(id),
+        }
+    }
+
+    pub fn active_sequence_parameter_set(&self) -> Option<&SequenceParameterSet> {
+        self.find_sequence_parameter_set(self.active_sequence_parameter_set_id?)
+    }
+
+    pub fn active_picture_parameter_set(&self) -> Option<&PictureParameterSet> {
+        self.find_picture_parameter_set(self.active_picture_parameter_set_id?)
+    }
+
+    /// Derives the picture order count of the picture containing a coded slice in a NAL unit
+    /// with `nal_unit_header`, see [`PictureOrderCountDecoder::decode`].
+    ///
+    /// § 8.2.1 Decoding process for picture order count
+    pub fn decode_picture_order_count(
+        &mut self,
+        nal_unit_header: &NalUnitHeader,
+        header: &SliceHeader,
+    ) -> Result<PictureOrderCount> {
+        let mut picture_order_count_decoder = self.picture_order_count_decoder;
+
+        let pic_parameter_set = self
+            .find_picture_parameter_set(header.pic_parameter_set_id)
+            .ok_or(BitStreamError::MissingValue)?;
+        let seq_parameter_set = self
+            .find_slice_sequence_parameter_set(
+                nal_unit_header,
+                pic_parameter_set.seq_parameter_set_id,
+            )
+            .ok_or(BitStreamError::MissingValue)?;
+        let picture_order_count =
+            picture_order_count_decoder.decode(seq_parameter_set, nal_unit_header, header)?;
+
+        self.picture_order_count_decoder = picture_order_count_decoder;
+        Ok(picture_order_count)
+    }
+
+    /// Starts decoding the primary coded picture containing a coded slice in a NAL unit with
+    /// `nal_unit_header`, returns its picture order count. The previous picture is marked in
+    /// the decoded picture buffer, see [`DecodedPictureBuffer::start_picture`].
+    ///
+    /// On error, neither the picture order count state nor the decoded picture buffer change.
+    ///
+    /// § 8.2 Slice decoding process
+    pub fn start_picture(
+        &mut self,
+        nal_unit_header: &NalUnitHeader,
+        header: &SliceHeader,
+    ) -> Result<PictureOrderCount> {
+        let mut picture_order_count_decoder = self.picture_order_count_decoder;
+        let mut decoded_picture_buffer = std::mem::take(&mut self.decoded_picture_buffer);
+        let result = self
+            .find_picture_parameter_set(header.pic_parameter_set_id)
+            .and_then(|pic_parameter_set| {
+                self.find_slice_sequence_parameter_set(
+                    nal_unit_header,
+                    pic_parameter_set.seq_parameter_set_id,
+                )
+            })
+            .ok_or(BitStreamError::MissingValue)
+            .and_then(|seq_parameter_set| {
+                let picture_order_count = picture_order_count_decoder.decode(
+                    seq_parameter_set,
+                    nal_unit_header,
+                    header,
+                )?;
+                decoded_picture_buffer.start_picture(
+                    seq_parameter_set,
+                    nal_unit_header,
+                    header,
+                    picture_order_count,
+                );
+                Ok(picture_order_count)
+            });
+        self.decoded_picture_buffer = decoded_picture_buffer;
+
+        if result.is_ok() {
+            self.picture_order_count_decoder = picture_order_count_decoder;
+        }
+        result
+    }
+
+    /// Constructs RefPicList0 and RefPicList1 of a slice of the picture started by
+    /// `start_picture`.
+    ///
+    /// § 8.2.4 Decoding process for reference picture lists construction
+    pub fn decode_ref_pic_lists(&self, header: &SliceHeader) -> Result<RefPicLists> {
+        let pic_parameter_set = self
+            .find_picture_parameter_set(header.pic_parameter_set_id)
+            .ok_or(BitStreamError::MissingValue)?;
+
+        // § 7.4.3 Slice header semantics
+        let num_ref_idx_l0_active_minus1 = header
+            .num_ref_idx_l0_active_minus1
+            .unwrap_or(pic_parameter_set.num_ref_idx_l0_default_active_minus1)
+            .0;
+        let num_ref_idx_l1_active_minus1 = header
+            .num_ref_idx_l1_active_minus1
+            .unwrap_or(pic_parameter_set.num_ref_idx_l1_default_active_minus1)
+            .0;
+        if num_ref_idx_l0_active_minus1 > 31 || num_ref_idx_l1_active_minus1 > 31 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        let num_ref_idx_l0_active = num_ref_idx_l0_active_minus1 as usize + 1;
+        let num_ref_idx_l1_active = num_ref_idx_l1_active_minus1 as usize + 1;
+
+        self.decoded_picture_buffer.ref_pic_lists(
+            header,
+            num_ref_idx_l0_active,
+            num_ref_idx_l1_active,
+        )
+    }
+
+    pub fn decoded_picture_buffer(&self) -> &DecodedPictureBuffer {
+        &self.decoded_picture_buffer
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -7,8 +7,308 @@ pub use stream::*;
 mod decoder;
 pub use decoder::*;
 
+mod profile_level;
+pub use profile_level::*;
+
+mod picture_order_count;
+pub use picture_order_count::*;
+
+mod decoded_picture_buffer;
+pub use decoded_picture_buffer::*;
+
+mod access_unit;
+pub use access_unit::*;
+
 #[cfg(test)]
 mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use crate::{nal_unit::{NalUnitHeader, PictureParameterSet, PredWeightTable,
+                           SequenceParameterSet, SignedExpGolombCode, SliceHeader, SliceTypeName,
+                           UnsignedExpGolombCode},
+                Decoder, NalUnit, NalUnitPayload};
+
+    /// RBSP of the SPS in `stream::test::test`: Baseline profile, pic_order_cnt_type 2,
+    /// MaxFrameNum 16 and 31x68 macroblocks
+    pub(crate) const SPS_DATA: [u8; 14] =
+        [66, 128, 40, 218, 7, 192, 137, 229, 150, 1, 180, 40, 77, 64];
+
+    /// RBSP of the PPS in `stream::test::test`: CAVLC and deblocking_filter_control_present_flag 1
+    pub(crate) const PPS_DATA: [u8; 3] = [206, 6, 242];
+
+    /// RBSP of a subset SPS: profile_idc 128 (Stereo High), two views, view 1 predicted from
+    /// view 0
+    pub(crate) const MVC_SUBSET_SPS_DATA: [u8; 15] = [
+        0x80, 0x00, 0x28, 0xac, 0xb4, 0x03, 0xc0, 0x11, 0x32, 0xa9, 0x6b, 0x94, 0x42, 0xa4, 0x40,
+    ];
+
+    pub(crate) fn test_sequence_parameter_set() -> SequenceParameterSet {
+        BitStream::new(&SPS_DATA).read(()).unwrap()
+    }
+
+    /// Decoder with `sps` and the PPS of `PPS_DATA`
+    pub(crate) fn test_decoder(sps: SequenceParameterSet) -> Decoder {
+        let mut decoder = Decoder::new();
+        decoder.set_sequence_parameter_set(sps);
+        let pps: PictureParameterSet = BitStream::new(&PPS_DATA).read(&decoder).unwrap();
+        decoder.set_picture_parameter_set(pps);
+        decoder
+    }
+
+    /// I, P or B slice header for a decoder made by `test_decoder`
+    ///
+    /// `ref_pic_list_modification_l0` and `memory_management_control_operations` are the
+    /// Exp-Golomb codes of ref_pic_list_modification( ) and dec_ref_pic_marking( ), including the
+    /// terminating operation, and are only signalled when not empty.
+    pub(crate) struct SliceHeaderBuilder {
+        pub nal_ref_idc: u8,
+        pub nal_unit_type: u8,
+        pub first_mb_in_slice: u64,
+        pub slice_type: u64,
+        pub frame_num: u8,
+        /// bottom_field_flag of a field, `None` for frames
+        pub bottom_field_flag: Option<bool>,
+        pub pic_order_cnt_lsb: Option<u8>,
+        /// Overrides num_ref_idx_l0_active_minus1 and num_ref_idx_l1_active_minus1
+        pub num_ref_idx_active_minus1: Option<u64>,
+        pub ref_pic_list_modification_l0: Vec<u64>,
+        /// pred_weight_table( ) of P and B slices, when the PPS signals it
+        pub pred_weight_table: Option<PredWeightTable>,
+        pub memory_management_control_operations: Vec<u64>,
+        /// cabac_init_idc of P and B slices when the PPS has entropy_coding_mode_flag
+        pub cabac_init_idc: Option<u64>,
+        pub slice_qp_delta: i64,
+        pub disable_deblocking_filter_idc: u64,
+    }
+
+    impl SliceHeaderBuilder {
+        pub fn new(nal_unit_type: u8, nal_ref_idc: u8, slice_type: u64, frame_num: u8) -> Self {
+            Self {
+                nal_ref_idc,
+                nal_unit_type,
+                first_mb_in_slice: 0,
+                slice_type,
+                frame_num,
+                bottom_field_flag: None,
+                pic_order_cnt_lsb: None,
+                num_ref_idx_active_minus1: None,
+                ref_pic_list_modification_l0: Vec::new(),
+                pred_weight_table: None,
+                memory_management_control_operations: Vec::new(),
+                cabac_init_idc: None,
+                slice_qp_delta: 0,
+                disable_deblocking_filter_idc: 0,
+            }
+        }
+
+        /// First byte of the NAL unit
+        pub fn nal_unit_header_byte(&self) -> u8 {
+            self.nal_ref_idc << 5 | self.nal_unit_type
+        }
+
+        pub fn nal_unit_header(&self) -> NalUnitHeader {
+            BitStream::new(&[self.nal_unit_header_byte()])
+                .read(())
+                .unwrap()
+        }
+
+        /// Writes slice_header( ), slice data or rbsp_trailing_bits( ) have to follow
+        pub fn write(&self, writer: &mut BitWriter) {
+            let idr = self.nal_unit_type == 5;
+            let slice_type = self.slice_type % 5;
+            for value in [self.first_mb_in_slice, self.slice_type, 0] {
+                writer.write(&UnsignedExpGolombCode(value), ()).unwrap();
+            }
+            writer.write(&self.frame_num, 4).unwrap();
+            if let Some(bottom_field_flag) = self.bottom_field_flag {
+                // field_pic_flag
+                writer.write_bit(true).unwrap();
+                writer.write_bit(bottom_field_flag).unwrap();
+            }
+            if idr {
+                // idr_pic_id
+                writer.write(&UnsignedExpGolombCode(0), ()).unwrap();
+            }
+            if let Some(pic_order_cnt_lsb) = self.pic_order_cnt_lsb {
+                writer.write(&pic_order_cnt_lsb, 4).unwrap();
+            }
+            if slice_type == 1 {
+                // direct_spatial_mv_pred_flag
+                writer.write_bit(true).unwrap();
+            }
+            if slice_type == 0 || slice_type == 1 {
+                writer
+                    .write_bit(self.num_ref_idx_active_minus1.is_some())
+                    .unwrap();
+                if let Some(num_ref_idx_active_minus1) = self.num_ref_idx_active_minus1 {
+                    let lists = if slice_type == 1 { 2 } else { 1 };
+                    for _ in 0..lists {
+                        writer
+                            .write(&UnsignedExpGolombCode(num_ref_idx_active_minus1), ())
+                            .unwrap();
+                    }
+                }
+
+                writer
+                    .write_bit(!self.ref_pic_list_modification_l0.is_empty())
+                    .unwrap();
+                for &value in &self.ref_pic_list_modification_l0 {
+                    writer.write(&UnsignedExpGolombCode(value), ()).unwrap();
+                }
+                if slice_type == 1 {
+                    // ref_pic_list_modification_flag_l1
+                    writer.write_bit(false).unwrap();
+                }
+
+                if let Some(pred_weight_table) = &self.pred_weight_table {
+                    let slice_type_name = if slice_type == 1 {
+                        SliceTypeName::B
+                    } else {
+                        SliceTypeName::P
+                    };
+                    // ChromaArrayType 1 and the PPS default of one active reference
+                    let num_ref_idx_active = self.num_ref_idx_active_minus1.unwrap_or(0) + 1;
+                    let args = (&slice_type_name, 1, num_ref_idx_active, num_ref_idx_active);
+                    writer.write(pred_weight_table, args).unwrap();
+                }
+            }
+            if idr {
+                // no_output_of_prior_pics_flag and long_term_reference_flag
+                writer.skip(2).unwrap();
+            } else if self.nal_ref_idc != 0 {
+                writer
+                    .write_bit(!self.memory_management_control_operations.is_empty())
+                    .unwrap();
+                for &value in &self.memory_management_control_operations {
+                    writer.write(&UnsignedExpGolombCode(value), ()).unwrap();
+                }
+            }
+            if let Some(cabac_init_idc) = self.cabac_init_idc {
+                writer
+                    .write(&UnsignedExpGolombCode(cabac_init_idc), ())
+                    .unwrap();
+            }
+            writer
+                .write(&SignedExpGolombCode(self.slice_qp_delta), ())
+                .unwrap();
+            writer
+                .write(
+                    &UnsignedExpGolombCode(self.disable_deblocking_filter_idc),
+                    (),
+                )
+                .unwrap();
+            if self.disable_deblocking_filter_idc != 1 {
+                // slice_alpha_c0_offset_div2 and slice_beta_offset_div2
+                writer.write_bit(true).unwrap();
+                writer.write_bit(true).unwrap();
+            }
+        }
+
+        /// Writes the slice header and parses it with `decoder`
+        pub fn read(&self, decoder: &Decoder) -> (NalUnitHeader, SliceHeader) {
+            let mut writer = BitWriter::new();
+            self.write(&mut writer);
+            // rbsp_trailing_bits
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            let data = writer.into_inner();
+
+            let header = self.nal_unit_header();
+            let slice_header = BitStream::new(&data).read((decoder, &header)).unwrap();
+            (header, slice_header)
+        }
+    }
+
     #[test]
     fn parse_pic_param_set() {}
+
+    #[test]
+    fn parameter_sets_round_trip() {
+        let mut decoder = Decoder::new();
+
+        let sps = test_sequence_parameter_set();
+        assert_eq!(sps.chroma_array_type(), 1);
+        assert_eq!((sps.sub_width_c(), sps.sub_height_c()), (Some(2), Some(2)));
+        assert_eq!((sps.bit_depth_luma(), sps.bit_depth_chroma()), (8, 8));
+        assert_eq!(sps.max_frame_num(), 16);
+        assert_eq!(sps.max_pic_order_cnt_lsb(), None);
+        assert_eq!(sps.frame_height_in_mbs(), 68);
+        assert_eq!(sps.pic_size_in_map_units(), 31 * 68);
+        assert_eq!((sps.cropped_width(), sps.cropped_height()), (488, 1080));
+        let mut writer = BitWriter::new();
+        writer.write(&sps, ()).unwrap();
+        // rbsp_trailing_bits
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &SPS_DATA[..]);
+        decoder.set_sequence_parameter_set(sps);
+
+        let pps: PictureParameterSet = BitStream::new(&PPS_DATA).read(&decoder).unwrap();
+        assert!(pps.transform_8x8_mode_flag.is_none());
+        let mut writer = BitWriter::new();
+        writer.write(&pps, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &PPS_DATA[..]);
+    }
+
+    #[test]
+    fn slice_round_trip() {
+        // IDR I slice with an Intra_16x16 macroblock without residual, and P slice with all
+        // 31x68 macroblocks skipped
+        for builder in [
+            SliceHeaderBuilder::new(5, 3, 7, 0),
+            SliceHeaderBuilder::new(1, 2, 5, 1),
+        ] {
+            let mut writer = BitWriter::new();
+            writer.write(&builder.nal_unit_header_byte(), 8).unwrap();
+            builder.write(&mut writer);
+            if builder.nal_unit_type == 5 {
+                // mb_type, intra_chroma_pred_mode and mb_qp_delta
+                writer.write(&UnsignedExpGolombCode(1), ()).unwrap();
+                writer.write(&UnsignedExpGolombCode(0), ()).unwrap();
+                writer.write(&SignedExpGolombCode(0), ()).unwrap();
+                // coeff_token of Intra16x16DCLevel without coefficients
+                writer.write_bit(true).unwrap();
+            } else {
+                // mb_skip_run
+                writer.write(&UnsignedExpGolombCode(31 * 68), ()).unwrap();
+            }
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            let data = writer.into_inner();
+
+            // The slice header refers to a PPS that isn't received yet
+            let mut decoder = Decoder::new();
+            decoder.set_sequence_parameter_set(test_sequence_parameter_set());
+            let result: Result<NalUnit, _> = BitStream::new(&data).read(&decoder);
+            assert!(matches!(result, Err(BitStreamError::MissingValue)));
+
+            let pps: PictureParameterSet = BitStream::new(&PPS_DATA).read(&decoder).unwrap();
+            decoder.set_picture_parameter_set(pps);
+            let unit: NalUnit = BitStream::new(&data).read(&decoder).unwrap();
+            let slice = match (builder.nal_unit_type, &unit.payload) {
+                (5, NalUnitPayload::IdrSlice(slice)) | (1, NalUnitPayload::Slice(slice)) => slice,
+                (_, payload) => panic!("unexpected payload {:?}", payload),
+            };
+            assert_eq!(slice.slice_header.frame_num, builder.frame_num as u16);
+            let macroblocks = slice.slice_data.macroblocks.as_ref().unwrap();
+            assert_eq!(macroblocks.len(), 1);
+            if builder.nal_unit_type == 5 {
+                let macroblock_layer = macroblocks[0].macroblock_layer.as_ref().unwrap();
+                assert_eq!(macroblock_layer.coded_block_pattern(), (0, 0));
+            } else {
+                assert_eq!(
+                    macroblocks[0].mb_skip_run,
+                    Some(UnsignedExpGolombCode(31 * 68))
+                );
+            }
+
+            let mut writer = BitWriter::new();
+            writer.write(&unit, &decoder).unwrap();
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            assert_eq!(&*writer.into_inner(), &data[..]);
+        }
+    }
 }
@@ -1,32 +1,77 @@
-use bit_stream::{BitField, BitStream, Result, cond_bit_field};
+use bit_stream::{cond_bit_field, BitField, BitFieldWrite, BitStream, BitWriter, Result};
 use serde::Serialize;
 
 mod access_unit_delimiter;
+mod cabac;
+mod cabac_init;
+mod cabac_macroblock;
+mod cavlc;
 mod exp_golomb;
+mod extension_data;
+mod filler_data;
 mod header;
+mod macroblock;
 mod pic_param_set;
+mod prefix_nal_unit;
 mod scaling_list;
+mod sei;
 mod seq_param_set;
+mod seq_param_set_extension;
 mod slice;
+mod slice_data;
 mod slice_header;
+mod subset_seq_param_set;
 
 pub use access_unit_delimiter::*;
+pub use cabac::*;
+pub use cabac_init::*;
+pub use cabac_macroblock::*;
+pub use cavlc::*;
 pub use exp_golomb::*;
+pub use extension_data::*;
+pub use filler_data::*;
 pub use header::*;
+pub use macroblock::*;
 pub use pic_param_set::*;
+pub use prefix_nal_unit::*;
 pub use scaling_list::*;
+pub use sei::*;
 pub use seq_param_set::*;
+pub use seq_param_set_extension::*;
 pub use slice::*;
+pub use slice_data::*;
 pub use slice_header::*;
+pub use subset_seq_param_set::*;
 
 use crate::decoder::Decoder;
 
 #[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, Serialize)]
 pub enum NalUnitPayload {
+    /// Coded slice of a non-IDR picture
+    Slice(SliceLayerWithoutPartitioning),
+    /// Coded slice of an IDR picture
+    IdrSlice(SliceLayerWithoutPartitioning),
+    SliceDataPartitionA(SliceDataPartitionA),
+    SliceDataPartitionB(SliceDataPartitionB),
+    SliceDataPartitionC(SliceDataPartitionC),
+    SupplementalEnhancementInformation(SupplementalEnhancementInformation),
     PictureParameterSet(PictureParameterSet),
     SequenceParameterSet(SequenceParameterSet),
     AccessUnitDelimiter(AccessUnitDelimiter),
+    /// end_of_seq_rbsp, has no content
+    EndOfSequence,
+    /// end_of_stream_rbsp, has no content
+    EndOfStream,
+    FillerData(FillerData),
+    SequenceParameterSetExtension(SequenceParameterSetExtension),
+    PrefixNalUnit(PrefixNalUnit),
+    SubsetSequenceParameterSet(SubsetSequenceParameterSet),
+    /// Coded slice extension, only MVC is supported
+    SliceExtension(SliceLayerWithoutPartitioning),
+    /// Coded slice of an auxiliary coded picture without partitioning
+    AuxiliarySlice(SliceLayerWithoutPartitioning),
     Unknown(Box<[u8]>),
 }
 
@@ -35,16 +80,84 @@ impl<'a> BitField<'a> for NalUnitPayload {
 
     fn read(stream: &mut BitStream, (decoder, header): Self::Args) -> Result<Self> {
         Ok(match header.ty {
+            1 => stream.read((decoder, header)).map(NalUnitPayload::Slice)?,
+            2 => stream
+                .read((decoder, header))
+                .map(NalUnitPayload::SliceDataPartitionA)?,
+            3 => stream
+                .read(decoder)
+                .map(NalUnitPayload::SliceDataPartitionB)?,
+            4 => stream
+                .read(decoder)
+                .map(NalUnitPayload::SliceDataPartitionC)?,
+            5 => stream
+                .read((decoder, header))
+                .map(NalUnitPayload::IdrSlice)?,
+            6 => stream
+                .read(decoder)
+                .map(NalUnitPayload::SupplementalEnhancementInformation)?,
             7 => stream.read(()).map(NalUnitPayload::SequenceParameterSet)?,
             8 => stream
                 .read(decoder)
                 .map(NalUnitPayload::PictureParameterSet)?,
             9 => stream.read(()).map(NalUnitPayload::AccessUnitDelimiter)?,
+            10 => NalUnitPayload::EndOfSequence,
+            11 => NalUnitPayload::EndOfStream,
+            12 => stream.read(()).map(NalUnitPayload::FillerData)?,
+            13 => stream
+                .read(())
+                .map(NalUnitPayload::SequenceParameterSetExtension)?,
+            14 => stream.read(header).map(NalUnitPayload::PrefixNalUnit)?,
+            15 => stream
+                .read(())
+                .map(NalUnitPayload::SubsetSequenceParameterSet)?,
+            // slice_layer_extension_rbsp, for MVC it's the same as slice_layer_without_partitioning_rbsp
+            //
+            // § 7.3.2.13 Slice layer extension RBSP syntax
+            20 | 21 if header.mvc_extension.is_some() => stream
+                .read((decoder, header))
+                .map(NalUnitPayload::SliceExtension)?,
+            19 => stream
+                .read((decoder, header))
+                .map(NalUnitPayload::AuxiliarySlice)?,
             _ => NalUnitPayload::Unknown(stream.read_all()),
         })
     }
 }
 
+impl<'a> BitFieldWrite<'a> for NalUnitPayload {
+    type Args = (&'a Decoder, &'a NalUnitHeader);
+
+    fn write(&self, writer: &mut BitWriter, (decoder, header): Self::Args) -> Result<()> {
+        match self {
+            NalUnitPayload::Slice(value)
+            | NalUnitPayload::IdrSlice(value)
+            | NalUnitPayload::AuxiliarySlice(value)
+            | NalUnitPayload::SliceExtension(value) => writer.write(value, (decoder, header)),
+            NalUnitPayload::SliceDataPartitionA(value) => writer.write(value, (decoder, header)),
+            NalUnitPayload::SliceDataPartitionB(value) => writer.write(value, decoder),
+            NalUnitPayload::SliceDataPartitionC(value) => writer.write(value, decoder),
+            NalUnitPayload::SupplementalEnhancementInformation(value) => {
+                writer.write(value, decoder)
+            }
+            NalUnitPayload::PictureParameterSet(value) => writer.write(value, decoder),
+            NalUnitPayload::SequenceParameterSet(value) => writer.write(value, ()),
+            NalUnitPayload::AccessUnitDelimiter(value) => writer.write(value, ()),
+            NalUnitPayload::EndOfSequence | NalUnitPayload::EndOfStream => Ok(()),
+            NalUnitPayload::FillerData(value) => writer.write(value, ()),
+            NalUnitPayload::SequenceParameterSetExtension(value) => writer.write(value, ()),
+            NalUnitPayload::PrefixNalUnit(value) => writer.write(value, header),
+            NalUnitPayload::SubsetSequenceParameterSet(value) => writer.write(value, ()),
+            NalUnitPayload::Unknown(data) => {
+                for byte in data.iter() {
+                    writer.write(byte, 8)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 cond_bit_field! {
     #[derive(Clone, Debug, Serialize)]
     #[extra_args(decoder: &Decoder)]
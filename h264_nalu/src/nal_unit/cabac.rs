@@ -0,0 +1,612 @@
+use bit_stream::{BitStream, BitStreamError, BitWriter, Result};
+
+/// A context variable, the probability state of a bin
+///
+/// § 9.3.1.1 Initialisation process for context variables
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContextVariable {
+    pub p_state_idx: u8,
+    pub val_mps: bool,
+}
+
+impl ContextVariable {
+    /// Initialises a context variable from its `m` and `n` values and SliceQPY.
+    ///
+    /// The values of `m` and `n` for each ctxIdx are specified in Tables 9-12 to 9-33,
+    /// for I and SI slices or by cabac_init_idc for other slices.
+    pub fn new(m: i32, n: i32, slice_qp_y: i32) -> Self {
+        let pre_ctx_state = (((m * slice_qp_y.clamp(0, 51)) >> 4) + n).clamp(1, 126);
+        if pre_ctx_state <= 63 {
+            Self {
+                p_state_idx: (63 - pre_ctx_state) as u8,
+                val_mps: false,
+            }
+        } else {
+            Self {
+                p_state_idx: (pre_ctx_state - 64) as u8,
+                val_mps: true,
+            }
+        }
+    }
+
+    /// § 9.3.3.2.1.1 State transition process
+    fn transit(&mut self, bin_val: bool) {
+        if bin_val == self.val_mps {
+            self.p_state_idx = (self.p_state_idx + 1).min(62);
+        } else {
+            if self.p_state_idx == 0 {
+                self.val_mps = !self.val_mps;
+            }
+            self.p_state_idx = TRANS_IDX_LPS[self.p_state_idx as usize];
+        }
+    }
+
+    /// codIRangeLPS for the current codIRange
+    fn range_lps(&self, cod_i_range: u32) -> u32 {
+        let q_cod_i_range_idx = (cod_i_range >> 6) & 3;
+        RANGE_TAB_LPS[self.p_state_idx as usize][q_cod_i_range_idx as usize] as u32
+    }
+}
+
+/// Parameters of the UEGk binarization
+///
+/// § 9.3.2.3 Concatenated unary/ k-th order Exp-Golomb (UEGk) binarization process
+#[derive(Clone, Copy, Debug)]
+pub struct Ueg {
+    /// Order of the Exp-Golomb suffix
+    pub k: u32,
+    pub signed_val_flag: bool,
+    /// cMax of the TU prefix
+    pub u_coff: u32,
+}
+
+/// The arithmetic decoding engine, reading bins from a [`BitStream`].
+///
+/// § 9.3.3.2 Arithmetic decoding process
+#[derive(Clone, Debug)]
+pub struct CabacDecoder {
+    cod_i_range: u32,
+    cod_i_offset: u32,
+}
+
+impl CabacDecoder {
+    /// Initialises the decoding engine at the current position of `stream`,
+    /// which must be byte aligned after cabac_alignment_one_bit.
+    ///
+    /// § 9.3.1.2 Initialisation process for the arithmetic decoding engine
+    pub fn new(stream: &mut BitStream) -> Result<Self> {
+        let cod_i_offset: u32 = stream.read(9)?;
+        // codIOffset equal to 510 or 511 is not allowed
+        if cod_i_offset >= 510 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        Ok(Self {
+            cod_i_range: 510,
+            cod_i_offset,
+        })
+    }
+
+    /// § 9.3.3.2.2 Renormalization process in the arithmetic decoding engine
+    fn renormalize(&mut self, stream: &mut BitStream) -> Result<()> {
+        while self.cod_i_range < 256 {
+            self.cod_i_range <<= 1;
+            self.cod_i_offset = self.cod_i_offset << 1 | stream.read_bit()? as u32;
+        }
+        Ok(())
+    }
+
+    /// DecodeDecision, updates the probability state of `context`
+    ///
+    /// § 9.3.3.2.1 Arithmetic decoding process for a binary decision
+    pub fn decode_decision(
+        &mut self,
+        stream: &mut BitStream,
+        context: &mut ContextVariable,
+    ) -> Result<bool> {
+        let cod_i_range_lps = context.range_lps(self.cod_i_range);
+        self.cod_i_range -= cod_i_range_lps;
+
+        let bin_val = if self.cod_i_offset >= self.cod_i_range {
+            self.cod_i_offset -= self.cod_i_range;
+            self.cod_i_range = cod_i_range_lps;
+            !context.val_mps
+        } else {
+            context.val_mps
+        };
+        context.transit(bin_val);
+
+        self.renormalize(stream)?;
+        Ok(bin_val)
+    }
+
+    /// DecodeBypass, for bins with equiprobable values
+    ///
+    /// § 9.3.3.2.3 Bypass decoding process for binary decisions
+    pub fn decode_bypass(&mut self, stream: &mut BitStream) -> Result<bool> {
+        self.cod_i_offset = self.cod_i_offset << 1 | stream.read_bit()? as u32;
+        if self.cod_i_offset >= self.cod_i_range {
+            self.cod_i_offset -= self.cod_i_range;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// DecodeTerminate, for end_of_slice_flag and the bin of mb_type indicating I_PCM.
+    ///
+    /// When it returns `true`, decoding is finished. The last bit read from `stream` is
+    /// rbsp_stop_one_bit, or the bit before the pcm_alignment_zero_bits.
+    ///
+    /// § 9.3.3.2.2.3 Decoding process for binary decisions before termination
+    pub fn decode_terminate(&mut self, stream: &mut BitStream) -> Result<bool> {
+        self.cod_i_range -= 2;
+        if self.cod_i_offset >= self.cod_i_range {
+            Ok(true)
+        } else {
+            self.renormalize(stream)?;
+            Ok(false)
+        }
+    }
+
+    /// Decodes a U or TU binarized value, the count of `1` bins before a `0` bin or `c_max`.
+    ///
+    /// Bin `binIdx` is decoded with `contexts[ctx_idx(binIdx)]`.
+    ///
+    /// § 9.3.2.1 Unary (U) binarization process
+    ///
+    /// § 9.3.2.2 Truncated unary (TU) binarization process
+    pub fn decode_unary(
+        &mut self,
+        stream: &mut BitStream,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: Option<u32>,
+    ) -> Result<u32> {
+        let mut value = 0;
+        while Some(value) != c_max {
+            if !self.decode_decision(stream, &mut contexts[ctx_idx(value)])? {
+                break;
+            }
+            value = value.checked_add(1).ok_or(BitStreamError::Overflow)?;
+        }
+        Ok(value)
+    }
+
+    /// Decodes a UEGk binarized value, a TU prefix with cMax uCoff decoded with `contexts`,
+    /// followed by a k-th order Exp-Golomb suffix and a sign decoded in bypass mode.
+    ///
+    /// § 9.3.2.3 Concatenated unary/ k-th order Exp-Golomb (UEGk) binarization process
+    pub fn decode_ueg(
+        &mut self,
+        stream: &mut BitStream,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        binarization: Ueg,
+    ) -> Result<i64> {
+        let Ueg {
+            k,
+            signed_val_flag,
+            u_coff,
+        } = binarization;
+        let mut value = self.decode_unary(stream, contexts, ctx_idx, Some(u_coff))? as i64;
+
+        if value == u_coff as i64 {
+            let mut k = k;
+            while self.decode_bypass(stream)? {
+                value += 1 << k;
+                k += 1;
+                if k >= 32 {
+                    return Err(BitStreamError::TooLarge);
+                }
+            }
+            while k > 0 {
+                k -= 1;
+                value += (self.decode_bypass(stream)? as i64) << k;
+            }
+        }
+
+        if signed_val_flag && value != 0 && self.decode_bypass(stream)? {
+            value = -value;
+        }
+        Ok(value)
+    }
+
+    /// Decodes a FL binarized value in range 0 to `c_max`, from the least significant bit.
+    ///
+    /// § 9.3.2.5 Fixed-length (FL) binarization process
+    pub fn decode_fixed_length(
+        &mut self,
+        stream: &mut BitStream,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: u32,
+    ) -> Result<u32> {
+        let fixed_length = 32 - c_max.leading_zeros();
+        let mut value = 0;
+        for bin_idx in 0..fixed_length {
+            value |=
+                (self.decode_decision(stream, &mut contexts[ctx_idx(bin_idx)])? as u32) << bin_idx;
+        }
+        Ok(value)
+    }
+}
+
+/// The arithmetic encoding engine, writing bins to a [`BitWriter`].
+///
+/// § 9.3.4.2 Arithmetic encoding process
+#[derive(Clone, Debug)]
+pub struct CabacEncoder {
+    cod_i_low: u32,
+    cod_i_range: u32,
+    first_bit_flag: bool,
+    bits_outstanding: u32,
+}
+
+impl Default for CabacEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CabacEncoder {
+    /// § 9.3.4.1 Initialisation process for the arithmetic encoding engine
+    pub fn new() -> Self {
+        Self {
+            cod_i_low: 0,
+            cod_i_range: 510,
+            first_bit_flag: true,
+            bits_outstanding: 0,
+        }
+    }
+
+    /// PutBit( B )
+    fn put_bit(&mut self, writer: &mut BitWriter, bit: bool) -> Result<()> {
+        if self.first_bit_flag {
+            self.first_bit_flag = false;
+        } else {
+            writer.write_bit(bit)?;
+        }
+        while self.bits_outstanding > 0 {
+            writer.write_bit(!bit)?;
+            self.bits_outstanding -= 1;
+        }
+        Ok(())
+    }
+
+    /// § 9.3.4.3 Renormalization process in the arithmetic encoding engine
+    fn renormalize(&mut self, writer: &mut BitWriter) -> Result<()> {
+        while self.cod_i_range < 256 {
+            if self.cod_i_low < 256 {
+                self.put_bit(writer, false)?;
+            } else if self.cod_i_low >= 512 {
+                self.cod_i_low -= 512;
+                self.put_bit(writer, true)?;
+            } else {
+                self.cod_i_low -= 256;
+                self.bits_outstanding += 1;
+            }
+            self.cod_i_range <<= 1;
+            self.cod_i_low <<= 1;
+        }
+        Ok(())
+    }
+
+    /// EncodeDecision, updates the probability state of `context`
+    ///
+    /// § 9.3.4.2 Encoding process for a binary decision
+    pub fn encode_decision(
+        &mut self,
+        writer: &mut BitWriter,
+        context: &mut ContextVariable,
+        bin_val: bool,
+    ) -> Result<()> {
+        let cod_i_range_lps = context.range_lps(self.cod_i_range);
+        self.cod_i_range -= cod_i_range_lps;
+        if bin_val != context.val_mps {
+            self.cod_i_low += self.cod_i_range;
+            self.cod_i_range = cod_i_range_lps;
+        }
+        context.transit(bin_val);
+        self.renormalize(writer)
+    }
+
+    /// EncodeBypass
+    ///
+    /// § 9.3.4.4 Bypass encoding process for binary decisions
+    pub fn encode_bypass(&mut self, writer: &mut BitWriter, bin_val: bool) -> Result<()> {
+        self.cod_i_low <<= 1;
+        if bin_val {
+            self.cod_i_low += self.cod_i_range;
+        }
+        if self.cod_i_low >= 1024 {
+            self.put_bit(writer, true)?;
+            self.cod_i_low -= 1024;
+        } else if self.cod_i_low < 512 {
+            self.put_bit(writer, false)?;
+        } else {
+            self.cod_i_low -= 512;
+            self.bits_outstanding += 1;
+        }
+        Ok(())
+    }
+
+    /// EncodeTerminate, flushes the encoder when `bin_val` is `true`.
+    ///
+    /// The last bit written by EncodeFlush is `1`, it's rbsp_stop_one_bit, or the bit before
+    /// the pcm_alignment_zero_bits.
+    ///
+    /// § 9.3.4.5 Encoding process for a binary decision before termination
+    pub fn encode_terminate(&mut self, writer: &mut BitWriter, bin_val: bool) -> Result<()> {
+        self.cod_i_range -= 2;
+        if bin_val {
+            self.cod_i_low += self.cod_i_range;
+
+            // EncodeFlush
+            self.cod_i_range = 2;
+            self.renormalize(writer)?;
+            self.put_bit(writer, (self.cod_i_low >> 9) & 1 == 1)?;
+            writer.write_bit((self.cod_i_low >> 8) & 1 == 1)?;
+            writer.write_bit(true)
+        } else {
+            self.renormalize(writer)
+        }
+    }
+
+    /// Encodes a U or TU binarized value, `value` `1` bins followed by a `0` bin unless `value`
+    /// is `c_max`.
+    ///
+    /// Bin `binIdx` is encoded with `contexts[ctx_idx(binIdx)]`.
+    ///
+    /// § 9.3.2.1 Unary (U) binarization process
+    ///
+    /// § 9.3.2.2 Truncated unary (TU) binarization process
+    pub fn encode_unary(
+        &mut self,
+        writer: &mut BitWriter,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: Option<u32>,
+        value: u32,
+    ) -> Result<()> {
+        if matches!(c_max, Some(c_max) if value > c_max) {
+            return Err(BitStreamError::Overflow);
+        }
+        for bin_idx in 0..value {
+            self.encode_decision(writer, &mut contexts[ctx_idx(bin_idx)], true)?;
+        }
+        if Some(value) != c_max {
+            self.encode_decision(writer, &mut contexts[ctx_idx(value)], false)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes a UEGk binarized value, a TU prefix with cMax uCoff encoded with `contexts`,
+    /// followed by a k-th order Exp-Golomb suffix and a sign encoded in bypass mode.
+    ///
+    /// § 9.3.2.3 Concatenated unary/ k-th order Exp-Golomb (UEGk) binarization process
+    pub fn encode_ueg(
+        &mut self,
+        writer: &mut BitWriter,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        binarization: Ueg,
+        value: i64,
+    ) -> Result<()> {
+        let Ueg {
+            k,
+            signed_val_flag,
+            u_coff,
+        } = binarization;
+        if !signed_val_flag && value < 0 {
+            return Err(BitStreamError::Overflow);
+        }
+        let abs = value.unsigned_abs();
+        self.encode_unary(
+            writer,
+            contexts,
+            ctx_idx,
+            Some(u_coff),
+            abs.min(u_coff as u64) as u32,
+        )?;
+
+        if abs >= u_coff as u64 {
+            let mut suf_s = abs - u_coff as u64;
+            let mut k = k;
+            while suf_s >= 1 << k {
+                self.encode_bypass(writer, true)?;
+                suf_s -= 1 << k;
+                k += 1;
+            }
+            self.encode_bypass(writer, false)?;
+            while k > 0 {
+                k -= 1;
+                self.encode_bypass(writer, (suf_s >> k) & 1 == 1)?;
+            }
+        }
+
+        if signed_val_flag && value != 0 {
+            self.encode_bypass(writer, value < 0)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes a FL binarized value in range 0 to `c_max`, from the least significant bit.
+    ///
+    /// § 9.3.2.5 Fixed-length (FL) binarization process
+    pub fn encode_fixed_length(
+        &mut self,
+        writer: &mut BitWriter,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: u32,
+        value: u32,
+    ) -> Result<()> {
+        if value > c_max {
+            return Err(BitStreamError::Overflow);
+        }
+        for bin_idx in 0..32 - c_max.leading_zeros() {
+            self.encode_decision(
+                writer,
+                &mut contexts[ctx_idx(bin_idx)],
+                (value >> bin_idx) & 1 == 1,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Table 9-44 – Specification of rangeTabLPS depending on pStateIdx and qCodIRangeIdx
+const RANGE_TAB_LPS: [[u8; 4]; 64] = [
+    [128, 176, 208, 240],
+    [128, 167, 197, 227],
+    [128, 158, 187, 216],
+    [123, 150, 178, 205],
+    [116, 142, 169, 195],
+    [111, 135, 160, 185],
+    [105, 128, 152, 175],
+    [100, 122, 144, 166],
+    [95, 116, 137, 158],
+    [90, 110, 130, 150],
+    [85, 104, 123, 142],
+    [81, 99, 117, 135],
+    [77, 94, 111, 128],
+    [73, 89, 105, 122],
+    [69, 85, 100, 116],
+    [66, 80, 95, 110],
+    [62, 76, 90, 104],
+    [59, 72, 86, 99],
+    [56, 69, 81, 94],
+    [53, 65, 77, 89],
+    [51, 62, 73, 85],
+    [48, 59, 69, 80],
+    [46, 56, 66, 76],
+    [43, 53, 63, 72],
+    [41, 50, 59, 69],
+    [39, 48, 56, 65],
+    [37, 45, 54, 62],
+    [35, 43, 51, 59],
+    [33, 41, 48, 56],
+    [32, 39, 46, 53],
+    [30, 37, 43, 50],
+    [29, 35, 41, 48],
+    [27, 33, 39, 45],
+    [26, 31, 37, 43],
+    [24, 30, 35, 41],
+    [23, 28, 33, 39],
+    [22, 27, 32, 37],
+    [21, 26, 30, 35],
+    [20, 24, 29, 33],
+    [19, 23, 27, 31],
+    [18, 22, 26, 30],
+    [17, 21, 25, 28],
+    [16, 20, 23, 27],
+    [15, 19, 22, 25],
+    [14, 18, 21, 24],
+    [14, 17, 20, 23],
+    [13, 16, 19, 22],
+    [12, 15, 18, 21],
+    [12, 14, 17, 20],
+    [11, 14, 16, 19],
+    [11, 13, 15, 18],
+    [10, 12, 15, 17],
+    [10, 12, 14, 16],
+    [9, 11, 13, 15],
+    [9, 11, 12, 14],
+    [8, 10, 12, 14],
+    [8, 9, 11, 13],
+    [7, 9, 11, 12],
+    [7, 9, 10, 12],
+    [7, 8, 10, 11],
+    [6, 8, 9, 11],
+    [6, 7, 9, 10],
+    [6, 7, 8, 9],
+    [2, 2, 2, 2],
+];
+
+// Table 9-45 – State transition table, transIdxLPS
+const TRANS_IDX_LPS: [u8; 64] = [
+    0, 0, 1, 2, 2, 4, 4, 5, 6, 7, 8, 9, 9, 11, 11, 12, 13, 13, 15, 15, 16, 16, 18, 18, 19, 19, 21,
+    21, 22, 22, 23, 24, 24, 25, 26, 26, 27, 27, 28, 29, 29, 30, 30, 30, 31, 32, 32, 33, 33, 33, 34,
+    34, 35, 35, 35, 36, 36, 36, 37, 37, 37, 38, 38, 63,
+];
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use crate::nal_unit::{CabacDecoder, CabacEncoder, ContextVariable, Ueg};
+
+    #[test]
+    fn cabac_round_trip() {
+        let context = ContextVariable::new(20, -15, 26);
+        assert_eq!(
+            context,
+            ContextVariable {
+                p_state_idx: 46,
+                val_mps: false
+            }
+        );
+
+        let ueg = Ueg {
+            k: 3,
+            signed_val_flag: true,
+            u_coff: 9,
+        };
+        let mut contexts = [context; 4];
+        let mut encoder = CabacEncoder::new();
+        let mut writer = BitWriter::new();
+        for i in 0..100 {
+            encoder
+                .encode_decision(&mut writer, &mut contexts[0], i % 3 == 0)
+                .unwrap();
+            encoder
+                .encode_unary(
+                    &mut writer,
+                    &mut contexts,
+                    |x| x.min(3) as usize,
+                    Some(5),
+                    i % 6,
+                )
+                .unwrap();
+            encoder
+                .encode_ueg(&mut writer, &mut contexts, |_| 1, ueg, i as i64 * 13 - 600)
+                .unwrap();
+            encoder.encode_terminate(&mut writer, false).unwrap();
+        }
+        encoder.encode_terminate(&mut writer, true).unwrap();
+        writer.byte_align().unwrap();
+        let data = writer.into_inner();
+
+        let mut contexts = [context; 4];
+        let mut stream = BitStream::new(&data);
+        let mut decoder = CabacDecoder::new(&mut stream).unwrap();
+        for i in 0..100 {
+            assert_eq!(
+                decoder
+                    .decode_decision(&mut stream, &mut contexts[0])
+                    .unwrap(),
+                i % 3 == 0
+            );
+            assert_eq!(
+                decoder
+                    .decode_unary(&mut stream, &mut contexts, |x| x.min(3) as usize, Some(5))
+                    .unwrap(),
+                i % 6
+            );
+            assert_eq!(
+                decoder
+                    .decode_ueg(&mut stream, &mut contexts, |_| 1, ueg)
+                    .unwrap(),
+                i as i64 * 13 - 600
+            );
+            assert!(!decoder.decode_terminate(&mut stream).unwrap());
+        }
+        assert!(decoder.decode_terminate(&mut stream).unwrap());
+        // Only rbsp_alignment_zero_bits are left after rbsp_stop_one_bit
+        assert!(stream.remaining() < 8);
+        assert_eq!(
+            data.last().unwrap().trailing_zeros() as usize,
+            stream.remaining()
+        );
+    }
+}
@@ -0,0 +1,1636 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+use bit_stream::{BitStream, BitStreamError, BitWriter, Result};
+
+use crate::nal_unit::{init_context_variables, luma4x4_block_position, sub_mb_type_info,
+                      CabacDecoder, CabacEncoder, ContextVariable, MacroblockLayer,
+                      MacroblockParameters, MappedExpGolombCode, MbPartPredMode, MbPred, MbType,
+                      Residual, ResidualLuma, SignedExpGolombCode, SliceTypeName, SubMbPred,
+                      TruncatedExpGolombCode, Ueg, UnsignedExpGolombCode};
+
+/// Decodes bins into the values passed by mutable reference, or encodes them.
+///
+/// The syntax of a macroblock and the ctxIdx derivations are written once for both directions.
+trait BinCoder {
+    /// Whether values are decoded, the values passed to the other methods are then overwritten
+    fn decoding(&self) -> bool;
+
+    fn decision(&mut self, context: &mut ContextVariable, bin_val: &mut bool) -> Result<()>;
+
+    fn bypass(&mut self, bin_val: &mut bool) -> Result<()>;
+
+    fn terminate(&mut self, bin_val: &mut bool) -> Result<()>;
+
+    fn unary(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: Option<u32>,
+        value: &mut u32,
+    ) -> Result<()>;
+
+    fn ueg(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        binarization: Ueg,
+        value: &mut i64,
+    ) -> Result<()>;
+
+    fn fixed_length(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: u32,
+        value: &mut u32,
+    ) -> Result<()>;
+
+    /// pcm_alignment_zero_bits and the samples of an I_PCM macroblock, after which the
+    /// arithmetic coding engine is initialised again
+    fn pcm_samples(
+        &mut self,
+        luma: &mut [u16],
+        chroma: &mut [u16],
+        bit_depth_luma: u8,
+        bit_depth_chroma: u8,
+    ) -> Result<()>;
+
+    /// Returns the optional syntax element `value`, which is set to `default()` when decoding
+    fn present<'a, T>(
+        &self,
+        value: &'a mut Option<T>,
+        default: impl FnOnce() -> T,
+    ) -> Result<&'a mut T> {
+        if self.decoding() {
+            *value = Some(default());
+        }
+        value.as_mut().ok_or(BitStreamError::MissingValue)
+    }
+
+    /// Returns element `index` of `values`, which is pushed as `default()` when decoding
+    fn element<'a, T>(
+        &self,
+        values: &'a mut Vec<T>,
+        index: usize,
+        default: impl FnOnce() -> T,
+    ) -> Result<&'a mut T> {
+        if self.decoding() {
+            values.push(default());
+        }
+        values.get_mut(index).ok_or(BitStreamError::MissingValue)
+    }
+}
+
+struct Decoding<'a, 'b> {
+    decoder: &'a mut CabacDecoder,
+    stream: &'a mut BitStream<'b>,
+}
+
+impl BinCoder for Decoding<'_, '_> {
+    fn decoding(&self) -> bool {
+        true
+    }
+
+    fn decision(&mut self, context: &mut ContextVariable, bin_val: &mut bool) -> Result<()> {
+        *bin_val = self.decoder.decode_decision(self.stream, context)?;
+        Ok(())
+    }
+
+    fn bypass(&mut self, bin_val: &mut bool) -> Result<()> {
+        *bin_val = self.decoder.decode_bypass(self.stream)?;
+        Ok(())
+    }
+
+    fn terminate(&mut self, bin_val: &mut bool) -> Result<()> {
+        *bin_val = self.decoder.decode_terminate(self.stream)?;
+        Ok(())
+    }
+
+    fn unary(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: Option<u32>,
+        value: &mut u32,
+    ) -> Result<()> {
+        *value = self
+            .decoder
+            .decode_unary(self.stream, contexts, ctx_idx, c_max)?;
+        Ok(())
+    }
+
+    fn ueg(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        binarization: Ueg,
+        value: &mut i64,
+    ) -> Result<()> {
+        *value = self
+            .decoder
+            .decode_ueg(self.stream, contexts, ctx_idx, binarization)?;
+        Ok(())
+    }
+
+    fn fixed_length(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: u32,
+        value: &mut u32,
+    ) -> Result<()> {
+        *value = self
+            .decoder
+            .decode_fixed_length(self.stream, contexts, ctx_idx, c_max)?;
+        Ok(())
+    }
+
+    fn pcm_samples(
+        &mut self,
+        luma: &mut [u16],
+        chroma: &mut [u16],
+        bit_depth_luma: u8,
+        bit_depth_chroma: u8,
+    ) -> Result<()> {
+        while !self.stream.byte_aligned() {
+            // pcm_alignment_zero_bit
+            if self.stream.read_bit()? {
+                return Err(BitStreamError::InvalidValue);
+            }
+        }
+        for sample in luma {
+            *sample = self.stream.read(bit_depth_luma)?;
+        }
+        for sample in chroma {
+            *sample = self.stream.read(bit_depth_chroma)?;
+        }
+        *self.decoder = CabacDecoder::new(self.stream)?;
+        Ok(())
+    }
+}
+
+struct Encoding<'a> {
+    encoder: &'a mut CabacEncoder,
+    writer: &'a mut BitWriter,
+}
+
+impl BinCoder for Encoding<'_> {
+    fn decoding(&self) -> bool {
+        false
+    }
+
+    fn decision(&mut self, context: &mut ContextVariable, bin_val: &mut bool) -> Result<()> {
+        self.encoder.encode_decision(self.writer, context, *bin_val)
+    }
+
+    fn bypass(&mut self, bin_val: &mut bool) -> Result<()> {
+        self.encoder.encode_bypass(self.writer, *bin_val)
+    }
+
+    fn terminate(&mut self, bin_val: &mut bool) -> Result<()> {
+        self.encoder.encode_terminate(self.writer, *bin_val)
+    }
+
+    fn unary(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: Option<u32>,
+        value: &mut u32,
+    ) -> Result<()> {
+        self.encoder
+            .encode_unary(self.writer, contexts, ctx_idx, c_max, *value)
+    }
+
+    fn ueg(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        binarization: Ueg,
+        value: &mut i64,
+    ) -> Result<()> {
+        self.encoder
+            .encode_ueg(self.writer, contexts, ctx_idx, binarization, *value)
+    }
+
+    fn fixed_length(
+        &mut self,
+        contexts: &mut [ContextVariable],
+        ctx_idx: impl Fn(u32) -> usize,
+        c_max: u32,
+        value: &mut u32,
+    ) -> Result<()> {
+        self.encoder
+            .encode_fixed_length(self.writer, contexts, ctx_idx, c_max, *value)
+    }
+
+    fn pcm_samples(
+        &mut self,
+        luma: &mut [u16],
+        chroma: &mut [u16],
+        bit_depth_luma: u8,
+        bit_depth_chroma: u8,
+    ) -> Result<()> {
+        while !self.writer.byte_aligned() {
+            self.writer.write_bit(false)?;
+        }
+        for sample in luma.iter() {
+            self.writer.write(sample, bit_depth_luma)?;
+        }
+        for sample in chroma.iter() {
+            self.writer.write(sample, bit_depth_chroma)?;
+        }
+        *self.encoder = CabacEncoder::new();
+        Ok(())
+    }
+}
+
+/// Bin strings of mb_type in P and SP slices, 5 is the prefix of intra macroblock types
+///
+/// Table 9-37 – Binarization for macroblock types in P, SP, and B slices
+const P_MB_TYPE_BIN_STRINGS: [(u64, &str); 5] =
+    [(0, "000"), (1, "011"), (2, "010"), (3, "001"), (5, "1")];
+
+/// Bin strings of mb_type in B slices, 23 is the prefix of intra macroblock types
+///
+/// Table 9-37 – Binarization for macroblock types in P, SP, and B slices
+const B_MB_TYPE_BIN_STRINGS: [(u64, &str); 24] = [
+    (0, "0"),
+    (1, "100"),
+    (2, "101"),
+    (3, "110000"),
+    (4, "110001"),
+    (5, "110010"),
+    (6, "110011"),
+    (7, "110100"),
+    (8, "110101"),
+    (9, "110110"),
+    (10, "110111"),
+    (11, "111110"),
+    (12, "1110000"),
+    (13, "1110001"),
+    (14, "1110010"),
+    (15, "1110011"),
+    (16, "1110100"),
+    (17, "1110101"),
+    (18, "1110110"),
+    (19, "1110111"),
+    (20, "1111000"),
+    (21, "1111001"),
+    (22, "111111"),
+    (23, "111101"),
+];
+
+/// Table 9-38 – Binarization for sub-macroblock types in P, SP, and B slices
+const P_SUB_MB_TYPE_BIN_STRINGS: [(u64, &str); 4] = [(0, "1"), (1, "00"), (2, "011"), (3, "010")];
+
+/// Table 9-38 – Binarization for sub-macroblock types in P, SP, and B slices
+const B_SUB_MB_TYPE_BIN_STRINGS: [(u64, &str); 13] = [
+    (0, "0"),
+    (1, "100"),
+    (2, "101"),
+    (3, "11000"),
+    (4, "11001"),
+    (5, "11010"),
+    (6, "11011"),
+    (7, "111000"),
+    (8, "111001"),
+    (9, "111010"),
+    (10, "111011"),
+    (11, "11110"),
+    (12, "11111"),
+];
+
+// Table 9-40 – Assignment of ctxBlockCatOffset to ctxBlockCat for syntax elements
+// coded_block_flag, significant_coeff_flag, last_significant_coeff_flag and
+// coeff_abs_level_minus1, indexed by ctxBlockCat 0 to 4
+const CODED_BLOCK_FLAG_CAT_OFFSET: [usize; 5] = [0, 4, 8, 12, 16];
+const SIGNIFICANT_COEFF_CAT_OFFSET: [usize; 5] = [0, 15, 29, 44, 47];
+const COEFF_ABS_LEVEL_CAT_OFFSET: [usize; 5] = [0, 10, 20, 30, 39];
+
+// Table 9-43 – Mapping of scanning position to ctxIdxInc for ctxBlockCat == 5,
+// significant_coeff_flag of frame and field coded blocks, and last_significant_coeff_flag
+const SIGNIFICANT_COEFF_8X8_CTX_IDX_INC: [[u8; 63]; 2] = [
+    [
+        0, 1, 2, 3, 4, 5, 5, 4, 4, 3, 3, 4, 4, 4, 5, 5, 4, 4, 4, 4, 3, 3, 6, 7, 7, 7, 8, 9, 10, 9,
+        8, 7, 7, 6, 11, 12, 13, 11, 6, 7, 8, 9, 14, 10, 9, 8, 6, 11, 12, 13, 11, 6, 9, 14, 10, 9,
+        11, 12, 13, 11, 14, 10, 12,
+    ],
+    [
+        0, 1, 1, 2, 2, 3, 3, 4, 5, 6, 7, 7, 7, 8, 4, 5, 6, 9, 10, 10, 8, 11, 12, 11, 9, 9, 10, 10,
+        8, 11, 12, 11, 9, 9, 10, 10, 8, 11, 12, 11, 9, 9, 10, 10, 8, 13, 13, 9, 9, 10, 10, 8, 13,
+        13, 9, 9, 10, 10, 14, 14, 14, 14, 14,
+    ],
+];
+const LAST_SIGNIFICANT_COEFF_8X8_CTX_IDX_INC: [u8; 63] = [
+    0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    3, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8,
+];
+
+/// A transform block coded with residual_block_cabac( ), and its position in 4x4 blocks
+#[derive(Clone, Copy, Debug)]
+enum TransformBlock {
+    /// Intra16x16DCLevel, ctxBlockCat 0
+    Intra16x16Dc,
+    /// Intra16x16ACLevel, ctxBlockCat 1
+    Intra16x16Ac(usize, usize),
+    /// LumaLevel4x4, ctxBlockCat 2
+    Luma4x4(usize, usize),
+    /// ChromaDCLevel of Cb or Cr as component 1 or 2, ctxBlockCat 3
+    ChromaDc(usize),
+    /// ChromaACLevel of Cb or Cr as component 1 or 2, ctxBlockCat 4
+    ChromaAc(usize, usize, usize),
+    /// LumaLevel8x8, ctxBlockCat 5
+    Luma8x8(usize, usize),
+}
+
+impl TransformBlock {
+    /// Table 9-42 – Specification of ctxBlockCat for the different blocks
+    fn ctx_block_cat(&self) -> usize {
+        match self {
+            TransformBlock::Intra16x16Dc => 0,
+            TransformBlock::Intra16x16Ac(..) => 1,
+            TransformBlock::Luma4x4(..) => 2,
+            TransformBlock::ChromaDc(_) => 3,
+            TransformBlock::ChromaAc(..) => 4,
+            TransformBlock::Luma8x8(..) => 5,
+        }
+    }
+}
+
+/// Syntax elements of a macroblock used to derive ctxIdxInc in the following macroblocks
+#[derive(Clone, Debug, Default)]
+struct MacroblockState {
+    /// P_Skip or B_Skip
+    skip: bool,
+    /// `None` for P_Skip and B_Skip
+    mb_type: Option<MbType>,
+    transform_size_8x8_flag: bool,
+    /// 15 for I_PCM
+    coded_block_pattern_luma: u8,
+    /// 2 for I_PCM
+    coded_block_pattern_chroma: u8,
+    intra_chroma_pred_mode: u64,
+    mb_qp_delta: i64,
+    /// Whether refIdxLX is greater than 0, indexed by `[X][y][x]` in 8x8 blocks
+    ref_idx_greater_than_0: [[[bool; 2]; 2]; 2],
+    /// absMvdComp, indexed by `[X][y][x][compIdx]` in 4x4 blocks
+    abs_mvd_comp: [[[[u32; 2]; 4]; 4]; 2],
+    /// coded_block_flag of the DC blocks of Y, Cb and Cr
+    coded_block_flag_dc: [bool; 3],
+    /// coded_block_flag of the 4x4 blocks of Y, Cb and Cr, indexed by `[component][y][x]`.
+    /// Blocks of coded 8x8 luma blocks have it inferred to be 1.
+    coded_block_flag: [[[bool; 4]; 4]; 3],
+}
+
+impl MacroblockState {
+    fn intra(&self) -> bool {
+        matches!(
+            self.mb_type,
+            Some(MbType::INxN | MbType::I16x16 { .. } | MbType::IPcm | MbType::Si)
+        )
+    }
+}
+
+/// Position and size in 4x4 blocks of macroblock partition `mb_part_idx`
+fn mb_partition(mb_type: MbType, mb_part_idx: usize) -> (usize, usize, usize, usize) {
+    match mb_type {
+        MbType::Inter16x8(..) => (0, mb_part_idx * 2, 4, 2),
+        MbType::Inter8x16(..) => (mb_part_idx * 2, 0, 2, 4),
+        MbType::P8x8 | MbType::P8x8Ref0 | MbType::B8x8 => {
+            (mb_part_idx % 2 * 2, mb_part_idx / 2 * 2, 2, 2)
+        }
+        _ => (0, 0, 4, 4),
+    }
+}
+
+/// Position and size in 4x4 blocks of sub-macroblock partition `sub_mb_part_idx` of
+/// sub-macroblock `mb_part_idx`
+fn sub_mb_partition(
+    slice_type_name: SliceTypeName,
+    sub_mb_type: u64,
+    mb_part_idx: usize,
+    sub_mb_part_idx: usize,
+) -> Result<(usize, usize, usize, usize)> {
+    let (_, num_sub_mb_part) = sub_mb_type_info(slice_type_name, sub_mb_type)?;
+    let (width, height) = match num_sub_mb_part {
+        1 => (2, 2),
+        // 8x4 partitions have sub_mb_type 1 in P slices, and 4, 6 and 8 in B slices
+        2 if (slice_type_name == SliceTypeName::B) == (sub_mb_type & 1 == 0) => (2, 1),
+        2 => (1, 2),
+        _ => (1, 1),
+    };
+    Ok((
+        mb_part_idx % 2 * 2 + sub_mb_part_idx * width % 2,
+        mb_part_idx / 2 * 2 + sub_mb_part_idx * width / 2 * height,
+        width,
+        height,
+    ))
+}
+
+/// Context variables and syntax elements of the macroblocks in a slice, to derive ctxIdx of
+/// the following bins.
+///
+/// Only frames and fields without MBAFF are supported, where the neighbouring macroblocks
+/// A and B are `CurrMbAddr - 1` and `CurrMbAddr - PicWidthInMbs`, and ChromaArrayType must not
+/// be 3.
+///
+/// § 9.3.3.1 Derivation process for ctxIdx
+#[derive(Clone, Debug)]
+pub struct CabacContext {
+    pic_width_in_mbs: u64,
+    field_pic_flag: bool,
+    context_variables: Vec<ContextVariable>,
+    curr_mb_addr: u64,
+    /// Macroblocks not in the map are not available
+    macroblocks: HashMap<u64, MacroblockState>,
+}
+
+impl CabacContext {
+    /// Initialises the context variables of a slice, see [`init_context_variables`]
+    pub fn new(
+        pic_width_in_mbs: u64,
+        field_pic_flag: bool,
+        cabac_init_idc: Option<u64>,
+        slice_qp_y: i32,
+    ) -> Result<Self> {
+        Ok(Self {
+            pic_width_in_mbs,
+            field_pic_flag,
+            context_variables: init_context_variables(cabac_init_idc, slice_qp_y)?,
+            curr_mb_addr: 0,
+            macroblocks: HashMap::new(),
+        })
+    }
+
+    /// Starts macroblock `mb_addr`, before its mb_skip_flag
+    pub fn start_macroblock(&mut self, mb_addr: u64) {
+        self.curr_mb_addr = mb_addr;
+        self.macroblocks.insert(mb_addr, Default::default());
+    }
+
+    /// Reads mb_skip_flag of the current macroblock in a P, SP or B slice
+    pub fn read_mb_skip_flag(
+        &mut self,
+        decoder: &mut CabacDecoder,
+        stream: &mut BitStream,
+        slice_type_name: SliceTypeName,
+    ) -> Result<bool> {
+        let mut mb_skip_flag = false;
+        self.mb_skip_flag(
+            &mut Decoding { decoder, stream },
+            slice_type_name,
+            &mut mb_skip_flag,
+        )?;
+        Ok(mb_skip_flag)
+    }
+
+    pub fn write_mb_skip_flag(
+        &mut self,
+        encoder: &mut CabacEncoder,
+        writer: &mut BitWriter,
+        slice_type_name: SliceTypeName,
+        mb_skip_flag: bool,
+    ) -> Result<()> {
+        self.mb_skip_flag(&mut Encoding { encoder, writer }, slice_type_name, &mut {
+            mb_skip_flag
+        })
+    }
+
+    /// Reads macroblock_layer( ) of the current macroblock
+    pub fn read_macroblock_layer(
+        &mut self,
+        decoder: &mut CabacDecoder,
+        stream: &mut BitStream,
+        params: &MacroblockParameters,
+    ) -> Result<MacroblockLayer> {
+        let mut layer = MacroblockLayer {
+            mb_type: MbType::INxN,
+            pcm_sample_luma: None,
+            pcm_sample_chroma: None,
+            sub_mb_pred: None,
+            mb_pred: None,
+            transform_size_8x8_flag: None,
+            coded_block_pattern: None,
+            mb_qp_delta: None,
+            residual: None,
+        };
+        self.macroblock_layer(&mut Decoding { decoder, stream }, params, &mut layer)?;
+        Ok(layer)
+    }
+
+    pub fn write_macroblock_layer(
+        &mut self,
+        encoder: &mut CabacEncoder,
+        writer: &mut BitWriter,
+        params: &MacroblockParameters,
+        layer: &MacroblockLayer,
+    ) -> Result<()> {
+        self.macroblock_layer(
+            &mut Encoding { encoder, writer },
+            params,
+            &mut layer.clone(),
+        )
+    }
+
+    fn current(&self) -> Option<&MacroblockState> {
+        self.macroblocks.get(&self.curr_mb_addr)
+    }
+
+    fn current_mut(&mut self) -> &mut MacroblockState {
+        self.macroblocks.entry(self.curr_mb_addr).or_default()
+    }
+
+    /// § 6.4.9 Derivation process for neighbouring macroblock addresses and their availability
+    fn mb_a(&self) -> Option<&MacroblockState> {
+        if self.curr_mb_addr.is_multiple_of(self.pic_width_in_mbs) {
+            None
+        } else {
+            self.macroblocks.get(&(self.curr_mb_addr - 1))
+        }
+    }
+
+    fn mb_b(&self) -> Option<&MacroblockState> {
+        self.curr_mb_addr
+            .checked_sub(self.pic_width_in_mbs)
+            .and_then(|mb_addr| self.macroblocks.get(&mb_addr))
+    }
+
+    /// Returns the macroblock and the position of the block left of the block at (`x`, `y`)
+    /// in the current macroblock, where macroblocks are `width` blocks wide
+    fn left(&self, x: usize, y: usize, width: usize) -> Option<(&MacroblockState, usize, usize)> {
+        if x > 0 {
+            self.current().map(|mb| (mb, x - 1, y))
+        } else {
+            self.mb_a().map(|mb| (mb, width - 1, y))
+        }
+    }
+
+    /// Returns the macroblock and the position of the block above the block at (`x`, `y`)
+    /// in the current macroblock, where macroblocks are `height` blocks high
+    fn above(&self, x: usize, y: usize, height: usize) -> Option<(&MacroblockState, usize, usize)> {
+        if y > 0 {
+            self.current().map(|mb| (mb, x, y - 1))
+        } else {
+            self.mb_b().map(|mb| (mb, x, height - 1))
+        }
+    }
+
+    /// Codes `value` with a binarization given by the bin strings of its values, bin `binIdx`
+    /// is coded with `ctx_idx(binIdx, previous bins)`
+    fn bin_string(
+        &mut self,
+        coder: &mut impl BinCoder,
+        bin_strings: &[(u64, &str)],
+        ctx_idx: impl Fn(usize, &[bool]) -> usize,
+        value: &mut u64,
+    ) -> Result<()> {
+        let encoded = if coder.decoding() {
+            None
+        } else {
+            let (_, bin_string) = bin_strings
+                .iter()
+                .find(|(x, _)| x == value)
+                .ok_or(BitStreamError::InvalidValue)?;
+            Some(bin_string.as_bytes())
+        };
+        let starts_with = |bin_string: &str, bins: &[bool]| {
+            bin_string.len() >= bins.len()
+                && bin_string
+                    .bytes()
+                    .zip(bins)
+                    .all(|(x, &bin)| (x == b'1') == bin)
+        };
+
+        let mut bins = vec![];
+        loop {
+            let mut bin = encoded.is_some_and(|x| x[bins.len()] == b'1');
+            coder.decision(
+                &mut self.context_variables[ctx_idx(bins.len(), &bins)],
+                &mut bin,
+            )?;
+            bins.push(bin);
+
+            if let Some((x, _)) = bin_strings
+                .iter()
+                .find(|(_, x)| x.len() == bins.len() && starts_with(x, &bins))
+            {
+                *value = *x;
+                return Ok(());
+            }
+            if !bin_strings.iter().any(|(_, x)| starts_with(x, &bins)) {
+                return Err(BitStreamError::InvalidValue);
+            }
+        }
+    }
+
+    fn mb_skip_flag(
+        &mut self,
+        coder: &mut impl BinCoder,
+        slice_type_name: SliceTypeName,
+        mb_skip_flag: &mut bool,
+    ) -> Result<()> {
+        let cond = |mb: Option<&MacroblockState>| mb.map_or(0, |mb| !mb.skip as usize);
+        let ctx_idx_offset = if slice_type_name == SliceTypeName::B {
+            24
+        } else {
+            11
+        };
+        let ctx_idx = ctx_idx_offset + cond(self.mb_a()) + cond(self.mb_b());
+        coder.decision(&mut self.context_variables[ctx_idx], mb_skip_flag)?;
+        self.current_mut().skip = *mb_skip_flag;
+        Ok(())
+    }
+
+    /// mb_type, with the prefix and suffix of SI, P, SP and B slices
+    fn mb_type(
+        &mut self,
+        coder: &mut impl BinCoder,
+        slice_type_name: SliceTypeName,
+        mb_type: &mut MbType,
+    ) -> Result<()> {
+        let mut value = if coder.decoding() {
+            0
+        } else {
+            mb_type.value(slice_type_name)?
+        };
+
+        match slice_type_name {
+            SliceTypeName::I => self.intra_mb_type(coder, 3, &mut value)?,
+            SliceTypeName::SI => {
+                let cond = |mb: Option<&MacroblockState>| {
+                    mb.map_or(0, |mb| (mb.mb_type != Some(MbType::Si)) as usize)
+                };
+                let ctx_idx = cond(self.mb_a()) + cond(self.mb_b());
+                let mut prefix = value != 0;
+                coder.decision(&mut self.context_variables[ctx_idx], &mut prefix)?;
+                if prefix {
+                    let mut suffix = value.saturating_sub(1);
+                    self.intra_mb_type(coder, 3, &mut suffix)?;
+                    value = suffix + 1;
+                } else {
+                    value = 0;
+                }
+            }
+            SliceTypeName::P | SliceTypeName::SP => {
+                let mut prefix = value.min(5);
+                self.bin_string(
+                    coder,
+                    &P_MB_TYPE_BIN_STRINGS,
+                    |bin_idx, bins| match bin_idx {
+                        0 => 14,
+                        1 => 15,
+                        _ => 16 + bins[1] as usize,
+                    },
+                    &mut prefix,
+                )?;
+                if prefix == 5 {
+                    let mut suffix = value.saturating_sub(5);
+                    self.intra_mb_type(coder, 17, &mut suffix)?;
+                    value = suffix + 5;
+                } else {
+                    value = prefix;
+                }
+            }
+            SliceTypeName::B => {
+                let cond = |mb: Option<&MacroblockState>| {
+                    mb.map_or(0, |mb| {
+                        !(mb.skip || mb.mb_type == Some(MbType::BDirect16x16)) as usize
+                    })
+                };
+                let ctx_idx_inc = cond(self.mb_a()) + cond(self.mb_b());
+                let mut prefix = value.min(23);
+                self.bin_string(
+                    coder,
+                    &B_MB_TYPE_BIN_STRINGS,
+                    |bin_idx, bins| match bin_idx {
+                        0 => 27 + ctx_idx_inc,
+                        1 => 30,
+                        2 => 32 - bins[1] as usize,
+                        _ => 32,
+                    },
+                    &mut prefix,
+                )?;
+                if prefix == 23 {
+                    let mut suffix = value.saturating_sub(23);
+                    self.intra_mb_type(coder, 32, &mut suffix)?;
+                    value = suffix + 23;
+                } else {
+                    value = prefix;
+                }
+            }
+        }
+
+        if coder.decoding() {
+            *mb_type = MbType::new(slice_type_name, value)?;
+        }
+        Ok(())
+    }
+
+    /// mb_type of I slices with ctxIdxOffset 3, or the suffix of mb_type in SI, P, SP and B
+    /// slices with ctxIdxOffset 3, 17, 17 and 32. `value` is the mb_type of Table 7-11.
+    ///
+    /// Table 9-36 – Binarization for macroblock types in I slices
+    fn intra_mb_type(
+        &mut self,
+        coder: &mut impl BinCoder,
+        ctx_idx_offset: usize,
+        value: &mut u64,
+    ) -> Result<()> {
+        let prefix = ctx_idx_offset == 3;
+        let ctx_idx = if prefix {
+            let cond = |mb: Option<&MacroblockState>| {
+                mb.map_or(0, |mb| (mb.mb_type != Some(MbType::INxN)) as usize)
+            };
+            3 + cond(self.mb_a()) + cond(self.mb_b())
+        } else {
+            ctx_idx_offset
+        };
+        let mut not_i_nxn = *value != 0;
+        coder.decision(&mut self.context_variables[ctx_idx], &mut not_i_nxn)?;
+        if !not_i_nxn {
+            *value = 0;
+            return Ok(());
+        }
+
+        let mut pcm = *value == 25;
+        coder.terminate(&mut pcm)?;
+        if pcm {
+            *value = 25;
+            return Ok(());
+        }
+
+        // ctxIdx of the bins of CodedBlockPatternLuma, CodedBlockPatternChroma and
+        // Intra16x16PredMode
+        let (luma_ctx_idx, chroma_ctx_idx, pred_mode_ctx_idx) = if prefix {
+            (6, [7, 8], [9, 10])
+        } else {
+            (
+                ctx_idx_offset + 1,
+                [ctx_idx_offset + 2; 2],
+                [ctx_idx_offset + 3; 2],
+            )
+        };
+        let i16x16 = value.saturating_sub(1);
+        let mut luma = i16x16 >= 12;
+        coder.decision(&mut self.context_variables[luma_ctx_idx], &mut luma)?;
+        let mut chroma = (i16x16 / 4 % 3) as u32;
+        coder.unary(
+            &mut self.context_variables,
+            |bin_idx| chroma_ctx_idx[bin_idx as usize],
+            Some(2),
+            &mut chroma,
+        )?;
+        let mut pred_mode = (i16x16 % 4) as u32;
+        let mut high = pred_mode >= 2;
+        coder.decision(&mut self.context_variables[pred_mode_ctx_idx[0]], &mut high)?;
+        let mut low = pred_mode % 2 == 1;
+        coder.decision(&mut self.context_variables[pred_mode_ctx_idx[1]], &mut low)?;
+        pred_mode = high as u32 * 2 + low as u32;
+
+        *value = 1 + pred_mode as u64 + 4 * chroma as u64 + 12 * luma as u64;
+        Ok(())
+    }
+
+    fn sub_mb_type(
+        &mut self,
+        coder: &mut impl BinCoder,
+        slice_type_name: SliceTypeName,
+        sub_mb_type: &mut UnsignedExpGolombCode,
+    ) -> Result<()> {
+        if slice_type_name == SliceTypeName::B {
+            self.bin_string(
+                coder,
+                &B_SUB_MB_TYPE_BIN_STRINGS,
+                |bin_idx, bins| match bin_idx {
+                    0 => 36,
+                    1 => 37,
+                    2 => 39 - bins[1] as usize,
+                    _ => 39,
+                },
+                &mut sub_mb_type.0,
+            )
+        } else {
+            self.bin_string(
+                coder,
+                &P_SUB_MB_TYPE_BIN_STRINGS,
+                |bin_idx, _| 21 + bin_idx,
+                &mut sub_mb_type.0,
+            )
+        }
+    }
+
+    fn transform_size_8x8_flag(
+        &mut self,
+        coder: &mut impl BinCoder,
+        transform_size_8x8_flag: &mut bool,
+    ) -> Result<()> {
+        let cond =
+            |mb: Option<&MacroblockState>| mb.map_or(0, |mb| mb.transform_size_8x8_flag as usize);
+        let ctx_idx = 399 + cond(self.mb_a()) + cond(self.mb_b());
+        coder.decision(
+            &mut self.context_variables[ctx_idx],
+            transform_size_8x8_flag,
+        )?;
+        self.current_mut().transform_size_8x8_flag = *transform_size_8x8_flag;
+        Ok(())
+    }
+
+    /// ref_idx_lX of list `list` of the partition at `partition`, see [`mb_partition`]
+    fn ref_idx(
+        &mut self,
+        coder: &mut impl BinCoder,
+        list: usize,
+        num_ref_idx_active_minus1: u64,
+        (x, y, width, height): (usize, usize, usize, usize),
+        ref_idx: &mut TruncatedExpGolombCode,
+    ) -> Result<()> {
+        let cond = |n: Option<(&MacroblockState, usize, usize)>| {
+            n.map_or(0, |(mb, x, y)| {
+                mb.ref_idx_greater_than_0[list][y / 2][x / 2] as usize
+            })
+        };
+        let ctx_idx_inc = cond(self.left(x, y, 4)) + 2 * cond(self.above(x, y, 4));
+
+        let mut value = u32::try_from(ref_idx.0).or(Err(BitStreamError::Overflow))?;
+        coder.unary(
+            &mut self.context_variables,
+            |bin_idx| match bin_idx {
+                0 => 54 + ctx_idx_inc,
+                1 => 58,
+                _ => 59,
+            },
+            None,
+            &mut value,
+        )?;
+        if value as u64 > num_ref_idx_active_minus1 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        ref_idx.0 = value as u64;
+
+        let current = self.current_mut();
+        for row in &mut current.ref_idx_greater_than_0[list][y / 2..(y + height) / 2] {
+            for x8x8 in &mut row[x / 2..(x + width) / 2] {
+                *x8x8 = value > 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// mvd_lX of list `list` of the partition at `partition`, see [`mb_partition`]
+    fn mvd(
+        &mut self,
+        coder: &mut impl BinCoder,
+        list: usize,
+        (x, y, width, height): (usize, usize, usize, usize),
+        mvd: &mut [SignedExpGolombCode; 2],
+    ) -> Result<()> {
+        let ueg = Ueg {
+            k: 3,
+            signed_val_flag: true,
+            u_coff: 9,
+        };
+        for (comp_idx, mvd) in mvd.iter_mut().enumerate() {
+            let abs_mvd_comp = |n: Option<(&MacroblockState, usize, usize)>| {
+                n.map_or(0, |(mb, x, y)| mb.abs_mvd_comp[list][y][x][comp_idx])
+            };
+            let sum =
+                abs_mvd_comp(self.left(x, y, 4)).saturating_add(abs_mvd_comp(self.above(x, y, 4)));
+            let ctx_idx_inc = match sum {
+                0..=2 => 0,
+                3..=32 => 1,
+                _ => 2,
+            };
+            let ctx_idx_offset = if comp_idx == 0 { 40 } else { 47 };
+            coder.ueg(
+                &mut self.context_variables,
+                |bin_idx| {
+                    ctx_idx_offset
+                        + match bin_idx {
+                            0 => ctx_idx_inc,
+                            1..=3 => bin_idx as usize + 2,
+                            _ => 6,
+                        }
+                },
+                ueg,
+                &mut mvd.0,
+            )?;
+        }
+
+        let current = self.current_mut();
+        for row in &mut current.abs_mvd_comp[list][y..y + height] {
+            for abs_mvd_comp in &mut row[x..x + width] {
+                for (comp_idx, mvd) in mvd.iter().enumerate() {
+                    abs_mvd_comp[comp_idx] = mvd.0.unsigned_abs().min(u32::MAX as u64) as u32;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// § 7.3.5.1 Macroblock prediction syntax
+    fn mb_pred(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        mb_type: MbType,
+        transform_size_8x8_flag: bool,
+        mb_pred: &mut MbPred,
+    ) -> Result<()> {
+        let mb_part_pred_mode = mb_type.mb_part_pred_mode(0, transform_size_8x8_flag);
+        match mb_part_pred_mode {
+            Some(MbPartPredMode::Intra4x4) | Some(MbPartPredMode::Intra8x8) => {
+                let (count, flags, rems) = if mb_part_pred_mode == Some(MbPartPredMode::Intra4x4) {
+                    (
+                        16,
+                        &mut mb_pred.prev_intra4x4_pred_mode_flag,
+                        &mut mb_pred.rem_intra4x4_pred_mode,
+                    )
+                } else {
+                    (
+                        4,
+                        &mut mb_pred.prev_intra8x8_pred_mode_flag,
+                        &mut mb_pred.rem_intra8x8_pred_mode,
+                    )
+                };
+                for i in 0..count {
+                    let flag = coder.element(flags, i, || false)?;
+                    coder.decision(&mut self.context_variables[68], flag)?;
+                    let flag = *flag;
+                    let rem = coder.element(rems, i, || None)?;
+                    if !flag {
+                        let rem = coder.present(rem, || 0)?;
+                        let mut value = *rem as u32;
+                        coder.fixed_length(&mut self.context_variables, |_| 69, 7, &mut value)?;
+                        *rem = value as u8;
+                    }
+                }
+            }
+            Some(MbPartPredMode::Intra16x16) | Some(MbPartPredMode::Direct) => {}
+            _ => {
+                let lists = [
+                    (
+                        &mut mb_pred.ref_idx_l0,
+                        &mut mb_pred.mvd_l0,
+                        params.num_ref_idx_l0_active_minus1,
+                        MbPartPredMode::PredL1,
+                    ),
+                    (
+                        &mut mb_pred.ref_idx_l1,
+                        &mut mb_pred.mvd_l1,
+                        params.num_ref_idx_l1_active_minus1,
+                        MbPartPredMode::PredL0,
+                    ),
+                ];
+                let modes: Vec<_> = (0..mb_type.num_mb_part())
+                    .map(|i| mb_type.mb_part_pred_mode(i, false))
+                    .collect();
+
+                let mut lists = lists;
+                for (list, (ref_idxs, _, num_ref_idx_active_minus1, other)) in
+                    lists.iter_mut().enumerate()
+                {
+                    for (mb_part_idx, mode) in modes.iter().enumerate() {
+                        let ref_idx = coder.element(ref_idxs, mb_part_idx, || None)?;
+                        if *num_ref_idx_active_minus1 > 0 && *mode != Some(*other) {
+                            let ref_idx = coder.present(ref_idx, || TruncatedExpGolombCode(0))?;
+                            self.ref_idx(
+                                coder,
+                                list,
+                                *num_ref_idx_active_minus1,
+                                mb_partition(mb_type, mb_part_idx),
+                                ref_idx,
+                            )?;
+                        }
+                    }
+                }
+                for (list, (_, mvds, _, other)) in lists.iter_mut().enumerate() {
+                    for (mb_part_idx, mode) in modes.iter().enumerate() {
+                        let mvd = coder.element(mvds, mb_part_idx, || None)?;
+                        if *mode != Some(*other) {
+                            let mvd = coder.present(mvd, || [SignedExpGolombCode(0); 2])?;
+                            self.mvd(coder, list, mb_partition(mb_type, mb_part_idx), mvd)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let intra = matches!(
+            mb_part_pred_mode,
+            Some(MbPartPredMode::Intra4x4)
+                | Some(MbPartPredMode::Intra8x8)
+                | Some(MbPartPredMode::Intra16x16)
+        );
+        if intra && (params.chroma_array_type == 1 || params.chroma_array_type == 2) {
+            let cond = |mb: Option<&MacroblockState>| {
+                mb.map_or(0, |mb| {
+                    (mb.intra()
+                        && mb.mb_type != Some(MbType::IPcm)
+                        && mb.intra_chroma_pred_mode != 0) as usize
+                })
+            };
+            let ctx_idx_inc = cond(self.mb_a()) + cond(self.mb_b());
+            let intra_chroma_pred_mode = coder
+                .present(&mut mb_pred.intra_chroma_pred_mode, || {
+                    UnsignedExpGolombCode(0)
+                })?;
+            let mut value =
+                u32::try_from(intra_chroma_pred_mode.0).or(Err(BitStreamError::Overflow))?;
+            coder.unary(
+                &mut self.context_variables,
+                |bin_idx| 64 + if bin_idx == 0 { ctx_idx_inc } else { 3 },
+                Some(3),
+                &mut value,
+            )?;
+            intra_chroma_pred_mode.0 = value as u64;
+            self.current_mut().intra_chroma_pred_mode = value as u64;
+        }
+        Ok(())
+    }
+
+    /// § 7.3.5.2 Sub-macroblock prediction syntax
+    fn sub_mb_pred(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        sub_mb_pred: &mut SubMbPred,
+    ) -> Result<()> {
+        let slice_type_name = params.slice_type_name;
+        let mut sub_mb_types = Vec::with_capacity(4);
+        for mb_part_idx in 0..4 {
+            let sub_mb_type = coder.element(&mut sub_mb_pred.sub_mb_type, mb_part_idx, || {
+                UnsignedExpGolombCode(0)
+            })?;
+            self.sub_mb_type(coder, slice_type_name, sub_mb_type)?;
+            let (mode, num_sub_mb_part) = sub_mb_type_info(slice_type_name, sub_mb_type.0)?;
+            sub_mb_types.push((sub_mb_type.0, mode, num_sub_mb_part));
+        }
+
+        let mut lists = [
+            (
+                &mut sub_mb_pred.ref_idx_l0,
+                &mut sub_mb_pred.mvd_l0,
+                params.num_ref_idx_l0_active_minus1,
+                MbPartPredMode::PredL1,
+            ),
+            (
+                &mut sub_mb_pred.ref_idx_l1,
+                &mut sub_mb_pred.mvd_l1,
+                params.num_ref_idx_l1_active_minus1,
+                MbPartPredMode::PredL0,
+            ),
+        ];
+        for (list, (ref_idxs, _, num_ref_idx_active_minus1, other)) in lists.iter_mut().enumerate()
+        {
+            for (mb_part_idx, &(_, mode, _)) in sub_mb_types.iter().enumerate() {
+                let ref_idx = coder.element(ref_idxs, mb_part_idx, || None)?;
+                if *num_ref_idx_active_minus1 > 0
+                    && mode != MbPartPredMode::Direct
+                    && mode != *other
+                {
+                    let ref_idx = coder.present(ref_idx, || TruncatedExpGolombCode(0))?;
+                    self.ref_idx(
+                        coder,
+                        list,
+                        *num_ref_idx_active_minus1,
+                        mb_partition(MbType::B8x8, mb_part_idx),
+                        ref_idx,
+                    )?;
+                }
+            }
+        }
+        for (list, (_, mvds, _, other)) in lists.iter_mut().enumerate() {
+            for (mb_part_idx, &(sub_mb_type, mode, num_sub_mb_part)) in
+                sub_mb_types.iter().enumerate()
+            {
+                let mvds = coder.element(mvds, mb_part_idx, Vec::new)?;
+                if mode != MbPartPredMode::Direct && mode != *other {
+                    for sub_mb_part_idx in 0..num_sub_mb_part {
+                        let mvd =
+                            coder.element(mvds, sub_mb_part_idx, || [SignedExpGolombCode(0); 2])?;
+                        let partition = sub_mb_partition(
+                            slice_type_name,
+                            sub_mb_type,
+                            mb_part_idx,
+                            sub_mb_part_idx,
+                        )?;
+                        self.mvd(coder, list, partition, mvd)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// coded_block_pattern, with a prefix for CodedBlockPatternLuma and a suffix for
+    /// CodedBlockPatternChroma when ChromaArrayType is 1 or 2
+    fn coded_block_pattern(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        coded_block_pattern: &mut MappedExpGolombCode,
+    ) -> Result<()> {
+        let luma = coded_block_pattern.0 % 16;
+        let mut chroma = (coded_block_pattern.0 / 16) as u32;
+
+        self.current_mut().coded_block_pattern_luma = 0;
+        for b8 in 0..4 {
+            let (x, y) = (b8 % 2 * 2, b8 / 2 * 2);
+            let cond = |n: Option<(&MacroblockState, usize, usize)>| {
+                n.map_or(0, |(mb, x, y)| {
+                    (mb.coded_block_pattern_luma >> (y / 2 * 2 + x / 2) & 1 == 0) as usize
+                })
+            };
+            let ctx_idx = 73 + cond(self.left(x, y, 4)) + 2 * cond(self.above(x, y, 4));
+            let mut bin = luma >> b8 & 1 == 1;
+            coder.decision(&mut self.context_variables[ctx_idx], &mut bin)?;
+            self.current_mut().coded_block_pattern_luma |= (bin as u8) << b8;
+        }
+
+        if params.chroma_array_type == 1 || params.chroma_array_type == 2 {
+            let cond = |mb: Option<&MacroblockState>, min: u8| {
+                mb.map_or(0, |mb| (mb.coded_block_pattern_chroma >= min) as usize)
+            };
+            let ctx_idx_inc = [
+                cond(self.mb_a(), 1) + 2 * cond(self.mb_b(), 1),
+                4 + cond(self.mb_a(), 2) + 2 * cond(self.mb_b(), 2),
+            ];
+            coder.unary(
+                &mut self.context_variables,
+                |bin_idx| 77 + ctx_idx_inc[bin_idx as usize],
+                Some(2),
+                &mut chroma,
+            )?;
+        } else if chroma != 0 {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        let current = self.current_mut();
+        current.coded_block_pattern_chroma = chroma as u8;
+        coded_block_pattern.0 = current.coded_block_pattern_luma + 16 * chroma as u8;
+        Ok(())
+    }
+
+    fn mb_qp_delta(
+        &mut self,
+        coder: &mut impl BinCoder,
+        mb_qp_delta: &mut SignedExpGolombCode,
+    ) -> Result<()> {
+        // mb_qp_delta of the previous macroblock in decoding order is 0 when it's skipped,
+        // I_PCM, or has no residual without Intra_16x16 prediction
+        let prev_mb = self
+            .curr_mb_addr
+            .checked_sub(1)
+            .and_then(|mb_addr| self.macroblocks.get(&mb_addr));
+        let ctx_idx_inc = prev_mb.map_or(0, |mb| (mb.mb_qp_delta != 0) as usize);
+
+        // Table 9-3 – Assignment of syntax element to codeNum for signed Exp-Golomb coded
+        // syntax elements se(v)
+        let mut mapped = u32::try_from(if mb_qp_delta.0 > 0 {
+            2 * mb_qp_delta.0 - 1
+        } else {
+            -2 * mb_qp_delta.0
+        })
+        .or(Err(BitStreamError::Overflow))?;
+        coder.unary(
+            &mut self.context_variables,
+            // Table 9-39: ctxIdxInc 2 for binIdx 1, and 3 after
+            |bin_idx| {
+                60 + match bin_idx {
+                    0 => ctx_idx_inc,
+                    1 => 2,
+                    _ => 3,
+                }
+            },
+            None,
+            &mut mapped,
+        )?;
+        mb_qp_delta.0 = if mapped % 2 == 1 {
+            (mapped as i64 + 1) / 2
+        } else {
+            -(mapped as i64 / 2)
+        };
+        self.current_mut().mb_qp_delta = mb_qp_delta.0;
+        Ok(())
+    }
+
+    /// Derives ctxIdxInc of coded_block_flag of `block`
+    ///
+    /// § 9.3.3.1.1.9 Derivation process of ctxIdxInc for the syntax element coded_block_flag
+    fn coded_block_flag_ctx_idx_inc(&self, block: TransformBlock, chroma_height: usize) -> usize {
+        let (a, b) = match block {
+            TransformBlock::Intra16x16Dc => (
+                self.mb_a().map(|mb| mb.coded_block_flag_dc[0]),
+                self.mb_b().map(|mb| mb.coded_block_flag_dc[0]),
+            ),
+            TransformBlock::ChromaDc(component) => (
+                self.mb_a().map(|mb| mb.coded_block_flag_dc[component]),
+                self.mb_b().map(|mb| mb.coded_block_flag_dc[component]),
+            ),
+            TransformBlock::Intra16x16Ac(x, y) | TransformBlock::Luma4x4(x, y) => (
+                self.left(x, y, 4)
+                    .map(|(mb, x, y)| mb.coded_block_flag[0][y][x]),
+                self.above(x, y, 4)
+                    .map(|(mb, x, y)| mb.coded_block_flag[0][y][x]),
+            ),
+            TransformBlock::ChromaAc(component, x, y) => (
+                self.left(x, y, 2)
+                    .map(|(mb, x, y)| mb.coded_block_flag[component][y][x]),
+                self.above(x, y, chroma_height)
+                    .map(|(mb, x, y)| mb.coded_block_flag[component][y][x]),
+            ),
+            TransformBlock::Luma8x8(..) => return 0,
+        };
+        // Unavailable macroblocks count as coded for intra macroblocks
+        let intra = self.current().is_some_and(|mb| mb.intra());
+        a.unwrap_or(intra) as usize + 2 * b.unwrap_or(intra) as usize
+    }
+
+    fn set_coded_block_flag(&mut self, block: TransformBlock, coded_block_flag: bool) {
+        let current = self.current_mut();
+        match block {
+            TransformBlock::Intra16x16Dc => current.coded_block_flag_dc[0] = coded_block_flag,
+            TransformBlock::ChromaDc(component) => {
+                current.coded_block_flag_dc[component] = coded_block_flag
+            }
+            TransformBlock::Intra16x16Ac(x, y) | TransformBlock::Luma4x4(x, y) => {
+                current.coded_block_flag[0][y][x] = coded_block_flag
+            }
+            TransformBlock::ChromaAc(component, x, y) => {
+                current.coded_block_flag[component][y][x] = coded_block_flag
+            }
+            TransformBlock::Luma8x8(x, y) => {
+                for row in &mut current.coded_block_flag[0][y..y + 2] {
+                    row[x..x + 2].fill(coded_block_flag);
+                }
+            }
+        }
+    }
+
+    /// § 7.3.5.3.3 Residual block CABAC syntax
+    fn residual_block(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        block: TransformBlock,
+        coeff_level: &mut [i32],
+    ) -> Result<()> {
+        let ctx_block_cat = block.ctx_block_cat();
+        // NumC8x8 and the height of chroma components in 4x4 blocks
+        let num_c8x8 = params.chroma_array_type as usize;
+        let chroma_height = 2 * num_c8x8;
+
+        let mut coded_block_flag = coeff_level.iter().any(|&x| x != 0);
+        if ctx_block_cat == 5 {
+            // coded_block_flag is inferred to be 1
+            if !coded_block_flag && !coder.decoding() {
+                return Err(BitStreamError::InvalidValue);
+            }
+            coded_block_flag = true;
+        } else {
+            let ctx_idx = 85
+                + CODED_BLOCK_FLAG_CAT_OFFSET[ctx_block_cat]
+                + self.coded_block_flag_ctx_idx_inc(block, chroma_height);
+            coder.decision(&mut self.context_variables[ctx_idx], &mut coded_block_flag)?;
+        }
+        self.set_coded_block_flag(block, coded_block_flag);
+        if coder.decoding() {
+            coeff_level.fill(0);
+        }
+        if !coded_block_flag {
+            return Ok(());
+        }
+
+        // ctxIdxOffset of significant_coeff_flag, last_significant_coeff_flag and
+        // coeff_abs_level_minus1
+        let (significant_offset, last_offset, abs_level_offset) = match ctx_block_cat {
+            5 if self.field_pic_flag => (436, 451, 426),
+            5 => (402, 417, 426),
+            _ => {
+                let (significant_offset, last_offset) = if self.field_pic_flag {
+                    (277, 338)
+                } else {
+                    (105, 166)
+                };
+                (
+                    significant_offset + SIGNIFICANT_COEFF_CAT_OFFSET[ctx_block_cat],
+                    last_offset + SIGNIFICANT_COEFF_CAT_OFFSET[ctx_block_cat],
+                    227 + COEFF_ABS_LEVEL_CAT_OFFSET[ctx_block_cat],
+                )
+            }
+        };
+        // ctxIdxInc of significant_coeff_flag and last_significant_coeff_flag from levelListIdx
+        //
+        // § 9.3.3.1.3 Assignment process of ctxIdxInc for syntax elements significant_coeff_flag,
+        // last_significant_coeff_flag, and coeff_abs_level_minus1
+        let field = self.field_pic_flag as usize;
+        let ctx_idx_inc = |level_list_idx: usize, last: bool| match ctx_block_cat {
+            3 => (level_list_idx / num_c8x8).min(2),
+            5 if last => LAST_SIGNIFICANT_COEFF_8X8_CTX_IDX_INC[level_list_idx] as usize,
+            5 => SIGNIFICANT_COEFF_8X8_CTX_IDX_INC[field][level_list_idx] as usize,
+            _ => level_list_idx,
+        };
+
+        let last_coeff = coeff_level.iter().rposition(|&x| x != 0);
+        let mut significant = vec![false; coeff_level.len()];
+        let mut num_coeff = coeff_level.len();
+        let mut i = 0;
+        while i + 1 < num_coeff {
+            significant[i] = coeff_level[i] != 0;
+            coder.decision(
+                &mut self.context_variables[significant_offset + ctx_idx_inc(i, false)],
+                &mut significant[i],
+            )?;
+            if significant[i] {
+                let mut last = last_coeff == Some(i);
+                coder.decision(
+                    &mut self.context_variables[last_offset + ctx_idx_inc(i, true)],
+                    &mut last,
+                )?;
+                if last {
+                    num_coeff = i + 1;
+                }
+            }
+            i += 1;
+        }
+        significant[num_coeff - 1] = true;
+
+        let ueg = Ueg {
+            k: 0,
+            signed_val_flag: false,
+            u_coff: 14,
+        };
+        let (mut num_decod_abs_level_eq1, mut num_decod_abs_level_gt1) = (0, 0);
+        for i in (0..num_coeff).rev().filter(|&i| significant[i]) {
+            let prefix_ctx_idx_inc = if num_decod_abs_level_gt1 != 0 {
+                0
+            } else {
+                (1 + num_decod_abs_level_eq1).min(4)
+            };
+            let suffix_ctx_idx_inc =
+                5 + num_decod_abs_level_gt1.min(4 - (ctx_block_cat == 3) as usize);
+
+            let mut coeff_abs_level_minus1 = coeff_level[i].unsigned_abs().saturating_sub(1) as i64;
+            coder.ueg(
+                &mut self.context_variables,
+                |bin_idx| {
+                    abs_level_offset
+                        + if bin_idx == 0 {
+                            prefix_ctx_idx_inc
+                        } else {
+                            suffix_ctx_idx_inc
+                        }
+                },
+                ueg,
+                &mut coeff_abs_level_minus1,
+            )?;
+            let mut coeff_sign_flag = coeff_level[i] < 0;
+            coder.bypass(&mut coeff_sign_flag)?;
+
+            let level =
+                i32::try_from(coeff_abs_level_minus1 + 1).or(Err(BitStreamError::Overflow))?;
+            coeff_level[i] = if coeff_sign_flag { -level } else { level };
+            if coeff_abs_level_minus1 == 0 {
+                num_decod_abs_level_eq1 += 1;
+            } else {
+                num_decod_abs_level_gt1 += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// residual_luma( ) of the luma component, 8x8 blocks are interleaved into `level4x4` as
+    /// documented in [`ResidualLuma`]
+    ///
+    /// § 7.3.5.3.1 Residual luma syntax
+    fn residual_luma(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        intra16x16: bool,
+        transform_size_8x8_flag: bool,
+        coded_block_pattern_luma: u8,
+        luma: &mut ResidualLuma,
+    ) -> Result<()> {
+        if intra16x16 {
+            let i16x16_dc_level = coder.present(&mut luma.i16x16_dc_level, || vec![0; 16])?;
+            if i16x16_dc_level.len() != 16 {
+                return Err(BitStreamError::InvalidValue);
+            }
+            self.residual_block(coder, params, TransformBlock::Intra16x16Dc, i16x16_dc_level)?;
+        }
+
+        let max_num_coeff = if intra16x16 { 15 } else { 16 };
+        let blocks = if intra16x16 {
+            &mut luma.i16x16_ac_level
+        } else {
+            &mut luma.level4x4
+        };
+        let blocks = coder.present(blocks, || vec![vec![0; max_num_coeff]; 16])?;
+        if blocks.len() != 16 || blocks.iter().any(|x| x.len() != max_num_coeff) {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        for i8x8 in 0..4 {
+            if coded_block_pattern_luma & (1 << i8x8) == 0 {
+                continue;
+            }
+            if transform_size_8x8_flag {
+                let mut level8x8 = vec![0; 64];
+                for i4x4 in 0..4 {
+                    for (i, level) in blocks[i8x8 * 4 + i4x4].iter().enumerate() {
+                        level8x8[4 * i + i4x4] = *level;
+                    }
+                }
+                let block = TransformBlock::Luma8x8(i8x8 % 2 * 2, i8x8 / 2 * 2);
+                self.residual_block(coder, params, block, &mut level8x8)?;
+                for i4x4 in 0..4 {
+                    for (i, level) in blocks[i8x8 * 4 + i4x4].iter_mut().enumerate() {
+                        *level = level8x8[4 * i + i4x4];
+                    }
+                }
+            } else {
+                for i4x4 in 0..4 {
+                    let luma4x4_blk_idx = i8x8 * 4 + i4x4;
+                    let (x, y) = luma4x4_block_position(luma4x4_blk_idx);
+                    let block = if intra16x16 {
+                        TransformBlock::Intra16x16Ac(x, y)
+                    } else {
+                        TransformBlock::Luma4x4(x, y)
+                    };
+                    self.residual_block(coder, params, block, &mut blocks[luma4x4_blk_idx])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// residual( 0, 15 ) of the current macroblock, after its mb_type, coded_block_pattern and
+    /// transform_size_8x8_flag
+    ///
+    /// § 7.3.5.3 Residual data syntax
+    fn residual(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        residual: &mut Residual,
+    ) -> Result<()> {
+        let current = self.current_mut();
+        let intra16x16 = matches!(current.mb_type, Some(MbType::I16x16 { .. }));
+        let transform_size_8x8_flag = current.transform_size_8x8_flag;
+        let coded_block_pattern_luma = current.coded_block_pattern_luma;
+        let coded_block_pattern_chroma = current.coded_block_pattern_chroma;
+        self.residual_luma(
+            coder,
+            params,
+            intra16x16,
+            transform_size_8x8_flag,
+            coded_block_pattern_luma,
+            &mut residual.luma,
+        )?;
+
+        if params.chroma_array_type == 1 || params.chroma_array_type == 2 {
+            let num_c8x8 = params.chroma_array_type as usize;
+
+            let chroma_dc_level = coder.present(&mut residual.chroma_dc_level, Vec::new)?;
+            for i_cb_cr in 0..2 {
+                let coeff_level =
+                    coder.element(chroma_dc_level, i_cb_cr, || vec![0; 4 * num_c8x8])?;
+                if coeff_level.len() != 4 * num_c8x8 {
+                    return Err(BitStreamError::InvalidValue);
+                }
+                if coded_block_pattern_chroma & 3 != 0 {
+                    let block = TransformBlock::ChromaDc(i_cb_cr + 1);
+                    self.residual_block(coder, params, block, coeff_level)?;
+                }
+            }
+
+            let chroma_ac_level = coder.present(&mut residual.chroma_ac_level, Vec::new)?;
+            for i_cb_cr in 0..2 {
+                let blocks = coder.element(chroma_ac_level, i_cb_cr, Vec::new)?;
+                for chroma4x4_blk_idx in 0..4 * num_c8x8 {
+                    let coeff_level = coder.element(blocks, chroma4x4_blk_idx, || vec![0; 15])?;
+                    if coeff_level.len() != 15 {
+                        return Err(BitStreamError::InvalidValue);
+                    }
+                    if coded_block_pattern_chroma & 2 != 0 {
+                        let (x, y) = (chroma4x4_blk_idx % 2, chroma4x4_blk_idx / 2);
+                        let block = TransformBlock::ChromaAc(i_cb_cr + 1, x, y);
+                        self.residual_block(coder, params, block, coeff_level)?;
+                    }
+                }
+            }
+        } else if params.chroma_array_type == 3 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        Ok(())
+    }
+
+    /// § 7.3.5 Macroblock layer syntax
+    fn macroblock_layer(
+        &mut self,
+        coder: &mut impl BinCoder,
+        params: &MacroblockParameters,
+        layer: &mut MacroblockLayer,
+    ) -> Result<()> {
+        self.mb_type(coder, params.slice_type_name, &mut layer.mb_type)?;
+        let mb_type = layer.mb_type;
+        self.current_mut().mb_type = Some(mb_type);
+
+        if mb_type == MbType::IPcm {
+            let chroma_sample_count = 2 * params.chroma_sample_count();
+            let luma = coder.present(&mut layer.pcm_sample_luma, || vec![0; 256])?;
+            let chroma = coder.present(&mut layer.pcm_sample_chroma, || {
+                vec![0; chroma_sample_count]
+            })?;
+            if luma.len() != 256 || chroma.len() != chroma_sample_count {
+                return Err(BitStreamError::InvalidValue);
+            }
+            coder.pcm_samples(luma, chroma, params.bit_depth_luma, params.bit_depth_chroma)?;
+
+            let current = self.current_mut();
+            current.coded_block_pattern_luma = 15;
+            current.coded_block_pattern_chroma = 2;
+            current.coded_block_flag_dc = [true; 3];
+            current.coded_block_flag = [[[true; 4]; 4]; 3];
+            return Ok(());
+        }
+
+        let mut no_sub_mb_part_size_less_than_8x8_flag = true;
+        if mb_type.num_mb_part() == 4 {
+            let sub_mb_pred = coder.present(&mut layer.sub_mb_pred, || SubMbPred {
+                sub_mb_type: vec![],
+                ref_idx_l0: vec![],
+                ref_idx_l1: vec![],
+                mvd_l0: vec![],
+                mvd_l1: vec![],
+            })?;
+            self.sub_mb_pred(coder, params, sub_mb_pred)?;
+            no_sub_mb_part_size_less_than_8x8_flag =
+                sub_mb_pred.no_sub_mb_part_size_less_than_8x8(params)?;
+        } else {
+            if params.transform_8x8_mode_flag && mb_type == MbType::INxN {
+                let transform_size_8x8_flag =
+                    coder.present(&mut layer.transform_size_8x8_flag, || false)?;
+                self.transform_size_8x8_flag(coder, transform_size_8x8_flag)?;
+            }
+            let transform_size_8x8_flag = layer.transform_size_8x8_flag == Some(true);
+            let mb_pred = coder.present(&mut layer.mb_pred, || MbPred {
+                prev_intra4x4_pred_mode_flag: vec![],
+                rem_intra4x4_pred_mode: vec![],
+                prev_intra8x8_pred_mode_flag: vec![],
+                rem_intra8x8_pred_mode: vec![],
+                intra_chroma_pred_mode: None,
+                ref_idx_l0: vec![],
+                ref_idx_l1: vec![],
+                mvd_l0: vec![],
+                mvd_l1: vec![],
+            })?;
+            self.mb_pred(coder, params, mb_type, transform_size_8x8_flag, mb_pred)?;
+        }
+
+        let mb_part_pred_mode =
+            mb_type.mb_part_pred_mode(0, layer.transform_size_8x8_flag == Some(true));
+        if mb_part_pred_mode != Some(MbPartPredMode::Intra16x16) {
+            let coded_block_pattern =
+                coder.present(&mut layer.coded_block_pattern, || MappedExpGolombCode(0))?;
+            self.coded_block_pattern(coder, params, coded_block_pattern)?;
+
+            if coded_block_pattern.0 % 16 > 0
+                && params.transform_8x8_mode_flag
+                && mb_type != MbType::INxN
+                && no_sub_mb_part_size_less_than_8x8_flag
+                && (mb_type != MbType::BDirect16x16 || params.direct_8x8_inference_flag)
+            {
+                let transform_size_8x8_flag =
+                    coder.present(&mut layer.transform_size_8x8_flag, || false)?;
+                self.transform_size_8x8_flag(coder, transform_size_8x8_flag)?;
+            }
+        }
+
+        let (coded_block_pattern_luma, coded_block_pattern_chroma) = layer.coded_block_pattern();
+        let current = self.current_mut();
+        current.coded_block_pattern_luma = coded_block_pattern_luma;
+        current.coded_block_pattern_chroma = coded_block_pattern_chroma;
+
+        let intra16x16 = mb_part_pred_mode == Some(MbPartPredMode::Intra16x16);
+        if coded_block_pattern_luma > 0 || coded_block_pattern_chroma > 0 || intra16x16 {
+            let mb_qp_delta = coder.present(&mut layer.mb_qp_delta, || SignedExpGolombCode(0))?;
+            self.mb_qp_delta(coder, mb_qp_delta)?;
+            let residual = coder.present(&mut layer.residual, || Residual {
+                luma: ResidualLuma {
+                    i16x16_dc_level: None,
+                    i16x16_ac_level: None,
+                    level4x4: None,
+                },
+                chroma_dc_level: None,
+                chroma_ac_level: None,
+                cb: None,
+                cr: None,
+            })?;
+            self.residual(coder, params, residual)?;
+        }
+        Ok(())
+    }
+}
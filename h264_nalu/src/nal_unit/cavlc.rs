@@ -0,0 +1,860 @@
+use std::collections::HashMap;
+
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
+use serde::Serialize;
+
+/// Reads a variable length code bit by bit, until `find` returns a value
+/// for the code read so far and its length.
+fn read_vlc<T>(stream: &mut BitStream, find: impl Fn(u8, u16) -> Option<T>) -> Result<T> {
+    let mut code = 0u16;
+    for length in 1..=16 {
+        code = code << 1 | stream.read_bit()? as u16;
+        if let Some(value) = find(length, code) {
+            return Ok(value);
+        }
+    }
+    Err(BitStreamError::InvalidValue)
+}
+
+fn write_vlc(writer: &mut BitWriter, length: u8, code: u16) -> Result<()> {
+    // A length of 0 marks a combination without code
+    if length == 0 {
+        return Err(BitStreamError::InvalidValue);
+    }
+    writer.write(&code, length)
+}
+
+/// Returns the lengths and codes of the coeff_token table for `n_c`, indexed by
+/// `[TotalCoeff][TrailingOnes]`
+fn coeff_token_table(n_c: i32) -> (&'static [[u8; 4]], &'static [[u16; 4]]) {
+    match n_c {
+        -2 => (
+            &CHROMA_DC_422_COEFF_TOKEN_LENGTH,
+            &CHROMA_DC_422_COEFF_TOKEN_CODE,
+        ),
+        -1 => (&CHROMA_DC_COEFF_TOKEN_LENGTH, &CHROMA_DC_COEFF_TOKEN_CODE),
+        0..=1 => (&COEFF_TOKEN_LENGTH[0], &COEFF_TOKEN_CODE[0]),
+        2..=3 => (&COEFF_TOKEN_LENGTH[1], &COEFF_TOKEN_CODE[1]),
+        4..=7 => (&COEFF_TOKEN_LENGTH[2], &COEFF_TOKEN_CODE[2]),
+        _ => (&COEFF_TOKEN_LENGTH[3], &COEFF_TOKEN_CODE[3]),
+    }
+}
+
+/// coeff_token, a ce(v) syntax element read with nC as argument
+///
+/// § 9.2.1 Parsing process for total number of non-zero transform coefficient levels and number of trailing ones
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct CoeffToken {
+    /// TotalCoeff( coeff_token )
+    pub total_coeff: usize,
+    /// TrailingOnes( coeff_token )
+    pub trailing_ones: usize,
+}
+
+impl<'a> BitField<'a> for CoeffToken {
+    type Args = i32;
+
+    fn read(stream: &mut BitStream, n_c: i32) -> Result<Self> {
+        let (lengths, codes) = coeff_token_table(n_c);
+        read_vlc(stream, |length, code| {
+            (0..lengths.len())
+                .flat_map(|total_coeff| {
+                    (0..4).map(move |trailing_ones| (total_coeff, trailing_ones))
+                })
+                .find(|&(total_coeff, trailing_ones)| {
+                    lengths[total_coeff][trailing_ones] == length
+                        && codes[total_coeff][trailing_ones] == code
+                })
+                .map(|(total_coeff, trailing_ones)| Self {
+                    total_coeff,
+                    trailing_ones,
+                })
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for CoeffToken {
+    type Args = i32;
+
+    fn write(&self, writer: &mut BitWriter, n_c: i32) -> Result<()> {
+        let (lengths, codes) = coeff_token_table(n_c);
+        if self.total_coeff >= lengths.len() || self.trailing_ones >= 4 {
+            return Err(BitStreamError::InvalidValue);
+        }
+        write_vlc(
+            writer,
+            lengths[self.total_coeff][self.trailing_ones],
+            codes[self.total_coeff][self.trailing_ones],
+        )
+    }
+}
+
+/// Returns the lengths and codes of the total_zeros table for `tz_vlc_index`, indexed by
+/// total_zeros
+fn total_zeros_table(
+    tz_vlc_index: usize,
+    max_num_coeff: usize,
+) -> Result<(&'static [u8], &'static [u16])> {
+    // tzVlcIndex is TotalCoeff( coeff_token ), in range 1 to maxNumCoeff - 1
+    if tz_vlc_index == 0 || tz_vlc_index >= max_num_coeff {
+        return Err(BitStreamError::InvalidValue);
+    }
+    Ok(match max_num_coeff {
+        4 => (
+            CHROMA_DC_TOTAL_ZEROS_LENGTH[tz_vlc_index - 1],
+            CHROMA_DC_TOTAL_ZEROS_CODE[tz_vlc_index - 1],
+        ),
+        8 => (
+            CHROMA_DC_422_TOTAL_ZEROS_LENGTH[tz_vlc_index - 1],
+            CHROMA_DC_422_TOTAL_ZEROS_CODE[tz_vlc_index - 1],
+        ),
+        _ => (
+            TOTAL_ZEROS_LENGTH[tz_vlc_index - 1],
+            TOTAL_ZEROS_CODE[tz_vlc_index - 1],
+        ),
+    })
+}
+
+/// Returns the lengths and codes of the run_before table for `zeros_left`, indexed by run_before
+fn run_before_table(zeros_left: usize) -> Result<(&'static [u8], &'static [u16])> {
+    if zeros_left == 0 {
+        return Err(BitStreamError::InvalidValue);
+    }
+    let index = zeros_left.min(7) - 1;
+    Ok((RUN_BEFORE_LENGTH[index], RUN_BEFORE_CODE[index]))
+}
+
+fn read_table_index(stream: &mut BitStream, (lengths, codes): (&[u8], &[u16])) -> Result<usize> {
+    read_vlc(stream, |length, code| {
+        (0..lengths.len()).find(|&i| lengths[i] == length && codes[i] == code)
+    })
+}
+
+fn write_table_index(
+    writer: &mut BitWriter,
+    (lengths, codes): (&[u8], &[u16]),
+    index: usize,
+) -> Result<()> {
+    if index >= lengths.len() {
+        return Err(BitStreamError::InvalidValue);
+    }
+    write_vlc(writer, lengths[index], codes[index])
+}
+
+/// total_zeros, a ce(v) syntax element read with tzVlcIndex and maxNumCoeff as arguments
+///
+/// § 9.2.3 Parsing process for run information
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct TotalZeros(pub usize);
+
+impl<'a> BitField<'a> for TotalZeros {
+    type Args = (usize, usize);
+
+    fn read(stream: &mut BitStream, (tz_vlc_index, max_num_coeff): Self::Args) -> Result<Self> {
+        let table = total_zeros_table(tz_vlc_index, max_num_coeff)?;
+        Ok(Self(read_table_index(stream, table)?))
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for TotalZeros {
+    type Args = (usize, usize);
+
+    fn write(
+        &self,
+        writer: &mut BitWriter,
+        (tz_vlc_index, max_num_coeff): Self::Args,
+    ) -> Result<()> {
+        let table = total_zeros_table(tz_vlc_index, max_num_coeff)?;
+        write_table_index(writer, table, self.0)
+    }
+}
+
+/// run_before, a ce(v) syntax element read with zerosLeft as argument
+///
+/// § 9.2.3 Parsing process for run information
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct RunBefore(pub usize);
+
+impl<'a> BitField<'a> for RunBefore {
+    type Args = usize;
+
+    fn read(stream: &mut BitStream, zeros_left: usize) -> Result<Self> {
+        let run_before = read_table_index(stream, run_before_table(zeros_left)?)?;
+        if run_before > zeros_left {
+            return Err(BitStreamError::InvalidValue);
+        }
+        Ok(Self(run_before))
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for RunBefore {
+    type Args = usize;
+
+    fn write(&self, writer: &mut BitWriter, zeros_left: usize) -> Result<()> {
+        write_table_index(writer, run_before_table(zeros_left)?, self.0)
+    }
+}
+
+/// level_prefix, a ce(v) syntax element, the count of leading zero bits before a `1` bit
+///
+/// § 9.2.2.1 Parsing process for level_prefix
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct LevelPrefix(pub u32);
+
+impl<'a> BitField<'a> for LevelPrefix {
+    type Args = ();
+
+    fn read(stream: &mut BitStream, _: ()) -> Result<Self> {
+        let mut level_prefix = 0;
+        while !stream.read_bit()? {
+            level_prefix += 1;
+            // levelCode must fit in 32 bits
+            if level_prefix > 31 {
+                return Err(BitStreamError::TooLarge);
+            }
+        }
+        Ok(Self(level_prefix))
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for LevelPrefix {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        if self.0 > 31 {
+            return Err(BitStreamError::TooLarge);
+        }
+        for _ in 0..self.0 {
+            writer.write_bit(false)?;
+        }
+        writer.write_bit(true)
+    }
+}
+
+/// Writes a levelCode with level_prefix and level_suffix, the inverse of the derivation in
+/// § 7.4.5.3.2 Residual block CAVLC semantics
+fn write_level_code(writer: &mut BitWriter, level_code: i64, suffix_length: u32) -> Result<()> {
+    let (level_prefix, level_suffix, level_suffix_size) = if suffix_length == 0 && level_code < 14 {
+        (level_code as u32, 0, 0)
+    } else if suffix_length == 0 && level_code < 30 {
+        (14, level_code - 14, 4)
+    } else if suffix_length > 0 && level_code < 15 << suffix_length {
+        (
+            (level_code >> suffix_length) as u32,
+            level_code & ((1 << suffix_length) - 1),
+            suffix_length,
+        )
+    } else {
+        // Escape codes, level_prefix 15 has a 12 bits level_suffix,
+        // each following level_prefix doubles the range
+        let base = (15 << suffix_length) + if suffix_length == 0 { 15 } else { 0 };
+        let mut level_prefix = 15;
+        loop {
+            let offset = if level_prefix >= 16 {
+                (1 << (level_prefix - 3)) - 4096
+            } else {
+                0
+            };
+            let level_suffix = level_code - base - offset;
+            if level_suffix < 1 << (level_prefix - 3) {
+                break (level_prefix, level_suffix, level_prefix - 3);
+            }
+            level_prefix += 1;
+            if level_prefix > 31 {
+                return Err(BitStreamError::TooLarge);
+            }
+        }
+    };
+
+    writer.write(&LevelPrefix(level_prefix), ())?;
+    writer.write(&(level_suffix as u32), level_suffix_size as u8)
+}
+
+/// Reads residual_block_cavlc( coeffLevel, startIdx, endIdx, maxNumCoeff ), returns coeffLevel
+///
+/// `n_c` is derived by [`CavlcContext::n_c`] for 4x4 blocks, or is -1 or -2 for chroma DC blocks.
+///
+/// § 7.3.5.3.2 Residual block CAVLC syntax
+pub fn read_residual_block(
+    stream: &mut BitStream,
+    n_c: i32,
+    start_idx: usize,
+    end_idx: usize,
+    max_num_coeff: usize,
+) -> Result<Vec<i32>> {
+    let mut coeff_level = vec![0; max_num_coeff];
+    let CoeffToken {
+        total_coeff,
+        trailing_ones,
+    } = stream.read(n_c)?;
+    if total_coeff == 0 {
+        return Ok(coeff_level);
+    }
+    if total_coeff > end_idx - start_idx + 1 {
+        return Err(BitStreamError::InvalidValue);
+    }
+
+    let mut level_val = vec![0; total_coeff];
+    let mut suffix_length = if total_coeff > 10 && trailing_ones < 3 {
+        1
+    } else {
+        0
+    };
+    for (i, level) in level_val.iter_mut().enumerate() {
+        if i < trailing_ones {
+            let trailing_ones_sign_flag = stream.read_bit()?;
+            *level = 1 - 2 * trailing_ones_sign_flag as i64;
+        } else {
+            let level_prefix = stream.read::<LevelPrefix>(())?.0;
+            let mut level_code = (level_prefix.min(15) as i64) << suffix_length;
+            if suffix_length > 0 || level_prefix >= 14 {
+                let level_suffix_size = if level_prefix == 14 && suffix_length == 0 {
+                    4
+                } else if level_prefix >= 15 {
+                    level_prefix - 3
+                } else {
+                    suffix_length
+                };
+                let level_suffix: u32 = stream.read(level_suffix_size as u8)?;
+                level_code += level_suffix as i64;
+            }
+            if level_prefix >= 15 && suffix_length == 0 {
+                level_code += 15;
+            }
+            if level_prefix >= 16 {
+                level_code += (1 << (level_prefix - 3)) - 4096;
+            }
+            if i == trailing_ones && trailing_ones < 3 {
+                level_code += 2;
+            }
+
+            *level = if level_code % 2 == 0 {
+                (level_code + 2) >> 1
+            } else {
+                (-level_code - 1) >> 1
+            };
+
+            if suffix_length == 0 {
+                suffix_length = 1;
+            }
+            if level.abs() > 3 << (suffix_length - 1) && suffix_length < 6 {
+                suffix_length += 1;
+            }
+        }
+    }
+
+    let mut zeros_left = if total_coeff < end_idx - start_idx + 1 {
+        stream.read::<TotalZeros>((total_coeff, max_num_coeff))?.0
+    } else {
+        0
+    };
+    if total_coeff + zeros_left > end_idx - start_idx + 1 {
+        return Err(BitStreamError::InvalidValue);
+    }
+
+    let mut run_val = vec![0; total_coeff];
+    for run in run_val.iter_mut().take(total_coeff - 1) {
+        if zeros_left > 0 {
+            *run = stream.read::<RunBefore>(zeros_left)?.0;
+            zeros_left -= *run;
+        }
+    }
+    run_val[total_coeff - 1] = zeros_left;
+
+    let mut coeff_num = start_idx;
+    for i in (0..total_coeff).rev() {
+        coeff_num += run_val[i];
+        coeff_level[coeff_num] = level_val[i] as i32;
+        coeff_num += 1;
+    }
+
+    Ok(coeff_level)
+}
+
+/// Writes residual_block_cavlc( coeffLevel, startIdx, endIdx, maxNumCoeff ),
+/// `maxNumCoeff` is the length of `coeff_level`
+pub fn write_residual_block(
+    writer: &mut BitWriter,
+    coeff_level: &[i32],
+    n_c: i32,
+    start_idx: usize,
+    end_idx: usize,
+) -> Result<()> {
+    // Positions of the non-zero coefficients, in reverse scanning order
+    let positions: Vec<usize> = (start_idx..=end_idx)
+        .rev()
+        .filter(|&i| coeff_level[i] != 0)
+        .collect();
+    let level_val: Vec<i64> = positions.iter().map(|&i| coeff_level[i] as i64).collect();
+    let total_coeff = positions.len();
+    let trailing_ones = level_val
+        .iter()
+        .take(3)
+        .take_while(|x| x.abs() == 1)
+        .count();
+
+    writer.write(
+        &CoeffToken {
+            total_coeff,
+            trailing_ones,
+        },
+        n_c,
+    )?;
+    if total_coeff == 0 {
+        return Ok(());
+    }
+
+    let mut suffix_length = if total_coeff > 10 && trailing_ones < 3 {
+        1
+    } else {
+        0
+    };
+    for (i, &level) in level_val.iter().enumerate() {
+        if i < trailing_ones {
+            writer.write_bit(level < 0)?;
+        } else {
+            let mut level_code = if level > 0 {
+                level * 2 - 2
+            } else {
+                -level * 2 - 1
+            };
+            if i == trailing_ones && trailing_ones < 3 {
+                level_code -= 2;
+            }
+            write_level_code(writer, level_code, suffix_length)?;
+
+            if suffix_length == 0 {
+                suffix_length = 1;
+            }
+            if level.abs() > 3 << (suffix_length - 1) && suffix_length < 6 {
+                suffix_length += 1;
+            }
+        }
+    }
+
+    let mut zeros_left = positions[0] + 1 - start_idx - total_coeff;
+    if total_coeff < end_idx - start_idx + 1 {
+        writer.write(&TotalZeros(zeros_left), (total_coeff, coeff_level.len()))?;
+    }
+    for i in 0..total_coeff - 1 {
+        if zeros_left > 0 {
+            let run_before = positions[i] - positions[i + 1] - 1;
+            writer.write(&RunBefore(run_before), zeros_left)?;
+            zeros_left -= run_before;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records TotalCoeff( coeff_token ) of the 4x4 blocks in the macroblocks of a slice,
+/// to derive nC for the following blocks.
+///
+/// Only frames and fields without MBAFF are supported, where the neighbouring macroblocks
+/// A and B are `CurrMbAddr - 1` and `CurrMbAddr - PicWidthInMbs`.
+///
+/// § 9.2.1 Parsing process for total number of non-zero transform coefficient levels and number of trailing ones
+#[derive(Clone, Debug)]
+pub struct CavlcContext {
+    pic_width_in_mbs: u64,
+    /// Width and height of chroma components in 4x4 blocks
+    chroma_size: (usize, usize),
+    curr_mb_addr: u64,
+    /// TotalCoeff of each colour component, indexed by `[y][x]` in 4x4 blocks.
+    /// Macroblocks not in the map are not available.
+    total_coeff: HashMap<u64, [[[u8; 4]; 4]; 3]>,
+}
+
+impl CavlcContext {
+    pub fn new(pic_width_in_mbs: u64, chroma_array_type: u64) -> Self {
+        Self {
+            pic_width_in_mbs,
+            chroma_size: match chroma_array_type {
+                1 => (2, 2),
+                2 => (2, 4),
+                _ => (4, 4),
+            },
+            curr_mb_addr: 0,
+            total_coeff: HashMap::new(),
+        }
+    }
+
+    /// Starts macroblock `mb_addr`, all of its blocks have a TotalCoeff of 0 until set,
+    /// which is also the value for P_Skip and B_Skip macroblocks
+    pub fn start_macroblock(&mut self, mb_addr: u64) {
+        self.curr_mb_addr = mb_addr;
+        self.total_coeff.insert(mb_addr, Default::default());
+    }
+
+    /// Blocks in I_PCM macroblocks have a TotalCoeff of 16
+    pub fn set_pcm(&mut self) {
+        self.total_coeff
+            .insert(self.curr_mb_addr, [[[16; 4]; 4]; 3]);
+    }
+
+    pub fn set_total_coeff(&mut self, component: usize, x: usize, y: usize, total_coeff: usize) {
+        if let Some(current) = self.total_coeff.get_mut(&self.curr_mb_addr) {
+            current[component][y][x] = total_coeff as u8;
+        }
+    }
+
+    /// Derives nC of the 4x4 block at (`x`, `y`) in the current macroblock, `component` is 0 for
+    /// luma, 1 for Cb and 2 for Cr
+    pub fn n_c(&self, component: usize, x: usize, y: usize) -> i32 {
+        let (width, height) = if component == 0 {
+            (4, 4)
+        } else {
+            self.chroma_size
+        };
+        let current = self.total_coeff.get(&self.curr_mb_addr);
+
+        let n_a = if x > 0 {
+            current.map(|mb| mb[component][y][x - 1])
+        } else if !self.curr_mb_addr.is_multiple_of(self.pic_width_in_mbs) {
+            self.total_coeff
+                .get(&(self.curr_mb_addr - 1))
+                .map(|mb| mb[component][y][width - 1])
+        } else {
+            None
+        };
+        let n_b = if y > 0 {
+            current.map(|mb| mb[component][y - 1][x])
+        } else if self.curr_mb_addr >= self.pic_width_in_mbs {
+            self.total_coeff
+                .get(&(self.curr_mb_addr - self.pic_width_in_mbs))
+                .map(|mb| mb[component][height - 1][x])
+        } else {
+            None
+        };
+
+        match (n_a, n_b) {
+            (Some(n_a), Some(n_b)) => (n_a as i32 + n_b as i32 + 1) >> 1,
+            (Some(n_a), None) => n_a as i32,
+            (None, Some(n_b)) => n_b as i32,
+            (None, None) => 0,
+        }
+    }
+}
+
+// Table 9-5 – coeff_token, TotalCoeff( coeff_token ) and TrailingOnes( coeff_token ),
+// indexed by [nC table][TotalCoeff][TrailingOnes], for 0 <= nC < 2, 2 <= nC < 4,
+// 4 <= nC < 8 and 8 <= nC. Combinations with a length of 0 have no code.
+const COEFF_TOKEN_LENGTH: [[[u8; 4]; 17]; 4] = [
+    [
+        [1, 0, 0, 0],
+        [6, 2, 0, 0],
+        [8, 6, 3, 0],
+        [9, 8, 7, 5],
+        [10, 9, 8, 6],
+        [11, 10, 9, 7],
+        [13, 11, 10, 8],
+        [13, 13, 11, 9],
+        [13, 13, 13, 10],
+        [14, 14, 13, 11],
+        [14, 14, 14, 13],
+        [15, 15, 14, 14],
+        [15, 15, 15, 14],
+        [16, 15, 15, 15],
+        [16, 16, 16, 15],
+        [16, 16, 16, 16],
+        [16, 16, 16, 16],
+    ],
+    [
+        [2, 0, 0, 0],
+        [6, 2, 0, 0],
+        [6, 5, 3, 0],
+        [7, 6, 6, 4],
+        [8, 6, 6, 4],
+        [8, 7, 7, 5],
+        [9, 8, 8, 6],
+        [11, 9, 9, 6],
+        [11, 11, 11, 7],
+        [12, 11, 11, 9],
+        [12, 12, 12, 11],
+        [12, 12, 12, 11],
+        [13, 13, 13, 12],
+        [13, 13, 13, 13],
+        [13, 14, 13, 13],
+        [14, 14, 14, 13],
+        [14, 14, 14, 14],
+    ],
+    [
+        [4, 0, 0, 0],
+        [6, 4, 0, 0],
+        [6, 5, 4, 0],
+        [6, 5, 5, 4],
+        [7, 5, 5, 4],
+        [7, 5, 5, 4],
+        [7, 6, 6, 4],
+        [7, 6, 6, 4],
+        [8, 7, 7, 5],
+        [8, 8, 7, 6],
+        [9, 8, 8, 7],
+        [9, 9, 8, 8],
+        [9, 9, 9, 8],
+        [10, 9, 9, 9],
+        [10, 10, 10, 10],
+        [10, 10, 10, 10],
+        [10, 10, 10, 10],
+    ],
+    [
+        [6, 0, 0, 0],
+        [6, 6, 0, 0],
+        [6, 6, 6, 0],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+        [6, 6, 6, 6],
+    ],
+];
+
+const COEFF_TOKEN_CODE: [[[u16; 4]; 17]; 4] = [
+    [
+        [1, 0, 0, 0],
+        [5, 1, 0, 0],
+        [7, 4, 1, 0],
+        [7, 6, 5, 3],
+        [7, 6, 5, 3],
+        [7, 6, 5, 4],
+        [15, 6, 5, 4],
+        [11, 14, 5, 4],
+        [8, 10, 13, 4],
+        [15, 14, 9, 4],
+        [11, 10, 13, 12],
+        [15, 14, 9, 12],
+        [11, 10, 13, 8],
+        [15, 1, 9, 12],
+        [11, 14, 13, 8],
+        [7, 10, 9, 12],
+        [4, 6, 5, 8],
+    ],
+    [
+        [3, 0, 0, 0],
+        [11, 2, 0, 0],
+        [7, 7, 3, 0],
+        [7, 10, 9, 5],
+        [7, 6, 5, 4],
+        [4, 6, 5, 6],
+        [7, 6, 5, 8],
+        [15, 6, 5, 4],
+        [11, 14, 13, 4],
+        [15, 10, 9, 4],
+        [11, 14, 13, 12],
+        [8, 10, 9, 8],
+        [15, 14, 13, 12],
+        [11, 10, 9, 12],
+        [7, 11, 6, 8],
+        [9, 8, 10, 1],
+        [7, 6, 5, 4],
+    ],
+    [
+        [15, 0, 0, 0],
+        [15, 14, 0, 0],
+        [11, 15, 13, 0],
+        [8, 12, 14, 12],
+        [15, 10, 11, 11],
+        [11, 8, 9, 10],
+        [9, 14, 13, 9],
+        [8, 10, 9, 8],
+        [15, 14, 13, 13],
+        [11, 14, 10, 12],
+        [15, 10, 13, 12],
+        [11, 14, 9, 12],
+        [8, 10, 13, 8],
+        [13, 7, 9, 12],
+        [9, 12, 11, 10],
+        [5, 8, 7, 6],
+        [1, 4, 3, 2],
+    ],
+    [
+        [3, 0, 0, 0],
+        [0, 1, 0, 0],
+        [4, 5, 6, 0],
+        [8, 9, 10, 11],
+        [12, 13, 14, 15],
+        [16, 17, 18, 19],
+        [20, 21, 22, 23],
+        [24, 25, 26, 27],
+        [28, 29, 30, 31],
+        [32, 33, 34, 35],
+        [36, 37, 38, 39],
+        [40, 41, 42, 43],
+        [44, 45, 46, 47],
+        [48, 49, 50, 51],
+        [52, 53, 54, 55],
+        [56, 57, 58, 59],
+        [60, 61, 62, 63],
+    ],
+];
+
+const CHROMA_DC_COEFF_TOKEN_LENGTH: [[u8; 4]; 5] = [
+    [2, 0, 0, 0],
+    [6, 1, 0, 0],
+    [6, 6, 3, 0],
+    [6, 7, 7, 6],
+    [6, 8, 8, 7],
+];
+
+const CHROMA_DC_COEFF_TOKEN_CODE: [[u16; 4]; 5] = [
+    [1, 0, 0, 0],
+    [7, 1, 0, 0],
+    [4, 6, 1, 0],
+    [3, 3, 2, 5],
+    [2, 3, 2, 0],
+];
+
+const CHROMA_DC_422_COEFF_TOKEN_LENGTH: [[u8; 4]; 9] = [
+    [1, 0, 0, 0],
+    [7, 2, 0, 0],
+    [7, 7, 3, 0],
+    [9, 7, 7, 5],
+    [9, 9, 7, 6],
+    [10, 10, 9, 7],
+    [11, 11, 10, 7],
+    [12, 12, 11, 10],
+    [13, 12, 12, 11],
+];
+
+const CHROMA_DC_422_COEFF_TOKEN_CODE: [[u16; 4]; 9] = [
+    [1, 0, 0, 0],
+    [15, 1, 0, 0],
+    [14, 13, 1, 0],
+    [7, 12, 11, 1],
+    [6, 5, 10, 1],
+    [7, 6, 4, 9],
+    [7, 6, 5, 8],
+    [7, 6, 5, 4],
+    [7, 5, 4, 4],
+];
+
+const TOTAL_ZEROS_LENGTH: [&[u8]; 15] = [
+    &[1, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 9],
+    &[3, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 6, 6, 6, 6],
+    &[4, 3, 3, 3, 4, 4, 3, 3, 4, 5, 5, 6, 5, 6],
+    &[5, 3, 4, 4, 3, 3, 3, 4, 3, 4, 5, 5, 5],
+    &[4, 4, 4, 3, 3, 3, 3, 3, 4, 5, 4, 5],
+    &[6, 5, 3, 3, 3, 3, 3, 3, 4, 3, 6],
+    &[6, 5, 3, 3, 3, 2, 3, 4, 3, 6],
+    &[6, 4, 5, 3, 2, 2, 3, 3, 6],
+    &[6, 6, 4, 2, 2, 3, 2, 5],
+    &[5, 5, 3, 2, 2, 2, 4],
+    &[4, 4, 3, 3, 1, 3],
+    &[4, 4, 2, 1, 3],
+    &[3, 3, 1, 2],
+    &[2, 2, 1],
+    &[1, 1],
+];
+
+const TOTAL_ZEROS_CODE: [&[u16]; 15] = [
+    &[1, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 1],
+    &[7, 6, 5, 4, 3, 5, 4, 3, 2, 3, 2, 3, 2, 1, 0],
+    &[5, 7, 6, 5, 4, 3, 4, 3, 2, 3, 2, 1, 1, 0],
+    &[3, 7, 5, 4, 6, 5, 4, 3, 3, 2, 2, 1, 0],
+    &[5, 4, 3, 7, 6, 5, 4, 3, 2, 1, 1, 0],
+    &[1, 1, 7, 6, 5, 4, 3, 2, 1, 1, 0],
+    &[1, 1, 5, 4, 3, 3, 2, 1, 1, 0],
+    &[1, 1, 1, 3, 3, 2, 2, 1, 0],
+    &[1, 0, 1, 3, 2, 1, 1, 1],
+    &[1, 0, 1, 3, 2, 1, 1],
+    &[0, 1, 1, 2, 1, 3],
+    &[0, 1, 1, 1, 1],
+    &[0, 1, 1, 1],
+    &[0, 1, 1],
+    &[0, 1],
+];
+
+const CHROMA_DC_TOTAL_ZEROS_LENGTH: [&[u8]; 3] = [&[1, 2, 3, 3], &[1, 2, 2], &[1, 1]];
+
+const CHROMA_DC_TOTAL_ZEROS_CODE: [&[u16]; 3] = [&[1, 1, 1, 0], &[1, 1, 0], &[1, 0]];
+
+const CHROMA_DC_422_TOTAL_ZEROS_LENGTH: [&[u8]; 7] = [
+    &[1, 3, 3, 4, 4, 4, 5, 5],
+    &[3, 2, 3, 3, 3, 3, 3],
+    &[3, 3, 2, 2, 3, 3],
+    &[3, 2, 2, 2, 3],
+    &[2, 2, 2, 2],
+    &[2, 2, 1],
+    &[1, 1],
+];
+
+const CHROMA_DC_422_TOTAL_ZEROS_CODE: [&[u16]; 7] = [
+    &[1, 2, 3, 2, 3, 1, 1, 0],
+    &[0, 1, 1, 4, 5, 6, 7],
+    &[0, 1, 1, 2, 6, 7],
+    &[6, 0, 1, 2, 7],
+    &[0, 1, 2, 3],
+    &[0, 1, 1],
+    &[0, 1],
+];
+
+const RUN_BEFORE_LENGTH: [&[u8]; 7] = [
+    &[1, 1],
+    &[1, 2, 2],
+    &[2, 2, 2, 2],
+    &[2, 2, 2, 3, 3],
+    &[2, 2, 3, 3, 3, 3],
+    &[2, 3, 3, 3, 3, 3, 3],
+    &[3, 3, 3, 3, 3, 3, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+];
+
+const RUN_BEFORE_CODE: [&[u16]; 7] = [
+    &[1, 0],
+    &[1, 1, 0],
+    &[3, 2, 1, 0],
+    &[3, 2, 1, 1, 0],
+    &[3, 2, 3, 2, 1, 0],
+    &[3, 0, 1, 3, 2, 5, 4],
+    &[7, 6, 5, 4, 3, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+];
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use crate::nal_unit::{read_residual_block, write_residual_block, CoeffToken};
+
+    #[test]
+    fn residual_block_cavlc() {
+        // coeff_token 0000100, trailing_ones_sign_flag 011, level_prefix 1,
+        // level_prefix 001, total_zeros 111, run_before 10 1 1 01
+        let data = [0b0000_1000, 0b1110_0101, 0b1110_1101];
+        let coeff_level = read_residual_block(&mut BitStream::new(&data), 0, 0, 15, 16).unwrap();
+        assert_eq!(
+            coeff_level,
+            [0, 3, 0, 1, -1, -1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let mut writer = BitWriter::new();
+        write_residual_block(&mut writer, &coeff_level, 0, 0, 15).unwrap();
+        assert_eq!(writer.into_inner().as_ref(), data);
+    }
+
+    #[test]
+    fn coeff_token() {
+        // TrailingOnes 3 and TotalCoeff 5 with nC 0
+        let data = [0b0000_1000];
+        let ce: CoeffToken = BitStream::new(&data).read(0).unwrap();
+        assert_eq!(
+            ce,
+            CoeffToken {
+                total_coeff: 5,
+                trailing_ones: 3
+            }
+        );
+
+        let mut writer = BitWriter::new();
+        writer.write(&ce, 0).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(writer.into_inner().as_ref(), data);
+    }
+}
@@ -1,4 +1,4 @@
-use bit_stream::{BitField, BitStream, Result};
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
 use derive_new_number::NewNumber;
 use serde::Serialize;
 
@@ -23,6 +23,18 @@ impl<'a> BitField<'a> for UnsignedExpGolombCode {
     }
 }
 
+impl<'a> BitFieldWrite<'a> for UnsignedExpGolombCode {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        let value = self.0.checked_add(1).ok_or(BitStreamError::Overflow)?;
+        // `value` has `length + 1` significant bits, the leading one is the separator
+        let length = 63 - value.leading_zeros() as u8;
+        writer.skip(length as usize)?;
+        writer.write(&value, length + 1)
+    }
+}
+
 /// § 9.1.1 Mapping process for signed Exp-Golomb codes
 #[derive(Clone, Copy, Debug, Eq, Hash, NewNumber, PartialEq, Serialize)]
 pub struct SignedExpGolombCode(pub i64);
@@ -34,10 +46,164 @@ impl<'a> BitField<'a> for SignedExpGolombCode {
         let ue = stream.read::<UnsignedExpGolombCode>(())?.0;
         // Safety: `i64::MAX` equals to `u64::MAX >> 1`
         // So `(u64 >> 1) as i64` will never overflow
-        Ok(Self(if ue % 1 == 1 {
-            (ue >> 1 + 1) as i64
+        Ok(Self(if ue % 2 == 1 {
+            (ue >> 1) as i64 + 1
         } else {
-            ((ue >> 1) as i64) * -1
+            -((ue >> 1) as i64)
         }))
     }
 }
+
+impl<'a> BitFieldWrite<'a> for SignedExpGolombCode {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        // Table 9-3 – Assignment of syntax element to codeNum for signed Exp-Golomb coded syntax elements se(v)
+        let code_num = if self.0 > 0 {
+            (self.0 as u64) * 2 - 1
+        } else {
+            self.0
+                .unsigned_abs()
+                .checked_mul(2)
+                .ok_or(BitStreamError::Overflow)?
+        };
+        writer.write(&UnsignedExpGolombCode(code_num), ())
+    }
+}
+
+/// te(v), a truncated Exp-Golomb code with a range of possible values, read with the range
+/// as argument
+///
+/// § 9.1 Parsing process for Exp-Golomb codes
+#[derive(Clone, Copy, Debug, Eq, Hash, NewNumber, PartialEq, Serialize)]
+pub struct TruncatedExpGolombCode(pub u64);
+
+impl<'a> BitField<'a> for TruncatedExpGolombCode {
+    type Args = u64;
+
+    fn read(stream: &mut BitStream, range: u64) -> Result<Self> {
+        if range > 1 {
+            Ok(Self(stream.read::<UnsignedExpGolombCode>(())?.0))
+        } else {
+            // The only bit is the inverted value
+            Ok(Self(!stream.read_bit()? as u64))
+        }
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for TruncatedExpGolombCode {
+    type Args = u64;
+
+    fn write(&self, writer: &mut BitWriter, range: u64) -> Result<()> {
+        if range > 1 {
+            writer.write(&UnsignedExpGolombCode(self.0), ())
+        } else if self.0 > 1 {
+            Err(BitStreamError::Overflow)
+        } else {
+            writer.write_bit(self.0 == 0)
+        }
+    }
+}
+
+/// me(v), a coded_block_pattern mapped from an Exp-Golomb codeNum, read with ChromaArrayType
+/// and whether the macroblock prediction mode is Intra_4x4 or Intra_8x8 as arguments
+///
+/// CodedBlockPatternLuma is `coded_block_pattern % 16` and CodedBlockPatternChroma is
+/// `coded_block_pattern / 16`.
+///
+/// § 9.1.2 Mapping process for coded block pattern
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct MappedExpGolombCode(pub u8);
+
+impl MappedExpGolombCode {
+    /// Returns the coded_block_pattern values indexed by codeNum
+    fn table(chroma_array_type: u64, intra: bool) -> &'static [u8] {
+        match (chroma_array_type, intra) {
+            (1 | 2, true) => &INTRA_CODED_BLOCK_PATTERN,
+            (1 | 2, false) => &INTER_CODED_BLOCK_PATTERN,
+            (_, true) => &INTRA_CODED_BLOCK_PATTERN_MONOCHROME,
+            (_, false) => &INTER_CODED_BLOCK_PATTERN_MONOCHROME,
+        }
+    }
+}
+
+impl<'a> BitField<'a> for MappedExpGolombCode {
+    type Args = (u64, bool);
+
+    fn read(stream: &mut BitStream, (chroma_array_type, intra): Self::Args) -> Result<Self> {
+        let code_num = stream.read::<UnsignedExpGolombCode>(())?.0;
+        Self::table(chroma_array_type, intra)
+            .get(code_num as usize)
+            .map(|&x| Self(x))
+            .ok_or(BitStreamError::InvalidValue)
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for MappedExpGolombCode {
+    type Args = (u64, bool);
+
+    fn write(&self, writer: &mut BitWriter, (chroma_array_type, intra): Self::Args) -> Result<()> {
+        let code_num = Self::table(chroma_array_type, intra)
+            .iter()
+            .position(|&x| x == self.0)
+            .ok_or(BitStreamError::InvalidValue)?;
+        writer.write(&UnsignedExpGolombCode(code_num as u64), ())
+    }
+}
+
+// Table 9-4 – Assignment of codeNum to values of coded_block_pattern for macroblock prediction
+// modes, for ChromaArrayType equal to 1 or 2, and equal to 0 or 3
+
+const INTRA_CODED_BLOCK_PATTERN: [u8; 48] = [
+    47, 31, 15, 0, 23, 27, 29, 30, 7, 11, 13, 14, 39, 43, 45, 46, 16, 3, 5, 10, 12, 19, 21, 26, 28,
+    35, 37, 42, 44, 1, 2, 4, 8, 17, 18, 20, 24, 6, 9, 22, 25, 32, 33, 34, 36, 40, 38, 41,
+];
+
+const INTER_CODED_BLOCK_PATTERN: [u8; 48] = [
+    0, 16, 1, 2, 4, 8, 32, 3, 5, 10, 12, 15, 47, 7, 11, 13, 14, 6, 9, 31, 35, 37, 42, 44, 33, 34,
+    36, 40, 39, 43, 45, 46, 17, 18, 20, 24, 19, 21, 26, 28, 23, 27, 29, 30, 22, 25, 38, 41,
+];
+
+const INTRA_CODED_BLOCK_PATTERN_MONOCHROME: [u8; 16] =
+    [15, 0, 7, 11, 13, 14, 3, 5, 10, 12, 1, 2, 4, 8, 6, 9];
+
+const INTER_CODED_BLOCK_PATTERN_MONOCHROME: [u8; 16] =
+    [0, 1, 2, 4, 8, 3, 5, 10, 12, 15, 7, 11, 13, 14, 6, 9];
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::{MappedExpGolombCode, SignedExpGolombCode, TruncatedExpGolombCode};
+
+    #[test]
+    fn signed_exp_golomb_code() {
+        // codeNum 0 to 4
+        let data = [0b1010_0110, 0b0100_0010, 0b1000_0000];
+        let mut stream = BitStream::new(&data);
+        for value in [0, 1, -1, 2, -2] {
+            assert_eq!(stream.read::<SignedExpGolombCode>(()).unwrap().0, value);
+        }
+    }
+
+    #[test]
+    fn truncated_and_mapped_exp_golomb_code() {
+        // te(v) with range 1, te(v) with range 3 and me(v) of an intra macroblock with
+        // ChromaArrayType 1
+        let data = [0b0011_1000];
+        let mut stream = BitStream::new(&data);
+        let te: TruncatedExpGolombCode = stream.read(1).unwrap();
+        assert_eq!(te, TruncatedExpGolombCode(1));
+        let te2: TruncatedExpGolombCode = stream.read(3).unwrap();
+        assert_eq!(te2, TruncatedExpGolombCode(2));
+        let me: MappedExpGolombCode = stream.read((1, true)).unwrap();
+        assert_eq!(me, MappedExpGolombCode(47));
+
+        let mut writer = BitWriter::new();
+        writer.write(&te, 1).unwrap();
+        writer.write(&te2, 3).unwrap();
+        writer.write(&me, (1, true)).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(writer.into_inner().as_ref(), data);
+    }
+}
@@ -0,0 +1,80 @@
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitWriter, Result};
+use serde::Serialize;
+
+/// Trailing `*_extension_data_flag`s of an RBSP, read while `more_rbsp_data()`
+///
+/// Decoders shall ignore their values, they are kept so units can be written back unchanged.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExtensionData {
+    pub extension_data_flag: Vec<bool>,
+}
+
+impl<'a> BitField<'a> for ExtensionData {
+    type Args = ();
+
+    fn read(stream: &mut BitStream, _: ()) -> Result<Self> {
+        let mut extension_data_flag = Vec::new();
+        while stream.more_rbsp_data() {
+            extension_data_flag.push(stream.read_bit()?);
+        }
+
+        Ok(Self {
+            extension_data_flag,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for ExtensionData {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        for flag in &self.extension_data_flag {
+            writer.write_bit(*flag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remaining bits of an RBSP that are not parsed, read while `more_rbsp_data()`
+///
+/// The bits are packed from the most significant bit of `data`, so the unit can be written back
+/// unchanged.
+#[derive(Clone, Debug, Serialize)]
+pub struct UnparsedData {
+    pub data: Box<[u8]>,
+    pub bit_count: usize,
+}
+
+impl<'a> BitField<'a> for UnparsedData {
+    type Args = ();
+
+    fn read(stream: &mut BitStream, _: ()) -> Result<Self> {
+        let mut data = Vec::new();
+        let mut bit_count = 0;
+        while stream.more_rbsp_data() {
+            if bit_count % 8 == 0 {
+                data.push(0);
+            }
+            if stream.read_bit()? {
+                *data.last_mut().unwrap() |= 0x80 >> (bit_count % 8);
+            }
+            bit_count += 1;
+        }
+
+        Ok(Self {
+            data: data.into_boxed_slice(),
+            bit_count,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for UnparsedData {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        for i in 0..self.bit_count {
+            writer.write_bit(self.data[i / 8] & (0x80 >> (i % 8)) != 0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
+use serde::Serialize;
+
+/// filler_data_rbsp
+///
+/// § 7.3.2.7 Filler data RBSP syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct FillerData {
+    /// Count of `ff_byte`s, each one is a byte equal to 0xFF.
+    pub ff_byte_count: usize,
+}
+
+impl<'a> BitField<'a> for FillerData {
+    type Args = ();
+
+    fn read(stream: &mut BitStream, _: ()) -> Result<Self> {
+        let mut ff_byte_count = 0;
+        while stream.more_rbsp_data() {
+            let ff_byte: u8 = stream.read(8)?;
+            if ff_byte != 0xFF {
+                return Err(BitStreamError::InvalidValue);
+            }
+            ff_byte_count += 1;
+        }
+
+        Ok(Self { ff_byte_count })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for FillerData {
+    type Args = ();
+
+    fn write(&self, writer: &mut BitWriter, _: ()) -> Result<()> {
+        for _ in 0..self.ff_byte_count {
+            writer.write(&0xFFu8, 8)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError};
+
+    use super::FillerData;
+
+    #[test]
+    fn filler_data() {
+        let filler_data: FillerData = BitStream::new(&[0xff, 0xff, 0xff, 0x80]).read(()).unwrap();
+        assert_eq!(filler_data.ff_byte_count, 3);
+
+        let result: Result<FillerData, _> = BitStream::new(&[0xff, 0xfe, 0x80]).read(());
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+}
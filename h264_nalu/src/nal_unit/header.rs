@@ -65,14 +65,161 @@ cond_bit_field! {
                 }
 
                 if svc_extension_flag {
-                    // TODO nal_unit_header_svc_extension
+                    pub svc_extension: NalUnitHeaderSvcExtension;
                 } else if avc_3d_extension_flag {
-                    // TODO nal_unit_header_3davc_extension
+                    pub avc_3d_extension: NalUnitHeader3davcExtension;
                 } else {
-                    // TODO nal_unit_header_mvc_extension
+                    pub mvc_extension: NalUnitHeaderMvcExtension;
                 }
             },
             _ => {}
         }
     }
 }
+
+cond_bit_field! {
+    /// nal_unit_header_svc_extension
+    ///
+    /// § G.7.3.1.1 NAL unit header SVC extension syntax
+    #[derive(Clone, Copy, Debug, Serialize)]
+    pub struct NalUnitHeaderSvcExtension {
+        /// specifies whether the current coded picture is an IDR picture
+        /// when dependency_id is equal to the maximum value of dependency_id in the access unit.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub idr_flag: bool;
+        /// specifies a priority identifier for the NAL unit.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub priority_id: u6;
+        /// specifies whether inter-layer prediction may be used for decoding the coded slice.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub no_inter_layer_pred_flag: bool;
+        /// specifies a dependency identifier for the NAL unit.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub dependency_id: u3;
+        /// specifies a quality identifier for the NAL unit.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub quality_id: u4;
+        /// specifies a temporal identifier for the NAL unit.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub temporal_id: u3;
+        /// specifies whether the reference base pictures or the decoded pictures
+        /// are used as reference pictures for inter prediction.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub use_ref_base_pic_flag: bool;
+        /// specifies whether the NAL unit is not used for decoding
+        /// representations of the current picture or subsequent pictures with a greater dependency_id.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub discardable_flag: bool;
+        /// affects the decoded picture output and removal processes as specified in Annex C.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub output_flag: bool;
+        /// shall be equal to 3.
+        ///
+        /// § G.7.4.1.1 NAL unit header SVC extension semantics
+        pub reserved_three_2bits: u2;
+    }
+}
+
+cond_bit_field! {
+    /// nal_unit_header_mvc_extension
+    ///
+    /// § H.7.3.1.1 NAL unit header MVC extension syntax
+    #[derive(Clone, Copy, Debug, Serialize)]
+    pub struct NalUnitHeaderMvcExtension {
+        /// equal to 0 specifies that the current access unit is an IDR access unit.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub non_idr_flag: bool;
+        /// specifies a priority identifier for the NAL unit.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub priority_id: u6;
+        /// specifies a view identifier for the NAL unit.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub view_id: u10;
+        /// specifies a temporal identifier for the NAL unit.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub temporal_id: u3;
+        /// specifies whether the coded picture is an anchor picture.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub anchor_pic_flag: bool;
+        /// specifies whether the current view component is used for inter-view prediction.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub inter_view_flag: bool;
+        /// shall be equal to 1.
+        ///
+        /// § H.7.4.1.1 NAL unit header MVC extension semantics
+        pub reserved_one_bit: bool;
+    }
+}
+
+cond_bit_field! {
+    /// nal_unit_header_3davc_extension
+    ///
+    /// § J.7.3.1.1 NAL unit header 3D-AVC extension syntax
+    #[derive(Clone, Copy, Debug, Serialize)]
+    pub struct NalUnitHeader3davcExtension {
+        /// specifies the view order index for the NAL unit.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub view_idx: u8;
+        /// specifies whether the NAL unit belongs to a depth view component.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub depth_flag: bool;
+        /// equal to 0 specifies that the current access unit is an IDR access unit.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub non_idr_flag: bool;
+        /// specifies a temporal identifier for the NAL unit.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub temporal_id: u3;
+        /// specifies whether the coded picture is an anchor picture.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub anchor_pic_flag: bool;
+        /// specifies whether the current view component is used for inter-view prediction.
+        ///
+        /// § J.7.4.1.1 NAL unit header 3D-AVC extension semantics
+        pub inter_view_flag: bool;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::NalUnitHeader;
+
+    #[test]
+    fn nal_unit_header_mvc_extension() {
+        // nal_unit_type 20, non_idr_flag 1, view_id 1, inter_view_flag 1
+        let data = [0x74, 0x40, 0x00, 0x43];
+
+        let header: NalUnitHeader = BitStream::new(&data).read(()).unwrap();
+        assert_eq!(header.ty, 20);
+        assert!(!header.svc_extension_flag);
+        let mvc_extension = header.mvc_extension.unwrap();
+        assert!(mvc_extension.non_idr_flag);
+        assert_eq!(mvc_extension.view_id, 1);
+        assert!(mvc_extension.inter_view_flag);
+
+        let mut writer = BitWriter::new();
+        writer.write(&header, ()).unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+    }
+}
@@ -0,0 +1,991 @@
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
+use serde::Serialize;
+
+use crate::nal_unit::{read_residual_block, write_residual_block, CavlcContext,
+                      MappedExpGolombCode, SignedExpGolombCode, SliceTypeName,
+                      TruncatedExpGolombCode, UnsignedExpGolombCode};
+
+/// MbPartPredMode( mb_type, mbPartIdx ) and SubMbPredMode( sub_mb_type )
+///
+/// § 7.4.5 Macroblock layer semantics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum MbPartPredMode {
+    Intra4x4,
+    Intra8x8,
+    Intra16x16,
+    PredL0,
+    PredL1,
+    BiPred,
+    Direct,
+}
+
+/// Macroblock types of Table 7-11 to Table 7-14, without P_Skip and B_Skip which are inferred
+/// from mb_skip_run. Inter macroblock types of P, SP and B slices share variants.
+///
+/// § 7.4.5 Macroblock layer semantics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum MbType {
+    /// I_NxN, predicted in Intra_4x4 or Intra_8x8 depending on transform_size_8x8_flag
+    INxN,
+    /// I_16x16_<predMode>_<chroma>_<luma>
+    I16x16 {
+        intra16x16_pred_mode: u8,
+        coded_block_pattern_chroma: u8,
+        coded_block_pattern_luma: u8,
+    },
+    /// I_PCM
+    IPcm,
+    /// SI
+    Si,
+    /// P_L0_16x16, B_L0_16x16, B_L1_16x16 and B_Bi_16x16
+    Inter16x16(MbPartPredMode),
+    /// P_L0_L0_16x8 and B_X_Y_16x8
+    Inter16x8(MbPartPredMode, MbPartPredMode),
+    /// P_L0_L0_8x16 and B_X_Y_8x16
+    Inter8x16(MbPartPredMode, MbPartPredMode),
+    /// P_8x8
+    P8x8,
+    /// P_8x8ref0
+    P8x8Ref0,
+    /// B_Direct_16x16
+    BDirect16x16,
+    /// B_8x8
+    B8x8,
+}
+
+/// Prediction modes of the partitions of B_X_Y_16x8 and B_X_Y_8x16 macroblocks,
+/// in mb_type order
+///
+/// Table 7-14 – Macroblock type values 0 to 22 for B slices
+const B_PARTITION_PRED_MODES: [(MbPartPredMode, MbPartPredMode); 9] = [
+    (MbPartPredMode::PredL0, MbPartPredMode::PredL0),
+    (MbPartPredMode::PredL1, MbPartPredMode::PredL1),
+    (MbPartPredMode::PredL0, MbPartPredMode::PredL1),
+    (MbPartPredMode::PredL1, MbPartPredMode::PredL0),
+    (MbPartPredMode::PredL0, MbPartPredMode::BiPred),
+    (MbPartPredMode::PredL1, MbPartPredMode::BiPred),
+    (MbPartPredMode::BiPred, MbPartPredMode::PredL0),
+    (MbPartPredMode::BiPred, MbPartPredMode::PredL1),
+    (MbPartPredMode::BiPred, MbPartPredMode::BiPred),
+];
+
+impl MbType {
+    /// Table 7-11 – Macroblock types for I slices
+    fn new_intra(mb_type: u64) -> Result<Self> {
+        Ok(match mb_type {
+            0 => MbType::INxN,
+            1..=24 => MbType::I16x16 {
+                intra16x16_pred_mode: ((mb_type - 1) % 4) as u8,
+                coded_block_pattern_chroma: ((mb_type - 1) / 4 % 3) as u8,
+                coded_block_pattern_luma: if mb_type > 12 { 15 } else { 0 },
+            },
+            25 => MbType::IPcm,
+            _ => return Err(BitStreamError::InvalidValue),
+        })
+    }
+
+    /// Derives the macroblock type from mb_type in a slice of `slice_type_name`.
+    /// Intra macroblock types in SI, P, SP and B slices are offset by 1, 5, 5 and 23.
+    pub fn new(slice_type_name: SliceTypeName, mb_type: u64) -> Result<Self> {
+        use MbPartPredMode::*;
+
+        match slice_type_name {
+            SliceTypeName::I => Self::new_intra(mb_type),
+            SliceTypeName::SI => match mb_type {
+                0 => Ok(MbType::Si),
+                _ => Self::new_intra(mb_type - 1),
+            },
+            SliceTypeName::P | SliceTypeName::SP => Ok(match mb_type {
+                0 => MbType::Inter16x16(PredL0),
+                1 => MbType::Inter16x8(PredL0, PredL0),
+                2 => MbType::Inter8x16(PredL0, PredL0),
+                3 => MbType::P8x8,
+                4 => MbType::P8x8Ref0,
+                _ => return Self::new_intra(mb_type - 5),
+            }),
+            SliceTypeName::B => Ok(match mb_type {
+                0 => MbType::BDirect16x16,
+                1 => MbType::Inter16x16(PredL0),
+                2 => MbType::Inter16x16(PredL1),
+                3 => MbType::Inter16x16(BiPred),
+                4..=21 => {
+                    let (first, second) = B_PARTITION_PRED_MODES[(mb_type as usize - 4) / 2];
+                    if mb_type.is_multiple_of(2) {
+                        MbType::Inter16x8(first, second)
+                    } else {
+                        MbType::Inter8x16(first, second)
+                    }
+                }
+                22 => MbType::B8x8,
+                _ => return Self::new_intra(mb_type - 23),
+            }),
+        }
+    }
+
+    /// Returns the mb_type value of this macroblock type in a slice of `slice_type_name`
+    pub fn value(&self, slice_type_name: SliceTypeName) -> Result<u64> {
+        (0..=48)
+            .find(|&value| Self::new(slice_type_name, value).ok().as_ref() == Some(self))
+            .ok_or(BitStreamError::InvalidValue)
+    }
+
+    /// NumMbPart( mb_type ), 1 for macroblock types without partitions
+    pub fn num_mb_part(&self) -> usize {
+        match self {
+            MbType::Inter16x8(..) | MbType::Inter8x16(..) => 2,
+            MbType::P8x8 | MbType::P8x8Ref0 | MbType::B8x8 => 4,
+            _ => 1,
+        }
+    }
+
+    /// MbPartPredMode( mb_type, mbPartIdx ), `None` for macroblock types where it's not applicable
+    pub fn mb_part_pred_mode(
+        &self,
+        mb_part_idx: usize,
+        transform_size_8x8_flag: bool,
+    ) -> Option<MbPartPredMode> {
+        match *self {
+            MbType::INxN if transform_size_8x8_flag => Some(MbPartPredMode::Intra8x8),
+            MbType::INxN | MbType::Si => Some(MbPartPredMode::Intra4x4),
+            MbType::I16x16 { .. } => Some(MbPartPredMode::Intra16x16),
+            MbType::Inter16x16(mode) => Some(mode),
+            MbType::Inter16x8(first, second) | MbType::Inter8x16(first, second) => {
+                Some(if mb_part_idx == 0 { first } else { second })
+            }
+            MbType::BDirect16x16 => Some(MbPartPredMode::Direct),
+            MbType::IPcm | MbType::P8x8 | MbType::P8x8Ref0 | MbType::B8x8 => None,
+        }
+    }
+}
+
+/// Returns SubMbPredMode( sub_mb_type ) and NumSubMbPart( sub_mb_type )
+///
+/// Table 7-17 – Sub-macroblock types in P macroblocks
+///
+/// Table 7-18 – Sub-macroblock types in B macroblocks
+pub(crate) fn sub_mb_type_info(
+    slice_type_name: SliceTypeName,
+    sub_mb_type: u64,
+) -> Result<(MbPartPredMode, usize)> {
+    use MbPartPredMode::*;
+
+    Ok(match (slice_type_name, sub_mb_type) {
+        (SliceTypeName::B, 0) => (Direct, 4),
+        (SliceTypeName::B, 1) => (PredL0, 1),
+        (SliceTypeName::B, 2) => (PredL1, 1),
+        (SliceTypeName::B, 3) => (BiPred, 1),
+        (SliceTypeName::B, 4..=5) => (PredL0, 2),
+        (SliceTypeName::B, 6..=7) => (PredL1, 2),
+        (SliceTypeName::B, 8..=9) => (BiPred, 2),
+        (SliceTypeName::B, 10) => (PredL0, 4),
+        (SliceTypeName::B, 11) => (PredL1, 4),
+        (SliceTypeName::B, 12) => (BiPred, 4),
+        (SliceTypeName::B, _) => return Err(BitStreamError::InvalidValue),
+        (_, 0) => (PredL0, 1),
+        (_, 1..=2) => (PredL0, 2),
+        (_, 3) => (PredL0, 4),
+        (_, _) => return Err(BitStreamError::InvalidValue),
+    })
+}
+
+/// Values from the active parameter sets and the slice header used by macroblock_layer( )
+#[derive(Clone, Copy, Debug)]
+pub struct MacroblockParameters {
+    pub slice_type_name: SliceTypeName,
+    pub chroma_array_type: u64,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+    pub transform_8x8_mode_flag: bool,
+    pub direct_8x8_inference_flag: bool,
+    pub num_ref_idx_l0_active_minus1: u64,
+    pub num_ref_idx_l1_active_minus1: u64,
+}
+
+impl MacroblockParameters {
+    /// MbWidthC * MbHeightC, the number of samples of each chroma component in a macroblock
+    ///
+    /// § 6.2 Source, decoded, and output picture formats
+    pub(crate) fn chroma_sample_count(&self) -> usize {
+        match self.chroma_array_type {
+            1 => 8 * 8,
+            2 => 8 * 16,
+            3 => 16 * 16,
+            _ => 0,
+        }
+    }
+}
+
+/// mb_pred( mb_type )
+///
+/// § 7.3.5.1 Macroblock prediction syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct MbPred {
+    pub prev_intra4x4_pred_mode_flag: Vec<bool>,
+    pub rem_intra4x4_pred_mode: Vec<Option<u8>>,
+    pub prev_intra8x8_pred_mode_flag: Vec<bool>,
+    pub rem_intra8x8_pred_mode: Vec<Option<u8>>,
+    pub intra_chroma_pred_mode: Option<UnsignedExpGolombCode>,
+    /// ref_idx_l0 of each macroblock partition
+    pub ref_idx_l0: Vec<Option<TruncatedExpGolombCode>>,
+    /// ref_idx_l1 of each macroblock partition
+    pub ref_idx_l1: Vec<Option<TruncatedExpGolombCode>>,
+    pub mvd_l0: Vec<Option<[SignedExpGolombCode; 2]>>,
+    pub mvd_l1: Vec<Option<[SignedExpGolombCode; 2]>>,
+}
+
+impl<'a> BitField<'a> for MbPred {
+    type Args = (&'a MacroblockParameters, MbType, bool);
+
+    fn read(
+        stream: &mut BitStream,
+        (params, mb_type, transform_size_8x8_flag): Self::Args,
+    ) -> Result<Self> {
+        let mut mb_pred = MbPred {
+            prev_intra4x4_pred_mode_flag: vec![],
+            rem_intra4x4_pred_mode: vec![],
+            prev_intra8x8_pred_mode_flag: vec![],
+            rem_intra8x8_pred_mode: vec![],
+            intra_chroma_pred_mode: None,
+            ref_idx_l0: vec![],
+            ref_idx_l1: vec![],
+            mvd_l0: vec![],
+            mvd_l1: vec![],
+        };
+
+        match mb_type.mb_part_pred_mode(0, transform_size_8x8_flag) {
+            Some(MbPartPredMode::Intra4x4) => {
+                for _ in 0..16 {
+                    let flag = stream.read_bit()?;
+                    mb_pred.prev_intra4x4_pred_mode_flag.push(flag);
+                    mb_pred.rem_intra4x4_pred_mode.push(if flag {
+                        None
+                    } else {
+                        Some(stream.read(3)?)
+                    });
+                }
+            }
+            Some(MbPartPredMode::Intra8x8) => {
+                for _ in 0..4 {
+                    let flag = stream.read_bit()?;
+                    mb_pred.prev_intra8x8_pred_mode_flag.push(flag);
+                    mb_pred.rem_intra8x8_pred_mode.push(if flag {
+                        None
+                    } else {
+                        Some(stream.read(3)?)
+                    });
+                }
+            }
+            Some(MbPartPredMode::Intra16x16) | Some(MbPartPredMode::Direct) => {}
+            _ => {
+                let modes: Vec<_> = (0..mb_type.num_mb_part())
+                    .map(|i| mb_type.mb_part_pred_mode(i, false))
+                    .collect();
+                for mode in &modes {
+                    mb_pred.ref_idx_l0.push(
+                        if params.num_ref_idx_l0_active_minus1 > 0
+                            && *mode != Some(MbPartPredMode::PredL1)
+                        {
+                            Some(stream.read(params.num_ref_idx_l0_active_minus1)?)
+                        } else {
+                            None
+                        },
+                    );
+                }
+                for mode in &modes {
+                    mb_pred.ref_idx_l1.push(
+                        if params.num_ref_idx_l1_active_minus1 > 0
+                            && *mode != Some(MbPartPredMode::PredL0)
+                        {
+                            Some(stream.read(params.num_ref_idx_l1_active_minus1)?)
+                        } else {
+                            None
+                        },
+                    );
+                }
+                for mode in &modes {
+                    mb_pred
+                        .mvd_l0
+                        .push(if *mode != Some(MbPartPredMode::PredL1) {
+                            Some([stream.read(())?, stream.read(())?])
+                        } else {
+                            None
+                        });
+                }
+                for mode in &modes {
+                    mb_pred
+                        .mvd_l1
+                        .push(if *mode != Some(MbPartPredMode::PredL0) {
+                            Some([stream.read(())?, stream.read(())?])
+                        } else {
+                            None
+                        });
+                }
+            }
+        }
+
+        let intra = matches!(
+            mb_type.mb_part_pred_mode(0, transform_size_8x8_flag),
+            Some(MbPartPredMode::Intra4x4)
+                | Some(MbPartPredMode::Intra8x8)
+                | Some(MbPartPredMode::Intra16x16)
+        );
+        if intra && (params.chroma_array_type == 1 || params.chroma_array_type == 2) {
+            mb_pred.intra_chroma_pred_mode = Some(stream.read(())?);
+        }
+
+        Ok(mb_pred)
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for MbPred {
+    type Args = (&'a MacroblockParameters, MbType, bool);
+
+    fn write(&self, writer: &mut BitWriter, (params, _, _): Self::Args) -> Result<()> {
+        for (flag, rem) in self
+            .prev_intra4x4_pred_mode_flag
+            .iter()
+            .zip(&self.rem_intra4x4_pred_mode)
+            .chain(
+                self.prev_intra8x8_pred_mode_flag
+                    .iter()
+                    .zip(&self.rem_intra8x8_pred_mode),
+            )
+        {
+            writer.write_bit(*flag)?;
+            if !flag {
+                writer.write(&rem.ok_or(BitStreamError::MissingValue)?, 3)?;
+            }
+        }
+        if let Some(intra_chroma_pred_mode) = &self.intra_chroma_pred_mode {
+            writer.write(intra_chroma_pred_mode, ())?;
+        }
+        for ref_idx in self.ref_idx_l0.iter().flatten() {
+            writer.write(ref_idx, params.num_ref_idx_l0_active_minus1)?;
+        }
+        for ref_idx in self.ref_idx_l1.iter().flatten() {
+            writer.write(ref_idx, params.num_ref_idx_l1_active_minus1)?;
+        }
+        for mvd in self.mvd_l0.iter().chain(&self.mvd_l1).flatten() {
+            writer.write(&mvd[0], ())?;
+            writer.write(&mvd[1], ())?;
+        }
+        Ok(())
+    }
+}
+
+/// sub_mb_pred( mb_type )
+///
+/// § 7.3.5.2 Sub-macroblock prediction syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct SubMbPred {
+    pub sub_mb_type: Vec<UnsignedExpGolombCode>,
+    /// ref_idx_l0 of each sub-macroblock
+    pub ref_idx_l0: Vec<Option<TruncatedExpGolombCode>>,
+    /// ref_idx_l1 of each sub-macroblock
+    pub ref_idx_l1: Vec<Option<TruncatedExpGolombCode>>,
+    /// mvd_l0 of each sub-macroblock partition in each sub-macroblock
+    pub mvd_l0: Vec<Vec<[SignedExpGolombCode; 2]>>,
+    /// mvd_l1 of each sub-macroblock partition in each sub-macroblock
+    pub mvd_l1: Vec<Vec<[SignedExpGolombCode; 2]>>,
+}
+
+impl SubMbPred {
+    /// Derives noSubMbPartSizeLessThan8x8Flag
+    ///
+    /// § 7.3.5 Macroblock layer syntax
+    pub(crate) fn no_sub_mb_part_size_less_than_8x8(
+        &self,
+        params: &MacroblockParameters,
+    ) -> Result<bool> {
+        for sub_mb_type in &self.sub_mb_type {
+            let (mode, num_sub_mb_part) = sub_mb_type_info(params.slice_type_name, sub_mb_type.0)?;
+            if mode != MbPartPredMode::Direct {
+                if num_sub_mb_part > 1 {
+                    return Ok(false);
+                }
+            } else if !params.direct_8x8_inference_flag {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<'a> BitField<'a> for SubMbPred {
+    type Args = (&'a MacroblockParameters, MbType);
+
+    fn read(stream: &mut BitStream, (params, mb_type): Self::Args) -> Result<Self> {
+        let mut sub_mb_type = Vec::with_capacity(4);
+        let mut infos = Vec::with_capacity(4);
+        for _ in 0..4 {
+            let value: UnsignedExpGolombCode = stream.read(())?;
+            infos.push(sub_mb_type_info(params.slice_type_name, value.0)?);
+            sub_mb_type.push(value);
+        }
+
+        let mut ref_idx_l0 = Vec::with_capacity(4);
+        for &(mode, _) in &infos {
+            ref_idx_l0.push(
+                if params.num_ref_idx_l0_active_minus1 > 0
+                    && mb_type != MbType::P8x8Ref0
+                    && mode != MbPartPredMode::Direct
+                    && mode != MbPartPredMode::PredL1
+                {
+                    Some(stream.read(params.num_ref_idx_l0_active_minus1)?)
+                } else {
+                    None
+                },
+            );
+        }
+
+        let mut ref_idx_l1 = Vec::with_capacity(4);
+        for &(mode, _) in &infos {
+            ref_idx_l1.push(
+                if params.num_ref_idx_l1_active_minus1 > 0
+                    && mode != MbPartPredMode::Direct
+                    && mode != MbPartPredMode::PredL0
+                {
+                    Some(stream.read(params.num_ref_idx_l1_active_minus1)?)
+                } else {
+                    None
+                },
+            );
+        }
+
+        let mut mvd_l0 = Vec::with_capacity(4);
+        for &(mode, num_sub_mb_part) in &infos {
+            let mut mvd = vec![];
+            if mode != MbPartPredMode::Direct && mode != MbPartPredMode::PredL1 {
+                for _ in 0..num_sub_mb_part {
+                    mvd.push([stream.read(())?, stream.read(())?]);
+                }
+            }
+            mvd_l0.push(mvd);
+        }
+
+        let mut mvd_l1 = Vec::with_capacity(4);
+        for &(mode, num_sub_mb_part) in &infos {
+            let mut mvd = vec![];
+            if mode != MbPartPredMode::Direct && mode != MbPartPredMode::PredL0 {
+                for _ in 0..num_sub_mb_part {
+                    mvd.push([stream.read(())?, stream.read(())?]);
+                }
+            }
+            mvd_l1.push(mvd);
+        }
+
+        Ok(Self {
+            sub_mb_type,
+            ref_idx_l0,
+            ref_idx_l1,
+            mvd_l0,
+            mvd_l1,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for SubMbPred {
+    type Args = (&'a MacroblockParameters, MbType);
+
+    fn write(&self, writer: &mut BitWriter, (params, _): Self::Args) -> Result<()> {
+        for sub_mb_type in &self.sub_mb_type {
+            writer.write(sub_mb_type, ())?;
+        }
+        for ref_idx in self.ref_idx_l0.iter().flatten() {
+            writer.write(ref_idx, params.num_ref_idx_l0_active_minus1)?;
+        }
+        for ref_idx in self.ref_idx_l1.iter().flatten() {
+            writer.write(ref_idx, params.num_ref_idx_l1_active_minus1)?;
+        }
+        for mvd in self.mvd_l0.iter().chain(&self.mvd_l1).flatten() {
+            writer.write(&mvd[0], ())?;
+            writer.write(&mvd[1], ())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the position in 4x4 blocks of the luma4x4BlkIdx-th block in a macroblock
+///
+/// § 6.4.3 Inverse 4x4 luma block scanning process
+pub(crate) fn luma4x4_block_position(luma4x4_blk_idx: usize) -> (usize, usize) {
+    (
+        luma4x4_blk_idx / 4 % 2 * 2 + luma4x4_blk_idx % 4 % 2,
+        luma4x4_blk_idx / 4 / 2 * 2 + luma4x4_blk_idx % 4 / 2,
+    )
+}
+
+fn total_coeff(coeff_level: &[i32]) -> usize {
+    coeff_level.iter().filter(|&&x| x != 0).count()
+}
+
+/// residual_luma( i16x16DClevel, i16x16AClevel, level4x4, level8x8, 0, 15 ) of one colour
+/// component, read with CAVLC by `BitField` or with CABAC by [`CabacContext`].
+///
+/// Blocks not coded according to CodedBlockPatternLuma have all coefficients equal to 0.
+/// With transform_size_8x8_flag, level8x8[ i8x8 ][ 4 * i + i4x4 ] is
+/// `level4x4[ i8x8 * 4 + i4x4 ][ i ]`.
+///
+/// § 7.3.5.3.1 Residual luma syntax
+///
+/// [`CabacContext`]: crate::nal_unit::CabacContext
+#[derive(Clone, Debug, Serialize)]
+pub struct ResidualLuma {
+    /// i16x16DClevel, for Intra_16x16 macroblocks
+    pub i16x16_dc_level: Option<Vec<i32>>,
+    /// i16x16AClevel, for Intra_16x16 macroblocks
+    pub i16x16_ac_level: Option<Vec<Vec<i32>>>,
+    /// level4x4, for other macroblocks
+    pub level4x4: Option<Vec<Vec<i32>>>,
+}
+
+impl<'a> BitField<'a> for ResidualLuma {
+    /// `(context, component, Intra_16x16, CodedBlockPatternLuma)`,
+    /// `component` is 0 for Y, 1 for Cb and 2 for Cr.
+    type Args = (&'a mut CavlcContext, usize, bool, u8);
+
+    fn read(
+        stream: &mut BitStream,
+        (context, component, intra16x16, coded_block_pattern_luma): Self::Args,
+    ) -> Result<Self> {
+        let i16x16_dc_level = if intra16x16 {
+            let n_c = context.n_c(component, 0, 0);
+            Some(read_residual_block(stream, n_c, 0, 15, 16)?)
+        } else {
+            None
+        };
+
+        let max_num_coeff = if intra16x16 { 15 } else { 16 };
+        let mut blocks = Vec::with_capacity(16);
+        for luma4x4_blk_idx in 0..16 {
+            if coded_block_pattern_luma & (1 << (luma4x4_blk_idx / 4)) != 0 {
+                let (x, y) = luma4x4_block_position(luma4x4_blk_idx);
+                let n_c = context.n_c(component, x, y);
+                let coeff_level =
+                    read_residual_block(stream, n_c, 0, max_num_coeff - 1, max_num_coeff)?;
+                context.set_total_coeff(component, x, y, total_coeff(&coeff_level));
+                blocks.push(coeff_level);
+            } else {
+                blocks.push(vec![0; max_num_coeff]);
+            }
+        }
+
+        Ok(if intra16x16 {
+            Self {
+                i16x16_dc_level,
+                i16x16_ac_level: Some(blocks),
+                level4x4: None,
+            }
+        } else {
+            Self {
+                i16x16_dc_level,
+                i16x16_ac_level: None,
+                level4x4: Some(blocks),
+            }
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for ResidualLuma {
+    type Args = (&'a mut CavlcContext, usize, bool, u8);
+
+    fn write(
+        &self,
+        writer: &mut BitWriter,
+        (context, component, intra16x16, coded_block_pattern_luma): Self::Args,
+    ) -> Result<()> {
+        let blocks = if intra16x16 {
+            let i16x16_dc_level = self
+                .i16x16_dc_level
+                .as_ref()
+                .ok_or(BitStreamError::MissingValue)?;
+            let n_c = context.n_c(component, 0, 0);
+            write_residual_block(writer, i16x16_dc_level, n_c, 0, 15)?;
+            &self.i16x16_ac_level
+        } else {
+            &self.level4x4
+        };
+        let blocks = blocks.as_ref().ok_or(BitStreamError::MissingValue)?;
+
+        for (luma4x4_blk_idx, coeff_level) in blocks.iter().enumerate() {
+            if coded_block_pattern_luma & (1 << (luma4x4_blk_idx / 4)) != 0 {
+                let (x, y) = luma4x4_block_position(luma4x4_blk_idx);
+                let n_c = context.n_c(component, x, y);
+                write_residual_block(writer, coeff_level, n_c, 0, coeff_level.len() - 1)?;
+                context.set_total_coeff(component, x, y, total_coeff(coeff_level));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// residual( 0, 15 ), read with CAVLC by `BitField` or with CABAC by [`CabacContext`].
+///
+/// § 7.3.5.3 Residual data syntax
+///
+/// [`CabacContext`]: crate::nal_unit::CabacContext
+#[derive(Clone, Debug, Serialize)]
+pub struct Residual {
+    pub luma: ResidualLuma,
+    /// ChromaDCLevel of Cb and Cr, for ChromaArrayType 1 and 2
+    pub chroma_dc_level: Option<Vec<Vec<i32>>>,
+    /// ChromaACLevel of Cb and Cr, indexed by chroma4x4BlkIdx, for ChromaArrayType 1 and 2.
+    /// Blocks not coded according to CodedBlockPatternChroma have all coefficients equal to 0.
+    pub chroma_ac_level: Option<Vec<Vec<Vec<i32>>>>,
+    /// Residual of Cb, for ChromaArrayType 3
+    pub cb: Option<ResidualLuma>,
+    /// Residual of Cr, for ChromaArrayType 3
+    pub cr: Option<ResidualLuma>,
+}
+
+impl<'a> BitField<'a> for Residual {
+    /// `(params, context, Intra_16x16, CodedBlockPatternLuma, CodedBlockPatternChroma)`
+    type Args = (&'a MacroblockParameters, &'a mut CavlcContext, bool, u8, u8);
+
+    fn read(
+        stream: &mut BitStream,
+        (params, context, intra16x16, coded_block_pattern_luma, coded_block_pattern_chroma): Self::Args,
+    ) -> Result<Self> {
+        let luma = stream.read((&mut *context, 0, intra16x16, coded_block_pattern_luma))?;
+
+        let mut residual = Self {
+            luma,
+            chroma_dc_level: None,
+            chroma_ac_level: None,
+            cb: None,
+            cr: None,
+        };
+
+        match params.chroma_array_type {
+            1 | 2 => {
+                // NumC8x8 is 4 / ( SubWidthC * SubHeightC )
+                let num_c8x8 = params.chroma_array_type as usize;
+                let n_c = -(params.chroma_array_type as i32);
+
+                let mut chroma_dc_level = Vec::with_capacity(2);
+                for _ in 0..2 {
+                    chroma_dc_level.push(if coded_block_pattern_chroma & 3 != 0 {
+                        read_residual_block(stream, n_c, 0, 4 * num_c8x8 - 1, 4 * num_c8x8)?
+                    } else {
+                        vec![0; 4 * num_c8x8]
+                    });
+                }
+
+                let mut chroma_ac_level = Vec::with_capacity(2);
+                for component in 1..=2 {
+                    let mut blocks = Vec::with_capacity(num_c8x8 * 4);
+                    for chroma4x4_blk_idx in 0..num_c8x8 * 4 {
+                        if coded_block_pattern_chroma & 2 != 0 {
+                            let (x, y) = (chroma4x4_blk_idx % 2, chroma4x4_blk_idx / 2);
+                            let n_c = context.n_c(component, x, y);
+                            let coeff_level = read_residual_block(stream, n_c, 0, 14, 15)?;
+                            context.set_total_coeff(component, x, y, total_coeff(&coeff_level));
+                            blocks.push(coeff_level);
+                        } else {
+                            blocks.push(vec![0; 15]);
+                        }
+                    }
+                    chroma_ac_level.push(blocks);
+                }
+
+                residual.chroma_dc_level = Some(chroma_dc_level);
+                residual.chroma_ac_level = Some(chroma_ac_level);
+            }
+            3 => {
+                residual.cb =
+                    Some(stream.read((&mut *context, 1, intra16x16, coded_block_pattern_luma))?);
+                residual.cr =
+                    Some(stream.read((&mut *context, 2, intra16x16, coded_block_pattern_luma))?);
+            }
+            _ => {}
+        }
+
+        Ok(residual)
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for Residual {
+    type Args = (&'a MacroblockParameters, &'a mut CavlcContext, bool, u8, u8);
+
+    fn write(
+        &self,
+        writer: &mut BitWriter,
+        (params, context, intra16x16, coded_block_pattern_luma, coded_block_pattern_chroma): Self::Args,
+    ) -> Result<()> {
+        writer.write(
+            &self.luma,
+            (&mut *context, 0, intra16x16, coded_block_pattern_luma),
+        )?;
+
+        match params.chroma_array_type {
+            1 | 2 => {
+                let num_c8x8 = params.chroma_array_type as usize;
+                let n_c = -(params.chroma_array_type as i32);
+
+                if coded_block_pattern_chroma & 3 != 0 {
+                    let chroma_dc_level = self
+                        .chroma_dc_level
+                        .as_ref()
+                        .ok_or(BitStreamError::MissingValue)?;
+                    for coeff_level in chroma_dc_level {
+                        write_residual_block(writer, coeff_level, n_c, 0, 4 * num_c8x8 - 1)?;
+                    }
+                }
+
+                if coded_block_pattern_chroma & 2 != 0 {
+                    let chroma_ac_level = self
+                        .chroma_ac_level
+                        .as_ref()
+                        .ok_or(BitStreamError::MissingValue)?;
+                    for (component, blocks) in (1..=2).zip(chroma_ac_level) {
+                        for (chroma4x4_blk_idx, coeff_level) in blocks.iter().enumerate() {
+                            let (x, y) = (chroma4x4_blk_idx % 2, chroma4x4_blk_idx / 2);
+                            let n_c = context.n_c(component, x, y);
+                            write_residual_block(writer, coeff_level, n_c, 0, 14)?;
+                            context.set_total_coeff(component, x, y, total_coeff(coeff_level));
+                        }
+                    }
+                }
+            }
+            3 => {
+                let cb = self.cb.as_ref().ok_or(BitStreamError::MissingValue)?;
+                writer.write(cb, (&mut *context, 1, intra16x16, coded_block_pattern_luma))?;
+                let cr = self.cr.as_ref().ok_or(BitStreamError::MissingValue)?;
+                writer.write(cr, (&mut *context, 2, intra16x16, coded_block_pattern_luma))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// macroblock_layer( ), read with CAVLC by `BitField` or with CABAC by [`CabacContext`].
+///
+/// § 7.3.5 Macroblock layer syntax
+///
+/// [`CabacContext`]: crate::nal_unit::CabacContext
+#[derive(Clone, Debug, Serialize)]
+pub struct MacroblockLayer {
+    /// Derived from the mb_type syntax element according to the slice type
+    pub mb_type: MbType,
+    /// pcm_sample_luma, for I_PCM macroblocks
+    pub pcm_sample_luma: Option<Vec<u16>>,
+    /// pcm_sample_chroma, for I_PCM macroblocks
+    pub pcm_sample_chroma: Option<Vec<u16>>,
+    pub sub_mb_pred: Option<SubMbPred>,
+    pub mb_pred: Option<MbPred>,
+    pub transform_size_8x8_flag: Option<bool>,
+    pub coded_block_pattern: Option<MappedExpGolombCode>,
+    pub mb_qp_delta: Option<SignedExpGolombCode>,
+    pub residual: Option<Residual>,
+}
+
+impl MacroblockLayer {
+    /// Returns `(CodedBlockPatternLuma, CodedBlockPatternChroma)`
+    pub fn coded_block_pattern(&self) -> (u8, u8) {
+        match self.mb_type {
+            MbType::I16x16 {
+                coded_block_pattern_luma,
+                coded_block_pattern_chroma,
+                ..
+            } => (coded_block_pattern_luma, coded_block_pattern_chroma),
+            _ => self
+                .coded_block_pattern
+                .map_or((0, 0), |x| (x.0 % 16, x.0 / 16)),
+        }
+    }
+}
+
+impl<'a> BitField<'a> for MacroblockLayer {
+    type Args = (&'a MacroblockParameters, &'a mut CavlcContext);
+
+    fn read(stream: &mut BitStream, (params, context): Self::Args) -> Result<Self> {
+        let mb_type = MbType::new(
+            params.slice_type_name,
+            stream.read::<UnsignedExpGolombCode>(())?.0,
+        )?;
+
+        let mut layer = Self {
+            mb_type,
+            pcm_sample_luma: None,
+            pcm_sample_chroma: None,
+            sub_mb_pred: None,
+            mb_pred: None,
+            transform_size_8x8_flag: None,
+            coded_block_pattern: None,
+            mb_qp_delta: None,
+            residual: None,
+        };
+
+        if mb_type == MbType::IPcm {
+            while !stream.byte_aligned() {
+                // pcm_alignment_zero_bit
+                if stream.read_bit()? {
+                    return Err(BitStreamError::InvalidValue);
+                }
+            }
+            layer.pcm_sample_luma = Some(
+                (0..256)
+                    .map(|_| stream.read(params.bit_depth_luma))
+                    .collect::<Result<_>>()?,
+            );
+            layer.pcm_sample_chroma = Some(
+                (0..2 * params.chroma_sample_count())
+                    .map(|_| stream.read(params.bit_depth_chroma))
+                    .collect::<Result<_>>()?,
+            );
+            context.set_pcm();
+            return Ok(layer);
+        }
+
+        let mut no_sub_mb_part_size_less_than_8x8_flag = true;
+        if mb_type.num_mb_part() == 4 {
+            let sub_mb_pred: SubMbPred = stream.read((params, mb_type))?;
+            no_sub_mb_part_size_less_than_8x8_flag =
+                sub_mb_pred.no_sub_mb_part_size_less_than_8x8(params)?;
+            layer.sub_mb_pred = Some(sub_mb_pred);
+        } else {
+            if params.transform_8x8_mode_flag && mb_type == MbType::INxN {
+                layer.transform_size_8x8_flag = Some(stream.read_bit()?);
+            }
+            let transform_size_8x8_flag = layer.transform_size_8x8_flag == Some(true);
+            layer.mb_pred = Some(stream.read((params, mb_type, transform_size_8x8_flag))?);
+        }
+
+        let mb_part_pred_mode =
+            mb_type.mb_part_pred_mode(0, layer.transform_size_8x8_flag == Some(true));
+        if mb_part_pred_mode != Some(MbPartPredMode::Intra16x16) {
+            let intra = mb_part_pred_mode == Some(MbPartPredMode::Intra4x4)
+                || mb_part_pred_mode == Some(MbPartPredMode::Intra8x8);
+            let coded_block_pattern: MappedExpGolombCode =
+                stream.read((params.chroma_array_type, intra))?;
+            layer.coded_block_pattern = Some(coded_block_pattern);
+
+            if !coded_block_pattern.0.is_multiple_of(16)
+                && params.transform_8x8_mode_flag
+                && mb_type != MbType::INxN
+                && no_sub_mb_part_size_less_than_8x8_flag
+                && (mb_type != MbType::BDirect16x16 || params.direct_8x8_inference_flag)
+            {
+                layer.transform_size_8x8_flag = Some(stream.read_bit()?);
+            }
+        }
+
+        let (coded_block_pattern_luma, coded_block_pattern_chroma) = layer.coded_block_pattern();
+        let intra16x16 = mb_part_pred_mode == Some(MbPartPredMode::Intra16x16);
+        if coded_block_pattern_luma > 0 || coded_block_pattern_chroma > 0 || intra16x16 {
+            layer.mb_qp_delta = Some(stream.read(())?);
+            layer.residual = Some(stream.read((
+                params,
+                context,
+                intra16x16,
+                coded_block_pattern_luma,
+                coded_block_pattern_chroma,
+            ))?);
+        }
+
+        Ok(layer)
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for MacroblockLayer {
+    type Args = (&'a MacroblockParameters, &'a mut CavlcContext);
+
+    fn write(&self, writer: &mut BitWriter, (params, context): Self::Args) -> Result<()> {
+        writer.write(
+            &UnsignedExpGolombCode(self.mb_type.value(params.slice_type_name)?),
+            (),
+        )?;
+
+        if self.mb_type == MbType::IPcm {
+            let pcm_sample_luma = self
+                .pcm_sample_luma
+                .as_ref()
+                .ok_or(BitStreamError::MissingValue)?;
+            let pcm_sample_chroma = self
+                .pcm_sample_chroma
+                .as_ref()
+                .ok_or(BitStreamError::MissingValue)?;
+            if pcm_sample_luma.len() != 256
+                || pcm_sample_chroma.len() != 2 * params.chroma_sample_count()
+            {
+                return Err(BitStreamError::InvalidValue);
+            }
+
+            while !writer.byte_aligned() {
+                writer.write_bit(false)?;
+            }
+            for sample in pcm_sample_luma {
+                writer.write(sample, params.bit_depth_luma)?;
+            }
+            for sample in pcm_sample_chroma {
+                writer.write(sample, params.bit_depth_chroma)?;
+            }
+            context.set_pcm();
+            return Ok(());
+        }
+
+        if self.mb_type.num_mb_part() == 4 {
+            let sub_mb_pred = self
+                .sub_mb_pred
+                .as_ref()
+                .ok_or(BitStreamError::MissingValue)?;
+            writer.write(sub_mb_pred, (params, self.mb_type))?;
+        } else {
+            if params.transform_8x8_mode_flag && self.mb_type == MbType::INxN {
+                let transform_size_8x8_flag = self
+                    .transform_size_8x8_flag
+                    .ok_or(BitStreamError::MissingValue)?;
+                writer.write_bit(transform_size_8x8_flag)?;
+            }
+            let mb_pred = self.mb_pred.as_ref().ok_or(BitStreamError::MissingValue)?;
+            writer.write(
+                mb_pred,
+                (
+                    params,
+                    self.mb_type,
+                    self.transform_size_8x8_flag == Some(true),
+                ),
+            )?;
+        }
+
+        let mb_part_pred_mode = self
+            .mb_type
+            .mb_part_pred_mode(0, self.transform_size_8x8_flag == Some(true));
+        if mb_part_pred_mode != Some(MbPartPredMode::Intra16x16) {
+            let intra = mb_part_pred_mode == Some(MbPartPredMode::Intra4x4)
+                || mb_part_pred_mode == Some(MbPartPredMode::Intra8x8);
+            let coded_block_pattern = self
+                .coded_block_pattern
+                .ok_or(BitStreamError::MissingValue)?;
+            writer.write(&coded_block_pattern, (params.chroma_array_type, intra))?;
+
+            if self.mb_type != MbType::INxN {
+                if let Some(transform_size_8x8_flag) = self.transform_size_8x8_flag {
+                    writer.write_bit(transform_size_8x8_flag)?;
+                }
+            }
+        }
+
+        let (coded_block_pattern_luma, coded_block_pattern_chroma) = self.coded_block_pattern();
+        let intra16x16 = mb_part_pred_mode == Some(MbPartPredMode::Intra16x16);
+        if coded_block_pattern_luma > 0 || coded_block_pattern_chroma > 0 || intra16x16 {
+            let mb_qp_delta = self.mb_qp_delta.ok_or(BitStreamError::MissingValue)?;
+            writer.write(&mb_qp_delta, ())?;
+            let residual = self.residual.as_ref().ok_or(BitStreamError::MissingValue)?;
+            writer.write(
+                residual,
+                (
+                    params,
+                    context,
+                    intra16x16,
+                    coded_block_pattern_luma,
+                    coded_block_pattern_chroma,
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{nal_unit::{ScalingList, SignedExpGolombCode, UnsignedExpGolombCode},
             Decoder};
-use bit_stream::cond_bit_field;
+use bit_stream::{cond_bit_field, BitStreamError};
 use serde::Serialize;
 
 cond_bit_field! {
@@ -31,7 +31,7 @@ cond_bit_field! {
                         pub bottom_right: UnsignedExpGolombCode;
                     }
                 }
-                3 | 4 | 5 =>{
+                3..=5 =>{
                     pub slice_group_change_direction_flag: bool;
                     pub slice_group_change_rate_minus1: UnsignedExpGolombCode;
                 }
@@ -58,22 +58,78 @@ cond_bit_field! {
         pub constrained_intra_pred_flag: bool;
         pub redundant_pic_cnt_present_flag: bool;
 
-        if stream.remaining() != 0 {
+        #[write_cond(transform_8x8_mode_flag.is_some())]
+        if stream.more_rbsp_data() {
             pub transform_8x8_mode_flag: bool;
             pub pic_scaling_matrix_present_flag: bool;
 
             if pic_scaling_matrix_present_flag {
-                for _ in 0..6 {
-                    pub scaling_list_4x4: ScalingList[16];
+                let mut list_count = 6;
+                if transform_8x8_mode_flag {
+                    // A PPS may refer to a subset SPS, used by MVC and SVC slice extensions
+                    let seq_parameter_set = decoder
+                        .find_sequence_parameter_set(seq_parameter_set_id)
+                        .or_else(|| {
+                            decoder
+                                .find_subset_sequence_parameter_set(seq_parameter_set_id)
+                                .map(|x| &x.seq_parameter_set_data)
+                        })
+                        .ok_or(BitStreamError::MissingValue)?;
+                    list_count += if seq_parameter_set.chroma_format_idc() != 3 { 2 } else { 6 };
                 }
 
-                if transform_8x8_mode_flag {
-                    let seq_parameter_set = decoder.find_sequence_parameter_set(seq_parameter_set_id).unwrap();
-                    for _ in 0..(if seq_parameter_set.chroma_format_idc != Some(UnsignedExpGolombCode(3)) { 2 } else { 6 }){
-                        pub scaling_list_8x8: ScalingList[64];
+                for i in 0..list_count {
+                    pub pic_scaling_list_present_flag: bool;
+
+                    if pic_scaling_list_present_flag {
+                        if i < 6 {
+                            /// Indexed like the present flags, `None` after list 5.
+                            pub scaling_list_4x4: ScalingList[16];
+                        } else {
+                            /// Indexed like the present flags, `None` before list 6.
+                            pub scaling_list_8x8: ScalingList[64];
+                        }
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::PictureParameterSet;
+    use crate::{nal_unit::ScalingList,
+                test::{test_decoder, test_sequence_parameter_set, PPS_DATA}};
+
+    #[test]
+    fn scaling_lists() {
+        let decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps: PictureParameterSet = BitStream::new(&PPS_DATA).read(&decoder).unwrap();
+        // delta_scale -8
+        let list: ScalingList = BitStream::new(&[0b0000_1000, 0b1000_0000])
+            .read(16)
+            .unwrap();
+        pps.transform_8x8_mode_flag = Some(false);
+        pps.pic_scaling_matrix_present_flag = Some(true);
+        pps.pic_scaling_list_present_flag = Some(vec![false, true, false, false, false, false]);
+        pps.scaling_list_4x4 = Some(vec![None, Some(list), None, None, None, None]);
+        pps.scaling_list_8x8 = Some(vec![None; 6]);
+
+        let mut writer = BitWriter::new();
+        writer.write(&pps, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        let data = writer.into_inner();
+
+        let pps: PictureParameterSet = BitStream::new(&data).read(&decoder).unwrap();
+        let flags = pps.pic_scaling_list_present_flag.unwrap();
+        assert_eq!(flags, [false, true, false, false, false, false]);
+        let lists = pps.scaling_list_4x4.unwrap();
+        assert!(lists[1].as_ref().unwrap().use_default_scaling_matrix_flag);
+        assert_eq!(lists.iter().filter(|x| x.is_some()).count(), 1);
+        assert!(pps.scaling_list_8x8.unwrap().iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,102 @@
+use bit_stream::{cond_bit_field, BitStreamError};
+use serde::Serialize;
+
+use crate::nal_unit::{ExtensionData, NalUnitHeader, UnsignedExpGolombCode};
+
+cond_bit_field! {
+    /// prefix_nal_unit_rbsp
+    ///
+    /// Precedes a NAL unit of type 1 or 5 and carries its SVC or MVC NAL unit header extension,
+    /// which is in the prefix NAL unit's header. For MVC the RBSP has no content.
+    ///
+    /// § 7.3.2.12 Prefix NAL unit RBSP syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(header: &NalUnitHeader)]
+    pub struct PrefixNalUnit {
+        if header.svc_extension_flag {
+            pub prefix_nal_unit_svc: PrefixNalUnitSvc[header];
+        }
+    }
+}
+
+cond_bit_field! {
+    /// prefix_nal_unit_svc
+    ///
+    /// § G.7.3.2.12.1 Prefix NAL unit SVC syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(header: &NalUnitHeader)]
+    pub struct PrefixNalUnitSvc {
+        let svc_extension = header.svc_extension.ok_or(BitStreamError::MissingValue)?;
+
+        if header.ref_idc != 0 {
+            /// equal to 1 specifies that, when the value of dependency_id is equal to the maximum
+            /// value of dependency_id in the access unit, the reference base picture is marked
+            /// as "used for reference".
+            ///
+            /// § G.7.4.2.12.1 Prefix NAL unit SVC semantics
+            pub store_ref_base_pic_flag: bool;
+
+            if (svc_extension.use_ref_base_pic_flag || store_ref_base_pic_flag) &&
+                !svc_extension.idr_flag {
+                pub dec_ref_base_pic_marking: DecRefBasePicMarking;
+            }
+
+            pub additional_prefix_nal_unit_extension_flag: bool;
+        }
+
+        // When nal_ref_idc is equal to 0, the extension data follows without a flag
+        if header.ref_idc == 0 || additional_prefix_nal_unit_extension_flag == Some(true) {
+            pub additional_prefix_nal_unit_extension_data: ExtensionData;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// dec_ref_base_pic_marking
+    ///
+    /// § G.7.3.3.5 Decoded reference base picture marking syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct DecRefBasePicMarking {
+        /// selects the reference base picture marking mode of the current base picture.
+        ///
+        /// § G.7.4.3.5 Decoded reference base picture marking semantics
+        pub adaptive_ref_base_pic_marking_mode_flag: bool;
+
+        if adaptive_ref_base_pic_marking_mode_flag {
+            loop {
+                /// Memory management base control operations, in bitstream order.
+                ///
+                /// The last one always has `memory_management_base_control_operation` equal to 0.
+                pub memory_management_base_control_operations: MemoryManagementBaseControlOperation;
+                if memory_management_base_control_operations.memory_management_base_control_operation == 0 {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `memory_management_base_control_operation` loop
+    ///
+    /// § G.7.3.3.5 Decoded reference base picture marking syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MemoryManagementBaseControlOperation {
+        /// | memory_management_base_control_operation | Memory Management Base Control Operation                          |
+        /// |------------------------------------------|-------------------------------------------------------------------|
+        /// | 0                                        | End memory_management_base_control_operation syntax element loop  |
+        /// | 1                                        | Mark a short-term reference base picture as "unused for reference" |
+        /// | 2                                        | Mark a long-term reference base picture as "unused for reference"  |
+        ///
+        /// Table G-8 – Memory management base control operation (memory_management_base_control_operation) values
+        pub memory_management_base_control_operation: UnsignedExpGolombCode;
+
+        if memory_management_base_control_operation == 1 {
+            pub difference_of_base_pic_nums_minus1: UnsignedExpGolombCode;
+        }
+
+        if memory_management_base_control_operation == 2 {
+            pub long_term_base_pic_num: UnsignedExpGolombCode;
+        }
+    }
+}
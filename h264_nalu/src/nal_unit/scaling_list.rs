@@ -1,11 +1,14 @@
 use crate::nal_unit::SignedExpGolombCode;
-use bit_stream::{BitField, BitStream, Result};
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
 use serde::Serialize;
 
+/// § 7.3.2.1.1.1 Scaling list syntax
 #[derive(Clone, Debug, Serialize)]
 pub struct ScalingList {
     pub list: Vec<u8>,
     pub use_default_scaling_matrix_flag: bool,
+    /// The coded values, written back as is, since several can code the same `list`.
+    pub delta_scale: Vec<SignedExpGolombCode>,
 }
 
 impl<'a> BitField<'a> for ScalingList {
@@ -13,52 +16,111 @@ impl<'a> BitField<'a> for ScalingList {
 
     fn read(stream: &mut BitStream, size: u8) -> Result<Self> {
         let mut list: Vec<u8> = Vec::with_capacity(size as usize);
+        let mut use_default_scaling_matrix_flag = false;
+        let mut delta_scales = Vec::new();
 
-        let mut last_scale = {
-            let delta_scale: SignedExpGolombCode = stream.read(())?;
-            ((8 + delta_scale + 256) % 256) as u8
-        };
+        let mut last_scale = 8i64;
+        let mut next_scale = 8i64;
+        for j in 0..size {
+            if next_scale != 0 {
+                let delta_scale: SignedExpGolombCode = stream.read(())?;
+                next_scale = (last_scale + delta_scale.0).rem_euclid(256);
+                delta_scales.push(delta_scale);
+                use_default_scaling_matrix_flag = j == 0 && next_scale == 0;
+            }
 
-        if last_scale == 0 {
-            for _ in 0..size {
-                list.push(8);
+            if next_scale != 0 {
+                last_scale = next_scale;
             }
-            return Ok(Self {
-                list,
-                use_default_scaling_matrix_flag: true,
-            });
+            list.push(last_scale as u8);
         }
 
-        let mut j = 1u8;
-        loop {
-            let delta_scale: SignedExpGolombCode = stream.read(())?;
-            let next_scale: u8 = ((last_scale as i64 + delta_scale.0 + 256) % 256) as u8;
+        Ok(Self {
+            list,
+            use_default_scaling_matrix_flag,
+            delta_scale: delta_scales,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for ScalingList {
+    type Args = u8;
 
-            j += 1;
+    fn write(&self, writer: &mut BitWriter, size: u8) -> Result<()> {
+        let mut delta_scales = self.delta_scale.iter();
 
+        let mut last_scale = 8i64;
+        let mut next_scale = 8i64;
+        for _ in 0..size {
             if next_scale != 0 {
-                last_scale = next_scale;
-                list.push(last_scale);
-            } else {
-                list.push(last_scale);
-                break;
+                let delta_scale = delta_scales.next().ok_or(BitStreamError::MissingValue)?;
+                writer.write(delta_scale, ())?;
+                next_scale = (last_scale + delta_scale.0).rem_euclid(256);
             }
 
-            if j == size {
-                return Ok(Self {
-                    list,
-                    use_default_scaling_matrix_flag: false,
-                });
+            if next_scale != 0 {
+                last_scale = next_scale;
             }
         }
 
-        while j < size {
-            list.push(last_scale);
+        // Values after `nextScale` is 0 wouldn't be read
+        if delta_scales.next().is_some() {
+            return Err(BitStreamError::InvalidValue);
         }
 
-        Ok(Self {
-            list,
-            use_default_scaling_matrix_flag: false,
-        })
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use super::ScalingList;
+    use crate::nal_unit::SignedExpGolombCode;
+
+    #[test]
+    fn read() {
+        // delta_scale 0, 2 and -10, nextScale 0 repeats the last scale
+        let data = [0b1001_0000, 0b0010_1010];
+        let list: ScalingList = BitStream::new(&data).read(16).unwrap();
+        assert!(!list.use_default_scaling_matrix_flag);
+        let mut expected = vec![10; 16];
+        expected[0] = 8;
+        assert_eq!(list.list, expected);
+
+        // delta_scale -8
+        let data = [0b0000_1000, 0b1000_0000];
+        let list: ScalingList = BitStream::new(&data).read(16).unwrap();
+        assert!(list.use_default_scaling_matrix_flag);
+        assert_eq!(list.list, vec![8; 16]);
+    }
+
+    #[test]
+    fn write() {
+        for data in [
+            // delta_scale 0, 2 and -10
+            &[0b1001_0000, 0b0010_1010][..],
+            // delta_scale -8
+            &[0b0000_1000, 0b1000_0000],
+            // 16 times delta_scale 0, the same list as -8 but coded explicitly
+            &[0b1111_1111, 0b1111_1111],
+        ] {
+            let list: ScalingList = BitStream::new(data).read(16).unwrap();
+            let mut writer = BitWriter::new();
+            writer.write(&list, 16).unwrap();
+            assert_eq!(&*writer.into_inner(), data);
+        }
+
+        let mut list: ScalingList = BitStream::new(&[0b1001_0000, 0b0010_1010])
+            .read(16)
+            .unwrap();
+        list.delta_scale.push(SignedExpGolombCode(1));
+        let result = BitWriter::new().write(&list, 16);
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+
+        list.delta_scale.truncate(2);
+        let result = BitWriter::new().write(&list, 16);
+        assert!(matches!(result, Err(BitStreamError::MissingValue)));
     }
 }
@@ -0,0 +1,321 @@
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
+use serde::Serialize;
+
+mod buffering_period;
+mod pic_timing;
+mod recovery_point;
+mod user_data_registered;
+mod user_data_unregistered;
+
+pub use buffering_period::*;
+pub use pic_timing::*;
+pub use recovery_point::*;
+pub use user_data_registered::*;
+pub use user_data_unregistered::*;
+
+use crate::{nal_unit::{SequenceParameterSet, UnsignedExpGolombCode},
+            Decoder};
+
+/// sei_rbsp
+///
+/// § 7.3.2.3 Supplemental enhancement information RBSP syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct SupplementalEnhancementInformation {
+    pub sei_messages: Vec<SeiMessage>,
+}
+
+impl<'a> BitField<'a> for SupplementalEnhancementInformation {
+    type Args = &'a Decoder;
+
+    fn read(stream: &mut BitStream, decoder: &'a Decoder) -> Result<Self> {
+        // A buffering period message activates its sequence parameter set,
+        // following messages in the same access unit should use it.
+        let mut seq_parameter_set = decoder.active_sequence_parameter_set();
+
+        let mut sei_messages = Vec::new();
+        loop {
+            let sei_message: SeiMessage = stream.read((decoder, seq_parameter_set))?;
+            if let SeiPayload::BufferingPeriod(buffering_period) = &sei_message.payload {
+                seq_parameter_set =
+                    decoder.find_sequence_parameter_set(buffering_period.seq_parameter_set_id);
+            }
+            sei_messages.push(sei_message);
+
+            if !stream.more_rbsp_data() {
+                break;
+            }
+        }
+
+        Ok(Self { sei_messages })
+    }
+}
+
+impl SupplementalEnhancementInformation {
+    /// Returns caption byte pairs from all ATSC A/53 `cc_data` in this SEI, in transmission order.
+    pub fn captions(&self) -> Result<Vec<Caption>> {
+        let mut captions = Vec::new();
+        for sei_message in &self.sei_messages {
+            if let SeiPayload::UserDataRegisteredItuTT35(user_data) = &sei_message.payload {
+                if let Some(cc_data) = user_data.cc_data()? {
+                    captions.extend(cc_data.captions());
+                }
+            }
+        }
+        Ok(captions)
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for SupplementalEnhancementInformation {
+    type Args = &'a Decoder;
+
+    fn write(&self, writer: &mut BitWriter, decoder: &'a Decoder) -> Result<()> {
+        let mut seq_parameter_set = decoder.active_sequence_parameter_set();
+
+        for sei_message in &self.sei_messages {
+            writer.write(sei_message, (decoder, seq_parameter_set))?;
+            if let SeiPayload::BufferingPeriod(buffering_period) = &sei_message.payload {
+                seq_parameter_set =
+                    decoder.find_sequence_parameter_set(buffering_period.seq_parameter_set_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// sei_message
+///
+/// § 7.3.2.3.1 Supplemental enhancement information message syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct SeiMessage {
+    /// | payloadType | sei_payload                    |
+    /// |-------------|--------------------------------|
+    /// | 0           | buffering_period               |
+    /// | 1           | pic_timing                     |
+    /// | 4           | user_data_registered_itu_t_t35 |
+    /// | 5           | user_data_unregistered         |
+    /// | 6           | recovery_point                 |
+    ///
+    /// § D.1.1 General SEI message syntax
+    pub payload_type: u64,
+    pub payload: SeiPayload,
+}
+
+/// Reads a value coded as `ff_byte`s followed by a last byte,
+/// used by `payloadType` and `payloadSize`.
+fn read_ff_coded(stream: &mut BitStream) -> Result<u64> {
+    let mut value = 0;
+    loop {
+        let byte: u8 = stream.read(8)?;
+        value += byte as u64;
+        if byte != 0xff {
+            return Ok(value);
+        }
+    }
+}
+
+fn write_ff_coded(writer: &mut BitWriter, mut value: u64) -> Result<()> {
+    while value >= 0xff {
+        writer.write(&0xffu8, 8)?;
+        value -= 0xff;
+    }
+    writer.write(&value, 8)
+}
+
+impl<'a> BitField<'a> for SeiMessage {
+    /// The `Decoder` and the active `SequenceParameterSet`
+    type Args = (&'a Decoder, Option<&'a SequenceParameterSet>);
+
+    fn read(stream: &mut BitStream, (decoder, seq_parameter_set): Self::Args) -> Result<Self> {
+        let payload_type = read_ff_coded(stream)?;
+        let payload_size = read_ff_coded(stream)?;
+
+        // Typed payloads are read from their own stream,
+        // so unknown trailing bits (for example `reserved_sei_message_payload_extension`)
+        // won't break following messages
+        let data = stream.read_bytes(payload_size as usize)?;
+        let mut payload_stream = BitStream::new(&data);
+
+        let payload = match payload_type {
+            0 => {
+                let seq_parameter_set_id: UnsignedExpGolombCode = BitStream::new(&data).read(())?;
+                match decoder.find_sequence_parameter_set(seq_parameter_set_id) {
+                    Some(seq_parameter_set) => payload_stream
+                        .read(seq_parameter_set)
+                        .map(SeiPayload::BufferingPeriod)?,
+                    None => SeiPayload::Unknown(data),
+                }
+            }
+            1 => match seq_parameter_set {
+                Some(seq_parameter_set) => payload_stream
+                    .read(seq_parameter_set)
+                    .map(SeiPayload::PicTiming)?,
+                None => SeiPayload::Unknown(data),
+            },
+            4 => payload_stream
+                .read(payload_size)
+                .map(SeiPayload::UserDataRegisteredItuTT35)?,
+            5 => payload_stream
+                .read(payload_size)
+                .map(SeiPayload::UserDataUnregistered)?,
+            6 => payload_stream.read(()).map(SeiPayload::RecoveryPoint)?,
+            _ => SeiPayload::Unknown(data),
+        };
+
+        Ok(Self {
+            payload_type,
+            payload,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for SeiMessage {
+    type Args = (&'a Decoder, Option<&'a SequenceParameterSet>);
+
+    fn write(
+        &self,
+        writer: &mut BitWriter,
+        (decoder, seq_parameter_set): Self::Args,
+    ) -> Result<()> {
+        let mut payload_writer = BitWriter::new();
+        match &self.payload {
+            SeiPayload::BufferingPeriod(value) => {
+                let seq_parameter_set = decoder
+                    .find_sequence_parameter_set(value.seq_parameter_set_id)
+                    .ok_or(BitStreamError::MissingValue)?;
+                payload_writer.write(value, seq_parameter_set)?;
+            }
+            SeiPayload::PicTiming(value) => {
+                let seq_parameter_set = seq_parameter_set.ok_or(BitStreamError::MissingValue)?;
+                payload_writer.write(value, seq_parameter_set)?;
+            }
+            SeiPayload::UserDataRegisteredItuTT35(value) => {
+                let payload_size = 1
+                    + value.itu_t_t35_country_code_extension_byte.is_some() as u64
+                    + value.itu_t_t35_payload_byte.len() as u64;
+                payload_writer.write(value, payload_size)?;
+            }
+            SeiPayload::UserDataUnregistered(value) => {
+                let payload_size = 16 + value.user_data_payload_byte.len() as u64;
+                payload_writer.write(value, payload_size)?;
+            }
+            SeiPayload::RecoveryPoint(value) => payload_writer.write(value, ())?,
+            SeiPayload::Unknown(data) => {
+                for byte in data.iter() {
+                    payload_writer.write(byte, 8)?;
+                }
+            }
+        }
+
+        // § D.1.1 General SEI message syntax
+        if !payload_writer.byte_aligned() {
+            // payload_bit_equal_to_one
+            payload_writer.write_bit(true)?;
+            // payload_bit_equal_to_zero
+            payload_writer.byte_align()?;
+        }
+
+        let data = payload_writer.into_inner();
+        write_ff_coded(writer, self.payload_type)?;
+        write_ff_coded(writer, data.len() as u64)?;
+        for byte in data.iter() {
+            writer.write(byte, 8)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// sei_payload
+///
+/// § D.1.1 General SEI message syntax
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub enum SeiPayload {
+    BufferingPeriod(BufferingPeriod),
+    PicTiming(PicTiming),
+    UserDataRegisteredItuTT35(UserDataRegisteredItuTT35),
+    UserDataUnregistered(UserDataUnregistered),
+    RecoveryPoint(RecoveryPoint),
+    /// Unsupported payload types, or payloads whose sequence parameter set is unknown
+    Unknown(Box<[u8]>),
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::{SeiPayload, SupplementalEnhancementInformation};
+    use crate::{nal_unit::{Caption, CcType},
+                Decoder};
+
+    #[test]
+    fn sei_round_trip() {
+        // recovery_point, recovery_frame_cnt 0, exact_match_flag 1
+        let mut data = vec![6, 1, 0b1100_0100];
+        // user_data_unregistered, 16 bytes of UUID then one byte
+        data.extend([5, 17]);
+        data.extend(1..=17);
+        // rbsp_trailing_bits
+        data.push(0x80);
+
+        let decoder = Decoder::new();
+        let sei: SupplementalEnhancementInformation = BitStream::new(&data).read(&decoder).unwrap();
+        assert_eq!(sei.sei_messages.len(), 2);
+        match &sei.sei_messages[0].payload {
+            SeiPayload::RecoveryPoint(recovery_point) => {
+                assert!(recovery_point.exact_match_flag);
+                assert!(!recovery_point.broken_link_flag);
+            }
+            payload => panic!("unexpected payload {:?}", payload),
+        }
+        match &sei.sei_messages[1].payload {
+            SeiPayload::UserDataUnregistered(user_data) => {
+                assert_eq!(
+                    user_data.uuid_iso_iec_11578,
+                    0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10
+                );
+                assert_eq!(user_data.user_data_payload_byte, vec![17]);
+            }
+            payload => panic!("unexpected payload {:?}", payload),
+        }
+
+        let mut writer = BitWriter::new();
+        writer.write(&sei, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+    }
+
+    #[test]
+    fn sei_captions() {
+        #[rustfmt::skip]
+        let data = [
+            // user_data_registered_itu_t_t35, ATSC, "GA94", cc_data
+            4, 17, 0xb5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03,
+            // process_cc_data_flag, cc_count 2, em_data
+            0xc2, 0xff,
+            // field 1 pair, invalid field 2 pair
+            0xfc, 0x94, 0x2c, 0xf9, 0x80, 0x80,
+            // marker_bits, rbsp_trailing_bits
+            0xff, 0x80,
+        ];
+
+        let decoder = Decoder::new();
+        let sei: SupplementalEnhancementInformation = BitStream::new(&data).read(&decoder).unwrap();
+        assert_eq!(
+            sei.captions().unwrap(),
+            vec![Caption {
+                cc_type: CcType::Ntsc608Field1,
+                cc_data_1: 0x94,
+                cc_data_2: 0x2c,
+            }]
+        );
+
+        let mut writer = BitWriter::new();
+        writer.write(&sei, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+    }
+}
@@ -0,0 +1,50 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+use crate::nal_unit::{SequenceParameterSet, UnsignedExpGolombCode};
+
+cond_bit_field! {
+    /// buffering_period
+    ///
+    /// § D.1.2 Buffering period SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(seq_parameter_set: &SequenceParameterSet)]
+    pub struct BufferingPeriod {
+        /// specifies the sequence parameter set that contains the sequence HRD attributes.
+        ///
+        /// § D.2.2 Buffering period SEI message semantics
+        pub seq_parameter_set_id: UnsignedExpGolombCode;
+
+        let yuv_parameters = seq_parameter_set.yuv_parameters.as_ref();
+
+        // NalHrdBpPresentFlag
+        if let Some(hrd_parameters) = yuv_parameters.and_then(|x| x.nal_hrd_parameters.as_ref()) {
+            for _ in 0..=hrd_parameters.cpb_cnt_minus1.0 {
+                /// initial_cpb_removal_delay[ SchedSelIdx ] for the NAL HRD,
+                /// in units of a 90 kHz clock.
+                ///
+                /// § D.2.2 Buffering period SEI message semantics
+                pub nal_initial_cpb_removal_delay: u32[hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1];
+                /// initial_cpb_removal_delay_offset[ SchedSelIdx ] for the NAL HRD
+                ///
+                /// § D.2.2 Buffering period SEI message semantics
+                pub nal_initial_cpb_removal_delay_offset: u32[hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1];
+            }
+        }
+
+        // VclHrdBpPresentFlag
+        if let Some(hrd_parameters) = yuv_parameters.and_then(|x| x.vcl_hrd_parameters.as_ref()) {
+            for _ in 0..=hrd_parameters.cpb_cnt_minus1.0 {
+                /// initial_cpb_removal_delay[ SchedSelIdx ] for the VCL HRD,
+                /// in units of a 90 kHz clock.
+                ///
+                /// § D.2.2 Buffering period SEI message semantics
+                pub vcl_initial_cpb_removal_delay: u32[hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1];
+                /// initial_cpb_removal_delay_offset[ SchedSelIdx ] for the VCL HRD
+                ///
+                /// § D.2.2 Buffering period SEI message semantics
+                pub vcl_initial_cpb_removal_delay_offset: u32[hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1];
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+use crate::nal_unit::SequenceParameterSet;
+
+cond_bit_field! {
+    /// pic_timing
+    ///
+    /// § D.1.3 Picture timing SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(seq_parameter_set: &SequenceParameterSet)]
+    pub struct PicTiming {
+        let yuv_parameters = seq_parameter_set.yuv_parameters.as_ref();
+        let hrd_parameters = yuv_parameters
+            .and_then(|x| x.nal_hrd_parameters.as_ref().or(x.vcl_hrd_parameters.as_ref()));
+
+        // CpbDpbDelaysPresentFlag
+        if let Some(hrd_parameters) = hrd_parameters {
+            /// specifies how many clock ticks to wait after removal from the CPB of the
+            /// access unit associated with the most recent buffering period SEI message
+            /// before removing from the buffer the access unit data associated with the
+            /// picture timing SEI message.
+            ///
+            /// § D.2.3 Picture timing SEI message semantics
+            pub cpb_removal_delay: u32[hrd_parameters.cpb_removal_delay_length_minus1 + 1];
+            /// is used to compute the DPB output time of the picture.
+            ///
+            /// § D.2.3 Picture timing SEI message semantics
+            pub dpb_output_delay: u32[hrd_parameters.dpb_output_delay_length_minus1 + 1];
+        }
+
+        if yuv_parameters.is_some_and(|x| x.pic_struct_present_flag) {
+            /// indicates whether a picture should be displayed as a frame or one or more fields.
+            ///
+            /// | pic_struct | Indicated display of picture                                     | NumClockTS |
+            /// |------------|------------------------------------------------------------------|------------|
+            /// | 0          | (progressive) frame                                              | 1          |
+            /// | 1          | top field                                                        | 1          |
+            /// | 2          | bottom field                                                     | 1          |
+            /// | 3          | top field, bottom field, in that order                           | 2          |
+            /// | 4          | bottom field, top field, in that order                           | 2          |
+            /// | 5          | top field, bottom field, top field repeated, in that order       | 3          |
+            /// | 6          | bottom field, top field, bottom field repeated, in that order    | 3          |
+            /// | 7          | frame doubling                                                   | 2          |
+            /// | 8          | frame tripling                                                   | 3          |
+            /// | 9..15      | reserved                                                         |            |
+            ///
+            /// Table D-1 – Interpretation of pic_struct
+            ///
+            /// § D.2.3 Picture timing SEI message semantics
+            pub pic_struct: u4;
+
+            #[allow(non_snake_case)]
+            let NumClockTS = match pic_struct {
+                0..=2 => 1,
+                3 | 4 | 7 => 2,
+                5 | 6 | 8 => 3,
+                _ => 0,
+            };
+
+            // When time_offset_length is not present, it shall be inferred to be equal to 24
+            //
+            // § E.2.2 HRD parameters semantics
+            let time_offset_length = hrd_parameters.map_or(24, |x| x.time_offset_length);
+
+            for _ in 0..NumClockTS {
+                pub clock_timestamps: ClockTimestamp[time_offset_length];
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `NumClockTS` loop in `pic_timing`
+    ///
+    /// § D.1.3 Picture timing SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(time_offset_length: u8)]
+    pub struct ClockTimestamp {
+        /// equal to 1 indicates that a number of clock timestamp syntax elements are present
+        /// and follow immediately.
+        ///
+        /// § D.2.3 Picture timing SEI message semantics
+        pub clock_timestamp_flag: bool;
+
+        if clock_timestamp_flag {
+            /// indicates the scan type (interlaced or progressive) of the source material.
+            ///
+            /// | ct_type | Original picture scan |
+            /// |---------|-----------------------|
+            /// | 0       | progressive           |
+            /// | 1       | interlaced            |
+            /// | 2       | unknown               |
+            /// | 3       | reserved              |
+            ///
+            /// Table D-2 – Mapping of ct_type to source picture scan
+            ///
+            /// § D.2.3 Picture timing SEI message semantics
+            pub ct_type: u2;
+            pub nuit_field_based_flag: bool;
+            /// specifies the method of dropping values of the n_frames as specified in Table D-3.
+            ///
+            /// § D.2.3 Picture timing SEI message semantics
+            pub counting_type: u5;
+            pub full_timestamp_flag: bool;
+            pub discontinuity_flag: bool;
+            pub cnt_dropped_flag: bool;
+            pub n_frames: u8;
+
+            // When `full_timestamp_flag` is 1, all values are present without flags
+            if !full_timestamp_flag {
+                pub seconds_flag: bool;
+            }
+
+            if full_timestamp_flag || seconds_flag == Some(true) {
+                pub seconds_value: u6;
+
+                if !full_timestamp_flag {
+                    pub minutes_flag: bool;
+                }
+
+                if full_timestamp_flag || minutes_flag == Some(true) {
+                    pub minutes_value: u6;
+
+                    if !full_timestamp_flag {
+                        pub hours_flag: bool;
+                    }
+
+                    if full_timestamp_flag || hours_flag == Some(true) {
+                        pub hours_value: u5;
+                    }
+                }
+            }
+
+            if time_offset_length > 0 {
+                pub time_offset: i32[time_offset_length];
+            }
+        }
+    }
+}
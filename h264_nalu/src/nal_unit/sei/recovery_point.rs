@@ -0,0 +1,31 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+use crate::nal_unit::UnsignedExpGolombCode;
+
+cond_bit_field! {
+    /// recovery_point
+    ///
+    /// § D.1.8 Recovery point SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct RecoveryPoint {
+        /// specifies the recovery point of output pictures in output order.
+        ///
+        /// § D.2.8 Recovery point SEI message semantics
+        pub recovery_frame_cnt: UnsignedExpGolombCode;
+        /// indicates whether decoded pictures at and subsequent to the specified recovery point
+        /// in output order derived by starting the decoding process at the access unit
+        /// associated with the recovery point SEI message shall be an exact match to the
+        /// pictures that would be produced by starting the decoding process at the location
+        /// of a previous IDR access unit in the NAL unit stream.
+        ///
+        /// § D.2.8 Recovery point SEI message semantics
+        pub exact_match_flag: bool;
+        /// indicates the presence or absence of a broken link in the NAL unit stream at the
+        /// location of the recovery point SEI message.
+        ///
+        /// § D.2.8 Recovery point SEI message semantics
+        pub broken_link_flag: bool;
+        pub changing_slice_group_idc: u2;
+    }
+}
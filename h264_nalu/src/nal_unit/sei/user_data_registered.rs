@@ -0,0 +1,144 @@
+use bit_stream::{cond_bit_field, BitStream, Result};
+use serde::Serialize;
+
+/// United States
+const ITU_T_T35_COUNTRY_CODE_US: u8 = 0xB5;
+/// ATSC
+const ITU_T_T35_PROVIDER_CODE_ATSC: [u8; 2] = [0x00, 0x31];
+/// ATSC1_data
+const ATSC_USER_IDENTIFIER: &[u8; 4] = b"GA94";
+/// cc_data
+const ATSC_USER_DATA_TYPE_CODE_CC_DATA: u8 = 0x03;
+
+cond_bit_field! {
+    /// user_data_registered_itu_t_t35
+    ///
+    /// § D.1.6 User data registered by Rec. ITU-T T.35 SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(payload_size: u64)]
+    pub struct UserDataRegisteredItuTT35 {
+        /// a byte having a value specified as a country code by Rec. ITU-T T.35 Annex A.
+        ///
+        /// § D.2.6 User data registered by Rec. ITU-T T.35 SEI message semantics
+        pub itu_t_t35_country_code: u8;
+
+        let mut i = 1;
+        if itu_t_t35_country_code == 0xFF {
+            pub itu_t_t35_country_code_extension_byte: u8;
+            i = 2;
+        }
+
+        for _ in i..payload_size {
+            /// a byte containing data registered as specified by Rec. ITU-T T.35.
+            ///
+            /// § D.2.6 User data registered by Rec. ITU-T T.35 SEI message semantics
+            pub itu_t_t35_payload_byte: u8;
+        }
+    }
+}
+
+impl UserDataRegisteredItuTT35 {
+    /// Parses the payload as ATSC A/53 `cc_data`.
+    ///
+    /// Returns `None` if the payload doesn't carry `cc_data`.
+    pub fn cc_data(&self) -> Result<Option<CcData>> {
+        if self.itu_t_t35_country_code != ITU_T_T35_COUNTRY_CODE_US {
+            return Ok(None);
+        }
+
+        // itu_t_t35_provider_code, user_identifier, user_data_type_code
+        let data = &self.itu_t_t35_payload_byte;
+        if data.len() < 7
+            || data[0..2] != ITU_T_T35_PROVIDER_CODE_ATSC
+            || &data[2..6] != ATSC_USER_IDENTIFIER
+            || data[6] != ATSC_USER_DATA_TYPE_CODE_CC_DATA
+        {
+            return Ok(None);
+        }
+
+        BitStream::new(&data[7..]).read(()).map(Some)
+    }
+}
+
+cond_bit_field! {
+    /// cc_data
+    ///
+    /// ATSC A/53 Part 4, Table 6.9 Closed Captioning Data Syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct CcData {
+        pub process_em_data_flag: bool;
+        /// indicates whether the `cc_data_pkts` should be processed.
+        pub process_cc_data_flag: bool;
+        pub additional_data_flag: bool;
+        pub cc_count: u5;
+        pub em_data: u8;
+
+        for _ in 0..cc_count {
+            pub cc_data_pkts: CcDataPkt;
+        }
+
+        pub marker_bits: u8;
+    }
+}
+
+impl CcData {
+    /// Returns valid caption byte pairs, in transmission order.
+    pub fn captions(&self) -> impl Iterator<Item = Caption> + '_ {
+        self.cc_data_pkts
+            .iter()
+            .filter(move |x| self.process_cc_data_flag && x.cc_valid)
+            .map(|x| Caption {
+                cc_type: match x.cc_type {
+                    0 => CcType::Ntsc608Field1,
+                    1 => CcType::Ntsc608Field2,
+                    2 => CcType::DtvccPacketData,
+                    _ => CcType::DtvccPacketStart,
+                },
+                cc_data_1: x.cc_data_1,
+                cc_data_2: x.cc_data_2,
+            })
+    }
+}
+
+cond_bit_field! {
+    /// One construct in the `cc_count` loop of `cc_data`
+    ///
+    /// ATSC A/53 Part 4, Table 6.9 Closed Captioning Data Syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct CcDataPkt {
+        pub marker_bits: u5;
+        pub cc_valid: bool;
+        /// | cc_type | Meaning                     |
+        /// |---------|-----------------------------|
+        /// | 0       | NTSC line 21 field 1 (CEA-608) |
+        /// | 1       | NTSC line 21 field 2 (CEA-608) |
+        /// | 2       | DTVCC packet data (CEA-708)    |
+        /// | 3       | DTVCC packet start (CEA-708)   |
+        pub cc_type: u2;
+        pub cc_data_1: u8;
+        pub cc_data_2: u8;
+    }
+}
+
+/// Caption channel of a `Caption`, from `cc_type`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum CcType {
+    /// CEA-608 data for field 1 (CC1, CC2, T1, T2)
+    Ntsc608Field1,
+    /// CEA-608 data for field 2 (CC3, CC4, T3, T4)
+    Ntsc608Field2,
+    /// CEA-708 data continuing a DTVCC packet
+    DtvccPacketData,
+    /// CEA-708 data starting a new DTVCC packet
+    DtvccPacketStart,
+}
+
+/// A caption byte pair carried in `cc_data`
+///
+/// CEA-608 bytes still contain their odd parity bit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct Caption {
+    pub cc_type: CcType,
+    pub cc_data_1: u8,
+    pub cc_data_2: u8,
+}
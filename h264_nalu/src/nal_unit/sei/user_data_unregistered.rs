@@ -0,0 +1,24 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+cond_bit_field! {
+    /// user_data_unregistered
+    ///
+    /// § D.1.7 User data unregistered SEI message syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(payload_size: u64)]
+    pub struct UserDataUnregistered {
+        /// a value specified as a UUID according to the procedures of ISO/IEC 11578:1996 Annex A.
+        ///
+        /// § D.2.7 User data unregistered SEI message semantics
+        pub uuid_iso_iec_11578: u128;
+
+        for _ in 16..payload_size {
+            /// a byte containing data having syntax and semantics as specified by
+            /// the UUID generator.
+            ///
+            /// § D.2.7 User data unregistered SEI message semantics
+            pub user_data_payload_byte: u8;
+        }
+    }
+}
@@ -1,7 +1,20 @@
-use crate::nal_unit::{ScalingList, SignedExpGolombCode, UnsignedExpGolombCode};
-use bit_stream::cond_bit_field;
+use crate::{nal_unit::{ScalingList, SignedExpGolombCode, UnsignedExpGolombCode},
+            Level, Profile};
+use bit_stream::{cond_bit_field, BitStreamError};
 use serde::Serialize;
 
+/// Returns `InvalidValue` if log2_max_frame_num_minus4 or log2_max_pic_order_cnt_lsb_minus4 is
+/// not in range 0 to 12
+///
+/// § 7.4.2.1.1 Sequence parameter set data semantics
+fn check_log2_max_minus4(value: UnsignedExpGolombCode) -> Result<(), BitStreamError> {
+    if value.0 > 12 {
+        Err(BitStreamError::InvalidValue)
+    } else {
+        Ok(())
+    }
+}
+
 cond_bit_field! {
     /// A _syntax structure containing syntax elements_ that apply to zero or more
     /// _layer representations_ with the dependency_id _syntax element_ equal to 0
@@ -61,20 +74,30 @@ cond_bit_field! {
             pub seq_scaling_matrix_present_flag: bool;
 
             if seq_scaling_matrix_present_flag {
-                for _ in 0..6 {
-                    pub scaling_list_4x4: ScalingList[16];
-                }
+                for i in 0..(if chroma_format_idc != 3 { 8 } else { 12 }) {
+                    pub seq_scaling_list_present_flag: bool;
 
-                for _ in 0..(if chroma_format_idc != 3 { 2 } else { 6 }){
-                    pub scaling_list_8x8: ScalingList[64];
+                    if seq_scaling_list_present_flag {
+                        if i < 6 {
+                            /// Indexed like the present flags, `None` after list 5.
+                            pub scaling_list_4x4: ScalingList[16];
+                        } else {
+                            /// Indexed like the present flags, `None` before list 6.
+                            pub scaling_list_8x8: ScalingList[64];
+                        }
+                    }
                 }
             }
         }
 
         pub log2_max_frame_num_minus4: UnsignedExpGolombCode;
+        check_log2_max_minus4(log2_max_frame_num_minus4)?;
         pub pic_order_cnt_type: UnsignedExpGolombCode;
         match pic_order_cnt_type.0 {
-            0 => pub log2_max_pic_order_cnt_lsb_minus4: UnsignedExpGolombCode;
+            0 => {
+                pub log2_max_pic_order_cnt_lsb_minus4: UnsignedExpGolombCode;
+                check_log2_max_minus4(log2_max_pic_order_cnt_lsb_minus4)?;
+            }
             1 => {
                 pub delta_pic_order_always_zero_flag: bool;
                 pub offset_for_non_ref_pic: SignedExpGolombCode;
@@ -205,7 +228,7 @@ cond_bit_field! {
         pub bit_rate_scale: u4;
         pub cpb_size_scale: u4;
 
-        for _ in 0..cpb_cnt_minus1.0 {
+        for _ in 0..=cpb_cnt_minus1.0 {
             pub bit_rate_value_minus1: UnsignedExpGolombCode;
             pub cpb_size_value_minus1: UnsignedExpGolombCode;
             pub cbr_flag: bool;
@@ -217,3 +240,172 @@ cond_bit_field! {
         pub time_offset_length: u5;
     }
 }
+
+/// Variables derived from the syntax elements, with the inferred values of the syntax elements
+/// which are not present
+///
+/// § 7.4.2.1.1 Sequence parameter set data semantics
+impl SequenceParameterSet {
+    /// § A.2 Profiles
+    pub fn profile(&self) -> Profile {
+        Profile::new(self)
+    }
+
+    /// `None` for unknown level_idc values
+    ///
+    /// § A.3 Levels
+    pub fn level(&self) -> Option<Level> {
+        Level::new(self)
+    }
+
+    /// chroma_format_idc, inferred to be equal to 1 (4:2:0 chroma format) when not present
+    pub fn chroma_format_idc(&self) -> u64 {
+        self.chroma_format_idc.map_or(1, |x| x.0)
+    }
+
+    /// ChromaArrayType, 0 for monochrome pictures and pictures with separately coded colour
+    /// planes, otherwise chroma_format_idc
+    ///
+    /// Auxiliary coded pictures (nal_unit_type equal to 19) are monochrome, their
+    /// ChromaArrayType is 0 regardless of this value.
+    pub fn chroma_array_type(&self) -> u64 {
+        if self.separate_colour_plane_flag {
+            0
+        } else {
+            self.chroma_format_idc()
+        }
+    }
+
+    /// SubWidthC, the horizontal sampling ratio of the chroma arrays, `None` for monochrome
+    /// pictures and pictures with separately coded colour planes
+    ///
+    /// § 6.2 Source, decoded, and output picture formats
+    ///
+    /// | chroma_format_idc | separate_colour_plane_flag | Chroma Format | SubWidthC | SubHeightC |
+    /// |-------------------|----------------------------|---------------|-----------|------------|
+    /// | 0                 | 0                          | monochrome    | -         | -          |
+    /// | 1                 | 0                          | 4:2:0         | 2         | 2          |
+    /// | 2                 | 0                          | 4:2:2         | 2         | 1          |
+    /// | 3                 | 0                          | 4:4:4         | 1         | 1          |
+    /// | 3                 | 1                          | 4:4:4         | -         | -          |
+    ///
+    /// Table 6-1 – SubWidthC, and SubHeightC values derived from chroma_format_idc and
+    /// separate_colour_plane_flag
+    pub fn sub_width_c(&self) -> Option<u64> {
+        match self.chroma_array_type() {
+            1 | 2 => Some(2),
+            3 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// SubHeightC, the vertical sampling ratio of the chroma arrays, `None` for monochrome
+    /// pictures and pictures with separately coded colour planes
+    ///
+    /// See [`Self::sub_width_c`]
+    pub fn sub_height_c(&self) -> Option<u64> {
+        match self.chroma_array_type() {
+            1 => Some(2),
+            2 | 3 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// BitDepthY, the bit depth of the samples of the luma array
+    pub fn bit_depth_luma(&self) -> u64 {
+        8 + self.bit_depth_luma_minus8.map_or(0, |x| x.0)
+    }
+
+    /// BitDepthC, the bit depth of the samples of the chroma arrays
+    pub fn bit_depth_chroma(&self) -> u64 {
+        8 + self.bit_depth_chroma_minus8.map_or(0, |x| x.0)
+    }
+
+    /// MaxFrameNum, frame_num is in range 0 to MaxFrameNum - 1
+    pub fn max_frame_num(&self) -> u64 {
+        1 << (self.log2_max_frame_num_minus4.0 + 4)
+    }
+
+    /// MaxPicOrderCntLsb, only for pic_order_cnt_type equal to 0
+    pub fn max_pic_order_cnt_lsb(&self) -> Option<u64> {
+        self.log2_max_pic_order_cnt_lsb_minus4
+            .map(|x| 1 << (x.0 + 4))
+    }
+
+    /// PicWidthInMbs, the width of a decoded picture in units of macroblocks
+    pub fn pic_width_in_mbs(&self) -> u64 {
+        self.pic_width_in_mbs_minus1.0 + 1
+    }
+
+    /// PicHeightInMapUnits, the height of a decoded frame or field in units of slice group map
+    /// units
+    pub fn pic_height_in_map_units(&self) -> u64 {
+        self.pic_height_in_map_units_minus1.0 + 1
+    }
+
+    /// PicSizeInMapUnits
+    pub fn pic_size_in_map_units(&self) -> u64 {
+        self.pic_width_in_mbs() * self.pic_height_in_map_units()
+    }
+
+    /// FrameHeightInMbs, the height of a decoded frame in units of macroblocks
+    pub fn frame_height_in_mbs(&self) -> u64 {
+        (2 - self.frame_mbs_only_flag as u64) * self.pic_height_in_map_units()
+    }
+
+    /// Width in luma samples of the frames after applying the frame cropping rectangle
+    pub fn cropped_width(&self) -> u64 {
+        // CropUnitX
+        let crop_unit_x = self.sub_width_c().unwrap_or(1);
+        let offsets = self.frame_crop_left_offset.map_or(0, |x| x.0)
+            + self.frame_crop_right_offset.map_or(0, |x| x.0);
+        (self.pic_width_in_mbs() * 16).saturating_sub(crop_unit_x * offsets)
+    }
+
+    /// Height in luma samples of the frames after applying the frame cropping rectangle
+    pub fn cropped_height(&self) -> u64 {
+        // CropUnitY
+        let crop_unit_y = self.sub_height_c().unwrap_or(1) * (2 - self.frame_mbs_only_flag as u64);
+        let offsets = self.frame_crop_top_offset.map_or(0, |x| x.0)
+            + self.frame_crop_bottom_offset.map_or(0, |x| x.0);
+        (self.frame_height_in_mbs() * 16).saturating_sub(crop_unit_y * offsets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use super::SequenceParameterSet;
+    use crate::{nal_unit::UnsignedExpGolombCode,
+                test::{test_sequence_parameter_set, SPS_DATA}};
+
+    #[test]
+    fn log2_max_frame_num_range() {
+        // SPS_DATA with log2_max_frame_num_minus4 13 instead of 0, after profile_idc,
+        // constraint flags, level_idc and seq_parameter_set_id
+        let mut stream = BitStream::new(&SPS_DATA);
+        let mut writer = BitWriter::new();
+        for _ in 0..25 {
+            writer.write_bit(stream.read_bit().unwrap()).unwrap();
+        }
+        let log2_max_frame_num_minus4: UnsignedExpGolombCode = stream.read(()).unwrap();
+        assert_eq!(log2_max_frame_num_minus4.0, 0);
+        writer.write(&UnsignedExpGolombCode(13), ()).unwrap();
+        while let Ok(bit) = stream.read_bit() {
+            writer.write_bit(bit).unwrap();
+        }
+        let data = writer.into_inner();
+        let result: Result<SequenceParameterSet, _> = BitStream::new(&data).read(());
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+
+        let mut sps = test_sequence_parameter_set();
+        sps.log2_max_frame_num_minus4 = UnsignedExpGolombCode(12);
+        assert_eq!(sps.max_frame_num(), 1 << 16);
+        let mut writer = BitWriter::new();
+        writer.write(&sps, ()).unwrap();
+        sps.log2_max_frame_num_minus4 = UnsignedExpGolombCode(13);
+        let result = BitWriter::new().write(&sps, ());
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+}
@@ -0,0 +1,81 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+use crate::nal_unit::UnsignedExpGolombCode;
+
+cond_bit_field! {
+    /// seq_parameter_set_extension_rbsp
+    ///
+    /// § 7.3.2.1.2 Sequence parameter set extension RBSP syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct SequenceParameterSetExtension {
+        /// identifies the sequence parameter set associated with the sequence parameter set extension.
+        ///
+        /// § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+        pub seq_parameter_set_id: UnsignedExpGolombCode;
+        /// | aux_format_idc | Auxiliary coded pictures                                      |
+        /// |----------------|---------------------------------------------------------------|
+        /// | 0              | not present                                                   |
+        /// | 1              | alpha, multiplied with the primary coded picture              |
+        /// | 2              | alpha, not multiplied with the primary coded picture          |
+        /// | 3              | unspecified                                                   |
+        ///
+        /// § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+        pub aux_format_idc: UnsignedExpGolombCode;
+
+        if aux_format_idc != 0 {
+            /// specifies the bit depth of the samples of the sample array of the auxiliary coded picture.
+            ///
+            /// § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+            pub bit_depth_aux_minus8: UnsignedExpGolombCode;
+            pub alpha_incr_flag: bool;
+            /// specifies the interpretation sample value of an auxiliary coded picture luma sample
+            /// for which the associated luma sample of the primary coded picture is considered
+            /// opaque. The number of bits used to represent the alpha_opaque_value syntax element
+            /// is bit_depth_aux_minus8 + 9 bits.
+            ///
+            /// § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+            pub alpha_opaque_value: u16[(bit_depth_aux_minus8.0 + 9) as u8];
+            /// specifies the interpretation sample value of an auxiliary coded picture luma sample
+            /// for which the associated luma sample of the primary coded picture is considered
+            /// transparent. The number of bits used to represent the alpha_transparent_value
+            /// syntax element is bit_depth_aux_minus8 + 9 bits.
+            ///
+            /// § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+            pub alpha_transparent_value: u16[(bit_depth_aux_minus8.0 + 9) as u8];
+        }
+
+        pub additional_extension_flag: bool;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::SequenceParameterSetExtension;
+    use crate::{nal_unit::UnsignedExpGolombCode, Decoder};
+
+    #[test]
+    fn seq_param_set_extension_round_trip() {
+        // aux_format_idc 1, 9-bit alpha, opaque 255, transparent 0
+        let data = [0xa9, 0xfe, 0x00, 0x40];
+
+        let extension: SequenceParameterSetExtension = BitStream::new(&data).read(()).unwrap();
+        assert_eq!(extension.aux_format_idc.0, 1);
+        assert_eq!(extension.alpha_opaque_value, Some(255));
+        assert_eq!(extension.alpha_transparent_value, Some(0));
+
+        let mut writer = BitWriter::new();
+        writer.write(&extension, ()).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        let mut decoder = Decoder::new();
+        decoder.set_sequence_parameter_set_extension(extension);
+        assert!(decoder
+            .find_sequence_parameter_set_extension(UnsignedExpGolombCode(0))
+            .is_some());
+    }
+}
@@ -1,41 +1,170 @@
-use bit_stream::cond_bit_field;
+use bit_stream::{cond_bit_field, BitStreamError};
 use serde::Serialize;
 
-use crate::{nal_unit::{NalUnitHeader, SliceHeader},
+use crate::{nal_unit::{NalUnitHeader, SliceData, SliceHeader, UnparsedData, UnsignedExpGolombCode},
             Decoder};
 
 cond_bit_field! {
-    /// § 7.3.4 Slice data syntax
+    /// § 7.3.2.8 Slice layer without partitioning RBSP syntax
     #[derive(Clone, Debug, Serialize)]
-    #[extra_args(decoder: &Decoder, header: &SliceHeader)]
-    pub struct SliceData {
-        // § 3.148 sequence parameter set
-        let pic_parameter_set = decoder.find_picture_parameter_set(header.pic_parameter_set_id).unwrap();
-        let seq_parameter_set = decoder.find_sequence_parameter_set(pic_parameter_set.seq_parameter_set_id).unwrap();
-
-        if pic_parameter_set.entropy_coding_mode_flag {
-            // TODO
-        }
+    #[extra_args(decoder: &Decoder, header: &NalUnitHeader)]
+    pub struct SliceLayerWithoutPartitioning {
+        pub slice_header: SliceHeader[decoder, header];
+        pub slice_data: SliceData[decoder, header, &slice_header];
+    }
+}
+
+cond_bit_field! {
+    /// slice_data_partition_a_layer_rbsp
+    ///
+    /// § 7.3.2.9.1 Slice data partition A RBSP syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(decoder: &Decoder, header: &NalUnitHeader)]
+    pub struct SliceDataPartitionA {
+        pub slice_header: SliceHeader[decoder, header];
+        /// identifies the slice associated with the data partition.
+        ///
+        /// § 7.4.2.9.1 Slice data partition A RBSP semantics
+        pub slice_id: UnsignedExpGolombCode;
+        /// Only category 2
+        pub slice_data: SliceData[decoder, header, &slice_header];
+    }
+}
 
-        // § 7.4.3 Slice header semantics
-        #[allow(non_snake_case)]
-        let MbaffFrameFlag = seq_parameter_set.mb_adaptive_frame_field_flag && !header.field_pic_flag;
-        // #[allow(non_snake_case)]
-        // let mut CurrMbAddr = header.first_mb_in_slice;
-        if MbaffFrameFlag {
-            // CurrMbAddr *= 2;
+cond_bit_field! {
+    /// slice_data_partition_b_layer_rbsp
+    ///
+    /// Uses the parameter sets of the slice data partition A with the same `slice_id`, see
+    /// [`Decoder::set_slice_data_partition`].
+    ///
+    /// § 7.3.2.9.2 Slice data partition B RBSP syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(decoder: &Decoder)]
+    pub struct SliceDataPartitionB {
+        pub slice_id: UnsignedExpGolombCode;
+        let pic_parameter_set = decoder.find_slice_data_partition_picture_parameter_set(slice_id).ok_or(BitStreamError::MissingValue)?;
+        let seq_parameter_set = decoder.find_sequence_parameter_set(pic_parameter_set.seq_parameter_set_id).ok_or(BitStreamError::MissingValue)?;
+        if seq_parameter_set.separate_colour_plane_flag {
+            pub colour_plane_id: u2;
+        }
+        if pic_parameter_set.redundant_pic_cnt_present_flag {
+            pub redundant_pic_cnt: UnsignedExpGolombCode;
         }
 
-        // TODO
+        /// slice_data( ) of category 3, not parsed
+        pub slice_data: UnparsedData;
     }
 }
 
 cond_bit_field! {
-    /// § 7.3.2.8 Slice layer without partitioning RBSP syntax
+    /// slice_data_partition_c_layer_rbsp
+    ///
+    /// Uses the parameter sets of the slice data partition A with the same `slice_id`, see
+    /// [`Decoder::set_slice_data_partition`].
+    ///
+    /// § 7.3.2.9.3 Slice data partition C RBSP syntax
     #[derive(Clone, Debug, Serialize)]
-    #[extra_args(decoder: &Decoder, header: &NalUnitHeader)]
-    pub struct SliceLayerWithoutPartitioning {
-        pub slice_header: SliceHeader[decoder, header];
-        pub slice_data: SliceData[decoder, &slice_header];
+    #[extra_args(decoder: &Decoder)]
+    pub struct SliceDataPartitionC {
+        pub slice_id: UnsignedExpGolombCode;
+        let pic_parameter_set = decoder.find_slice_data_partition_picture_parameter_set(slice_id).ok_or(BitStreamError::MissingValue)?;
+        let seq_parameter_set = decoder.find_sequence_parameter_set(pic_parameter_set.seq_parameter_set_id).ok_or(BitStreamError::MissingValue)?;
+        if seq_parameter_set.separate_colour_plane_flag {
+            pub colour_plane_id: u2;
+        }
+        if pic_parameter_set.redundant_pic_cnt_present_flag {
+            pub redundant_pic_cnt: UnsignedExpGolombCode;
+        }
+
+        /// slice_data( ) of category 4, not parsed
+        pub slice_data: UnparsedData;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use super::SliceDataPartitionB;
+    use crate::{nal_unit::{PictureParameterSet, SubsetSequenceParameterSet, UnsignedExpGolombCode},
+                test::{test_decoder, test_sequence_parameter_set, MVC_SUBSET_SPS_DATA, PPS_DATA},
+                Decoder, NalUnit, NalUnitPayload};
+
+    #[test]
+    fn slice_extension_mvc() {
+        let mut decoder = Decoder::new();
+        let subset: SubsetSequenceParameterSet =
+            BitStream::new(&MVC_SUBSET_SPS_DATA).read(()).unwrap();
+        decoder.set_subset_sequence_parameter_set(subset);
+        let pps: PictureParameterSet = BitStream::new(&PPS_DATA).read(&decoder).unwrap();
+        decoder.set_picture_parameter_set(pps);
+
+        // P slice of view 1, RefPicList0 moves the inter-view reference to the front,
+        // then all 8160 macroblocks are skipped
+        let data = [
+            0x74, 0x40, 0x00, 0x43, 0x9a, 0x29, 0xa4, 0x50, 0x00, 0x7f, 0x86,
+        ];
+
+        let unit: NalUnit = BitStream::new(&data).read(&decoder).unwrap();
+        let slice = match &unit.payload {
+            NalUnitPayload::SliceExtension(slice) => slice,
+            payload => panic!("unexpected payload {:?}", payload),
+        };
+        let modification = slice
+            .slice_header
+            .ref_pic_list_mvc_modification
+            .as_ref()
+            .unwrap();
+        let operations = modification.ref_pic_list_modification_l0.as_ref().unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].modification_of_pic_nums_idc.0, 5);
+        assert_eq!(
+            operations[0].abs_diff_view_idx_minus1,
+            Some(UnsignedExpGolombCode(0))
+        );
+        let macroblocks = slice.slice_data.macroblocks.as_ref().unwrap();
+        assert_eq!(macroblocks.len(), 1);
+        assert_eq!(
+            macroblocks[0].mb_skip_run,
+            Some(UnsignedExpGolombCode(8160))
+        );
+        assert!(macroblocks[0].macroblock_layer.is_none());
+
+        let mut writer = BitWriter::new();
+        writer.write(&unit, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+    }
+
+    #[test]
+    fn slice_data_partition_b() {
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+
+        // slice_id 2 and 3 bits of slice data
+        let data = [0b0111_0110];
+
+        // The active picture parameter set isn't used without a slice data partition A
+        decoder.activate_picture_parameter_set(UnsignedExpGolombCode(0));
+        let result: Result<SliceDataPartitionB, _> = BitStream::new(&data).read(&decoder);
+        assert!(matches!(result, Err(BitStreamError::MissingValue)));
+
+        // A slice data partition A with another slice_id
+        decoder.set_slice_data_partition(UnsignedExpGolombCode(1), UnsignedExpGolombCode(0));
+        let result: Result<SliceDataPartitionB, _> = BitStream::new(&data).read(&decoder);
+        assert!(matches!(result, Err(BitStreamError::MissingValue)));
+
+        decoder.set_slice_data_partition(UnsignedExpGolombCode(2), UnsignedExpGolombCode(0));
+        let partition: SliceDataPartitionB = BitStream::new(&data).read(&decoder).unwrap();
+        assert_eq!(partition.slice_id.0, 2);
+        assert!(partition.redundant_pic_cnt.is_none());
+        assert_eq!(partition.slice_data.bit_count, 3);
+        assert_eq!(&*partition.slice_data.data, &[0b1010_0000][..]);
+
+        let mut writer = BitWriter::new();
+        writer.write(&partition, &decoder).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
     }
 }
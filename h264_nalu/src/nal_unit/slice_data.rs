@@ -0,0 +1,695 @@
+use bit_stream::{BitField, BitFieldWrite, BitStream, BitStreamError, BitWriter, Result};
+use serde::Serialize;
+
+use crate::{nal_unit::{CabacContext, CabacDecoder, CabacEncoder, CavlcContext, MacroblockLayer,
+                       MacroblockParameters, NalUnitHeader, SliceHeader, SliceTypeName,
+                       UnparsedData, UnsignedExpGolombCode},
+            Decoder};
+
+/// One iteration of the loop in slice_data( )
+#[derive(Clone, Debug, Serialize)]
+pub struct SliceDataMacroblock {
+    /// Count of skipped macroblocks before `macroblock_layer`, only in P, SP and B slices
+    pub mb_skip_run: Option<UnsignedExpGolombCode>,
+    /// Whether the macroblock is skipped, only in P, SP and B slices with CABAC
+    pub mb_skip_flag: Option<bool>,
+    /// `None` when the slice ends with skipped macroblocks, or the macroblock is skipped
+    pub macroblock_layer: Option<MacroblockLayer>,
+}
+
+/// slice_data( )
+///
+/// Only slices without MBAFF and slice groups are parsed, and CABAC slices only when
+/// ChromaArrayType is not 3. The slice data of others, of slice data partition A, and of CABAC
+/// slices that fail to parse is kept in `unparsed`.
+///
+/// § 7.3.4 Slice data syntax
+#[derive(Clone, Debug, Serialize)]
+pub struct SliceData {
+    pub macroblocks: Option<Vec<SliceDataMacroblock>>,
+    /// Slice data that is not parsed, `None` when `macroblocks` is present
+    pub unparsed: Option<UnparsedData>,
+}
+
+/// Values derived from the parameter sets and the slice header to parse the macroblocks
+struct SliceDataParameters {
+    macroblock: MacroblockParameters,
+    first_mb_addr: u64,
+    pic_width_in_mbs: u64,
+    pic_size_in_mbs: u64,
+    entropy_coding_mode_flag: bool,
+    field_pic_flag: bool,
+    cabac_init_idc: Option<u64>,
+    /// SliceQPY
+    slice_qp_y: i32,
+}
+
+impl SliceDataParameters {
+    /// Returns `None` if the macroblocks of the slice can't be parsed
+    fn new(
+        decoder: &Decoder,
+        nal_unit_header: &NalUnitHeader,
+        header: &SliceHeader,
+    ) -> Result<Option<Self>> {
+        // § 3.148 sequence parameter set
+        let pic_parameter_set = decoder
+            .find_picture_parameter_set(header.pic_parameter_set_id)
+            .ok_or(BitStreamError::MissingValue)?;
+        let seq_parameter_set = decoder
+            .find_slice_sequence_parameter_set(
+                nal_unit_header,
+                pic_parameter_set.seq_parameter_set_id,
+            )
+            .ok_or(BitStreamError::MissingValue)?;
+
+        // § 7.4.3 Slice header semantics
+        let mbaff_frame_flag =
+            seq_parameter_set.mb_adaptive_frame_field_flag && !header.field_pic_flag;
+
+        // Auxiliary coded pictures are monochrome, with the bit depth of the SPS extension
+        //
+        // § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+        let (chroma_array_type, bit_depth_luma) = if nal_unit_header.ty == 19 {
+            let bit_depth_aux_minus8 = decoder
+                .find_sequence_parameter_set_extension(pic_parameter_set.seq_parameter_set_id)
+                .and_then(|x| x.bit_depth_aux_minus8)
+                .ok_or(BitStreamError::MissingValue)?;
+            (0, bit_depth_aux_minus8.0 + 8)
+        } else {
+            (
+                seq_parameter_set.chroma_array_type(),
+                seq_parameter_set.bit_depth_luma(),
+            )
+        };
+
+        // TODO: MBAFF, slice groups, category 2 only slice data, and the context variables of
+        // ctxIdx 460 to 1023 for CABAC when ChromaArrayType is 3
+        let entropy_coding_mode_flag = pic_parameter_set.entropy_coding_mode_flag;
+        if mbaff_frame_flag
+            || pic_parameter_set.num_slice_groups_minus1.0 > 0
+            || nal_unit_header.ty == 2
+            || (entropy_coding_mode_flag && chroma_array_type == 3)
+        {
+            return Ok(None);
+        }
+        let pic_width_in_mbs = seq_parameter_set.pic_width_in_mbs();
+
+        // § 7.4.3 Slice header semantics
+        let pic_height_in_mbs =
+            seq_parameter_set.frame_height_in_mbs() / (1 + header.field_pic_flag as u64);
+
+        Ok(Some(Self {
+            macroblock: MacroblockParameters {
+                slice_type_name: header.slice_type_name()?,
+                chroma_array_type,
+                bit_depth_luma: bit_depth_luma as u8,
+                bit_depth_chroma: seq_parameter_set.bit_depth_chroma() as u8,
+                transform_8x8_mode_flag: pic_parameter_set.transform_8x8_mode_flag == Some(true),
+                direct_8x8_inference_flag: seq_parameter_set.direct_8x8_inference_flag,
+                num_ref_idx_l0_active_minus1: header
+                    .num_ref_idx_l0_active_minus1
+                    .unwrap_or(pic_parameter_set.num_ref_idx_l0_default_active_minus1)
+                    .0,
+                num_ref_idx_l1_active_minus1: header
+                    .num_ref_idx_l1_active_minus1
+                    .unwrap_or(pic_parameter_set.num_ref_idx_l1_default_active_minus1)
+                    .0,
+            },
+            first_mb_addr: header.first_mb_in_slice.0,
+            pic_width_in_mbs,
+            pic_size_in_mbs: pic_width_in_mbs * pic_height_in_mbs,
+            entropy_coding_mode_flag,
+            field_pic_flag: header.field_pic_flag,
+            cabac_init_idc: header.cabac_init_idc.map(|x| x.0),
+            // § 7.4.3 Slice header semantics
+            slice_qp_y: (26 + pic_parameter_set.pic_init_qp_minus26.0 + header.slice_qp_delta.0)
+                as i32,
+        }))
+    }
+
+    fn has_mb_skip_run(&self) -> bool {
+        self.macroblock.slice_type_name != SliceTypeName::I
+            && self.macroblock.slice_type_name != SliceTypeName::SI
+    }
+
+    /// Checks that `mb_skip_run` macroblocks from `curr_mb_addr` end inside the picture
+    fn check_mb_skip_run(&self, curr_mb_addr: u64, mb_skip_run: u64) -> Result<()> {
+        match curr_mb_addr.checked_add(mb_skip_run) {
+            Some(end) if end <= self.pic_size_in_mbs => Ok(()),
+            _ => Err(BitStreamError::InvalidValue),
+        }
+    }
+}
+
+/// Reads the macroblocks of CABAC slice data, up to end_of_slice_flag
+fn read_cabac_macroblocks(
+    stream: &mut BitStream,
+    params: &SliceDataParameters,
+) -> Result<Vec<SliceDataMacroblock>> {
+    while !stream.byte_aligned() {
+        // cabac_alignment_one_bit
+        if !stream.read_bit()? {
+            return Err(BitStreamError::InvalidValue);
+        }
+    }
+
+    let mut decoder = CabacDecoder::new(stream)?;
+    let mut context = CabacContext::new(
+        params.pic_width_in_mbs,
+        params.field_pic_flag,
+        params.cabac_init_idc,
+        params.slice_qp_y,
+    )?;
+    let mut macroblocks = vec![];
+    let mut curr_mb_addr = params.first_mb_addr;
+    loop {
+        if curr_mb_addr >= params.pic_size_in_mbs {
+            return Err(BitStreamError::InvalidValue);
+        }
+        context.start_macroblock(curr_mb_addr);
+
+        let mut mb_skip_flag = None;
+        if params.has_mb_skip_run() {
+            mb_skip_flag = Some(context.read_mb_skip_flag(
+                &mut decoder,
+                stream,
+                params.macroblock.slice_type_name,
+            )?);
+        }
+        let mut macroblock_layer = None;
+        if mb_skip_flag != Some(true) {
+            macroblock_layer =
+                Some(context.read_macroblock_layer(&mut decoder, stream, &params.macroblock)?);
+        }
+        macroblocks.push(SliceDataMacroblock {
+            mb_skip_run: None,
+            mb_skip_flag,
+            macroblock_layer,
+        });
+        curr_mb_addr += 1;
+
+        // end_of_slice_flag
+        if decoder.decode_terminate(stream)? {
+            break;
+        }
+    }
+    Ok(macroblocks)
+}
+
+/// Writes the macroblocks of CABAC slice data, except the last bit written by the arithmetic
+/// encoding engine which is rbsp_stop_one_bit
+fn write_cabac_macroblocks(
+    writer: &mut BitWriter,
+    params: &SliceDataParameters,
+    macroblocks: &[SliceDataMacroblock],
+) -> Result<()> {
+    if macroblocks.is_empty() {
+        return Err(BitStreamError::InvalidValue);
+    }
+    while !writer.byte_aligned() {
+        // cabac_alignment_one_bit
+        writer.write_bit(true)?;
+    }
+
+    // The encoder writes into a byte aligned writer, so that pcm_alignment_zero_bits are the same
+    let mut encoder = CabacEncoder::new();
+    let mut cabac_writer = BitWriter::new();
+    let mut context = CabacContext::new(
+        params.pic_width_in_mbs,
+        params.field_pic_flag,
+        params.cabac_init_idc,
+        params.slice_qp_y,
+    )?;
+    for (i, macroblock) in macroblocks.iter().enumerate() {
+        context.start_macroblock(params.first_mb_addr + i as u64);
+        if params.has_mb_skip_run() {
+            let mb_skip_flag = macroblock
+                .mb_skip_flag
+                .ok_or(BitStreamError::MissingValue)?;
+            context.write_mb_skip_flag(
+                &mut encoder,
+                &mut cabac_writer,
+                params.macroblock.slice_type_name,
+                mb_skip_flag,
+            )?;
+        }
+        if macroblock.mb_skip_flag != Some(true) {
+            let macroblock_layer = macroblock
+                .macroblock_layer
+                .as_ref()
+                .ok_or(BitStreamError::MissingValue)?;
+            context.write_macroblock_layer(
+                &mut encoder,
+                &mut cabac_writer,
+                &params.macroblock,
+                macroblock_layer,
+            )?;
+        }
+
+        // end_of_slice_flag
+        encoder.encode_terminate(&mut cabac_writer, i + 1 == macroblocks.len())?;
+    }
+
+    let bit_count = cabac_writer.len() - 1;
+    let data = cabac_writer.into_inner();
+    let mut stream = BitStream::new(&data);
+    for _ in 0..bit_count {
+        writer.write_bit(stream.read_bit()?)?;
+    }
+    Ok(())
+}
+
+impl<'a> BitField<'a> for SliceData {
+    type Args = (&'a Decoder, &'a NalUnitHeader, &'a SliceHeader);
+
+    fn read(
+        stream: &mut BitStream,
+        (decoder, nal_unit_header, header): Self::Args,
+    ) -> Result<Self> {
+        let params = match SliceDataParameters::new(decoder, nal_unit_header, header)? {
+            Some(params) => params,
+            None => {
+                return Ok(Self {
+                    macroblocks: None,
+                    unparsed: Some(stream.read(())?),
+                })
+            }
+        };
+
+        if params.entropy_coding_mode_flag {
+            // The slice data is kept unparsed if it's not entirely parsed
+            let mut cabac_stream = stream.clone();
+            return Ok(match read_cabac_macroblocks(&mut cabac_stream, &params) {
+                Ok(macroblocks) if !cabac_stream.more_rbsp_data() => {
+                    *stream = cabac_stream;
+                    Self {
+                        macroblocks: Some(macroblocks),
+                        unparsed: None,
+                    }
+                }
+                _ => Self {
+                    macroblocks: None,
+                    unparsed: Some(stream.read(())?),
+                },
+            });
+        }
+
+        let mut context =
+            CavlcContext::new(params.pic_width_in_mbs, params.macroblock.chroma_array_type);
+        let mut macroblocks = vec![];
+        let mut curr_mb_addr = params.first_mb_addr;
+        loop {
+            let mut mb_skip_run = None;
+            if params.has_mb_skip_run() {
+                let value: UnsignedExpGolombCode = stream.read(())?;
+                params.check_mb_skip_run(curr_mb_addr, value.0)?;
+                // Without slice groups, the next macroblock address is always CurrMbAddr + 1
+                for _ in 0..value.0 {
+                    context.start_macroblock(curr_mb_addr);
+                    curr_mb_addr += 1;
+                }
+                mb_skip_run = Some(value);
+
+                if value.0 > 0 && !stream.more_rbsp_data() {
+                    macroblocks.push(SliceDataMacroblock {
+                        mb_skip_run,
+                        mb_skip_flag: None,
+                        macroblock_layer: None,
+                    });
+                    break;
+                }
+            }
+
+            if curr_mb_addr >= params.pic_size_in_mbs {
+                return Err(BitStreamError::InvalidValue);
+            }
+            context.start_macroblock(curr_mb_addr);
+            let macroblock_layer = stream.read((&params.macroblock, &mut context))?;
+            macroblocks.push(SliceDataMacroblock {
+                mb_skip_run,
+                mb_skip_flag: None,
+                macroblock_layer: Some(macroblock_layer),
+            });
+            curr_mb_addr += 1;
+
+            if !stream.more_rbsp_data() {
+                break;
+            }
+        }
+
+        Ok(Self {
+            macroblocks: Some(macroblocks),
+            unparsed: None,
+        })
+    }
+}
+
+impl<'a> BitFieldWrite<'a> for SliceData {
+    type Args = (&'a Decoder, &'a NalUnitHeader, &'a SliceHeader);
+
+    fn write(
+        &self,
+        writer: &mut BitWriter,
+        (decoder, nal_unit_header, header): Self::Args,
+    ) -> Result<()> {
+        let macroblocks = match (&self.macroblocks, &self.unparsed) {
+            (Some(macroblocks), _) => macroblocks,
+            (None, Some(unparsed)) => return writer.write(unparsed, ()),
+            (None, None) => return Ok(()),
+        };
+        let params = SliceDataParameters::new(decoder, nal_unit_header, header)?
+            .ok_or(BitStreamError::InvalidValue)?;
+        if params.entropy_coding_mode_flag {
+            return write_cabac_macroblocks(writer, &params, macroblocks);
+        }
+
+        let mut context =
+            CavlcContext::new(params.pic_width_in_mbs, params.macroblock.chroma_array_type);
+        let mut curr_mb_addr = params.first_mb_addr;
+        for macroblock in macroblocks {
+            if let Some(mb_skip_run) = &macroblock.mb_skip_run {
+                params.check_mb_skip_run(curr_mb_addr, mb_skip_run.0)?;
+                writer.write(mb_skip_run, ())?;
+                for _ in 0..mb_skip_run.0 {
+                    context.start_macroblock(curr_mb_addr);
+                    curr_mb_addr += 1;
+                }
+            }
+            if let Some(macroblock_layer) = &macroblock.macroblock_layer {
+                if curr_mb_addr >= params.pic_size_in_mbs {
+                    return Err(BitStreamError::InvalidValue);
+                }
+                context.start_macroblock(curr_mb_addr);
+                writer.write(macroblock_layer, (&params.macroblock, &mut context))?;
+                curr_mb_addr += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use super::{SliceData, SliceDataMacroblock, SliceDataParameters};
+    use crate::{nal_unit::{CabacContext, CabacDecoder, MbType, SequenceParameterSetExtension,
+                           SliceLayerWithoutPartitioning, UnsignedExpGolombCode},
+                test::{test_decoder, test_sequence_parameter_set, SliceHeaderBuilder}};
+
+    #[test]
+    fn mb_skip_run_past_the_end() {
+        let decoder = test_decoder(test_sequence_parameter_set());
+        let mut builder = SliceHeaderBuilder::new(1, 2, 5, 1);
+        builder.first_mb_in_slice = 2;
+        let header = builder.nal_unit_header();
+        // One more than the remaining macroblocks, and CurrMbAddr + mb_skip_run overflowing
+        for mb_skip_run in [31 * 68 - 1, u64::MAX - 1] {
+            let mut writer = BitWriter::new();
+            builder.write(&mut writer);
+            writer
+                .write(&UnsignedExpGolombCode(mb_skip_run), ())
+                .unwrap();
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            let data = writer.into_inner();
+
+            let result: bit_stream::Result<SliceLayerWithoutPartitioning> =
+                BitStream::new(&data).read((&decoder, &header));
+            assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+        }
+
+        let (header, slice_header) = builder.read(&decoder);
+        let slice_data = SliceData {
+            macroblocks: Some(vec![SliceDataMacroblock {
+                mb_skip_run: Some(UnsignedExpGolombCode(u64::MAX - 1)),
+                mb_skip_flag: None,
+                macroblock_layer: None,
+            }]),
+            unparsed: None,
+        };
+        let result = BitWriter::new().write(&slice_data, (&decoder, &header, &slice_header));
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+
+    #[test]
+    fn auxiliary_slice_parameters() {
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let (header, slice_header) = SliceHeaderBuilder::new(19, 2, 7, 1).read(&decoder);
+        // bit_depth_aux_minus8 comes from the SPS extension
+        assert!(matches!(
+            SliceDataParameters::new(&decoder, &header, &slice_header),
+            Err(BitStreamError::MissingValue)
+        ));
+
+        // aux_format_idc 1, 9-bit alpha, opaque 255, transparent 0
+        let mut extension: SequenceParameterSetExtension =
+            BitStream::new(&[0xa9, 0xfe, 0x00, 0x40]).read(()).unwrap();
+        extension.bit_depth_aux_minus8 = Some(UnsignedExpGolombCode(2));
+        decoder.set_sequence_parameter_set_extension(extension);
+        let params = SliceDataParameters::new(&decoder, &header, &slice_header)
+            .unwrap()
+            .unwrap();
+        assert_eq!(params.macroblock.chroma_array_type, 0);
+        assert_eq!(params.macroblock.bit_depth_luma, 10);
+    }
+
+    #[test]
+    fn unparsed_slice_data() {
+        // Slice groups aren't parsed
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps = decoder
+            .find_picture_parameter_set(UnsignedExpGolombCode(0))
+            .unwrap()
+            .clone();
+        pps.num_slice_groups_minus1 = UnsignedExpGolombCode(1);
+        pps.slice_group_map_type = Some(UnsignedExpGolombCode(0));
+        decoder.set_picture_parameter_set(pps);
+
+        let builder = SliceHeaderBuilder::new(5, 3, 7, 0);
+        let mut writer = BitWriter::new();
+        builder.write(&mut writer);
+        for bit in [true, false, true, true, false, false, false, false, true] {
+            writer.write_bit(bit).unwrap();
+        }
+        // rbsp_trailing_bits
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        let data = writer.into_inner();
+
+        let header = builder.nal_unit_header();
+        let slice: SliceLayerWithoutPartitioning =
+            BitStream::new(&data).read((&decoder, &header)).unwrap();
+        assert!(slice.slice_data.macroblocks.is_none());
+        let unparsed = slice.slice_data.unparsed.as_ref().unwrap();
+        assert_eq!(unparsed.bit_count, 9);
+        assert_eq!(&*unparsed.data, &[0b1011_0000, 0b1000_0000][..]);
+
+        let mut writer = BitWriter::new();
+        writer.write(&slice, (&decoder, &header)).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(writer.into_inner(), data);
+    }
+
+    #[test]
+    fn cabac_known_answer() {
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps = decoder
+            .find_picture_parameter_set(UnsignedExpGolombCode(0))
+            .unwrap()
+            .clone();
+        pps.entropy_coding_mode_flag = true;
+        let pic_init_qp_minus26 = pps.pic_init_qp_minus26.0;
+        decoder.set_picture_parameter_set(pps);
+
+        let mut builder = SliceHeaderBuilder::new(1, 2, 7, 1);
+        // SliceQPY 31
+        builder.slice_qp_delta = 5 - pic_init_qp_minus26;
+        let mut writer = BitWriter::new();
+        builder.write(&mut writer);
+        // cabac_alignment_one_bit
+        while !writer.byte_aligned() {
+            writer.write_bit(true).unwrap();
+        }
+        let mut data = writer.into_inner().to_vec();
+        // I_16x16_2_0_0 with intra_chroma_pred_mode 1, I_NxN with rem_intra4x4_pred_mode 3 in
+        // block 5, CodedBlockPattern 16 and mb_qp_delta -1, then I_16x16_1_2_0 with
+        // intra_chroma_pred_mode 2 and mb_qp_delta 2, all without coefficients.
+        //
+        // Encoded by a separate implementation of the arithmetic encoding process of § 9.3.4.2,
+        // with the bins and their ctxIdx worked out by hand from § 9.3.2 and § 9.3.3.1.
+        data.extend([
+            0xfc, 0xc5, 0x3e, 0xe8, 0xc7, 0x2d, 0xfd, 0x33, 0xce, 0x2a, 0x70,
+        ]);
+
+        let header = builder.nal_unit_header();
+        let slice: SliceLayerWithoutPartitioning =
+            BitStream::new(&data).read((&decoder, &header)).unwrap();
+        let params = SliceDataParameters::new(&decoder, &header, &slice.slice_header)
+            .unwrap()
+            .unwrap();
+        assert_eq!(params.slice_qp_y, 31);
+
+        let macroblocks = slice.slice_data.macroblocks.as_ref().unwrap();
+        let macroblocks: Vec<_> = macroblocks
+            .iter()
+            .map(|x| x.macroblock_layer.as_ref().unwrap())
+            .collect();
+        assert_eq!(macroblocks.len(), 3);
+        assert_eq!(
+            macroblocks[0].mb_type,
+            MbType::I16x16 {
+                intra16x16_pred_mode: 2,
+                coded_block_pattern_chroma: 0,
+                coded_block_pattern_luma: 0,
+            }
+        );
+        assert_eq!(macroblocks[1].mb_type, MbType::INxN);
+        assert_eq!(
+            macroblocks[2].mb_type,
+            MbType::I16x16 {
+                intra16x16_pred_mode: 1,
+                coded_block_pattern_chroma: 2,
+                coded_block_pattern_luma: 0,
+            }
+        );
+        let coded_block_pattern = macroblocks.iter().map(|x| x.coded_block_pattern());
+        assert_eq!(
+            coded_block_pattern.collect::<Vec<_>>(),
+            [(0, 0), (0, 1), (0, 2)]
+        );
+        let mb_qp_delta = macroblocks.iter().map(|x| x.mb_qp_delta.map(|x| x.0));
+        assert_eq!(
+            mb_qp_delta.collect::<Vec<_>>(),
+            [Some(0), Some(-1), Some(2)]
+        );
+        let mb_pred = macroblocks[1].mb_pred.as_ref().unwrap();
+        let mut rem_intra4x4_pred_mode = vec![None; 16];
+        rem_intra4x4_pred_mode[5] = Some(3);
+        assert_eq!(mb_pred.rem_intra4x4_pred_mode, rem_intra4x4_pred_mode);
+        let intra_chroma_pred_mode = macroblocks.iter().map(|x| {
+            x.mb_pred
+                .as_ref()
+                .unwrap()
+                .intra_chroma_pred_mode
+                .unwrap()
+                .0
+        });
+        assert_eq!(intra_chroma_pred_mode.collect::<Vec<_>>(), [1, 0, 2]);
+
+        let mut writer = BitWriter::new();
+        writer.write(&slice, (&decoder, &header)).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+    }
+
+    #[test]
+    fn cabac_slice_data_round_trip() {
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps = decoder
+            .find_picture_parameter_set(UnsignedExpGolombCode(0))
+            .unwrap()
+            .clone();
+        pps.entropy_coding_mode_flag = true;
+        pps.transform_8x8_mode_flag = Some(true);
+        pps.pic_scaling_matrix_present_flag = Some(false);
+        decoder.set_picture_parameter_set(pps);
+
+        // I, P and B slices
+        for (slice_type, seed) in [(2, 1), (0, 2), (1, 3)] {
+            let mut builder = SliceHeaderBuilder::new(1, 2, slice_type, 1);
+            if slice_type != 2 {
+                builder.num_ref_idx_active_minus1 = Some(31);
+                builder.cabac_init_idc = Some(seed % 3);
+            }
+            let (header, slice_header) = builder.read(&decoder);
+            let params = SliceDataParameters::new(&decoder, &header, &slice_header)
+                .unwrap()
+                .unwrap();
+
+            // Macroblocks decoded from pseudo-random bytes, over two rows of macroblocks
+            let mut state: u64 = seed;
+            let random: Vec<u8> = (0..65536)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    (state >> 56) as u8
+                })
+                .collect();
+            let mut stream = BitStream::new(&random);
+            let mut cabac_decoder = CabacDecoder::new(&mut stream).unwrap();
+            let mut context = CabacContext::new(
+                params.pic_width_in_mbs,
+                params.field_pic_flag,
+                params.cabac_init_idc,
+                params.slice_qp_y,
+            )
+            .unwrap();
+            let mut macroblocks = vec![];
+            for mb_addr in 0..40 {
+                context.start_macroblock(mb_addr);
+                let mut mb_skip_flag = None;
+                if params.has_mb_skip_run() {
+                    mb_skip_flag = Some(
+                        context
+                            .read_mb_skip_flag(
+                                &mut cabac_decoder,
+                                &mut stream,
+                                params.macroblock.slice_type_name,
+                            )
+                            .unwrap(),
+                    );
+                }
+                let mut macroblock_layer = None;
+                if mb_skip_flag != Some(true) {
+                    macroblock_layer = Some(
+                        context
+                            .read_macroblock_layer(
+                                &mut cabac_decoder,
+                                &mut stream,
+                                &params.macroblock,
+                            )
+                            .unwrap(),
+                    );
+                }
+                macroblocks.push(SliceDataMacroblock {
+                    mb_skip_run: None,
+                    mb_skip_flag,
+                    macroblock_layer,
+                });
+            }
+            let slice_data = SliceData {
+                macroblocks: Some(macroblocks),
+                unparsed: None,
+            };
+
+            let mut writer = BitWriter::new();
+            builder.write(&mut writer);
+            writer
+                .write(&slice_data, (&decoder, &header, &slice_header))
+                .unwrap();
+            // rbsp_trailing_bits
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            let data = writer.into_inner();
+
+            let slice: SliceLayerWithoutPartitioning =
+                BitStream::new(&data).read((&decoder, &header)).unwrap();
+            assert!(slice.slice_data.macroblocks.is_some());
+            assert_eq!(
+                format!("{:?}", slice.slice_data),
+                format!("{:?}", slice_data)
+            );
+
+            let mut writer = BitWriter::new();
+            writer.write(&slice, (&decoder, &header)).unwrap();
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            assert_eq!(writer.into_inner(), data);
+        }
+    }
+}
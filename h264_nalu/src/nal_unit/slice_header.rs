@@ -38,8 +38,12 @@ cond_bit_field! {
         pub pic_parameter_set_id: UnsignedExpGolombCode;
 
         // § 3.148 sequence parameter set
-        let pic_parameter_set = decoder.find_picture_parameter_set(pic_parameter_set_id).unwrap();
-        let seq_parameter_set = decoder.find_sequence_parameter_set(pic_parameter_set.seq_parameter_set_id).unwrap();
+        let pic_parameter_set = decoder
+            .find_picture_parameter_set(pic_parameter_set_id)
+            .ok_or(BitStreamError::MissingValue)?;
+        let seq_parameter_set = decoder
+            .find_slice_sequence_parameter_set(header, pic_parameter_set.seq_parameter_set_id)
+            .ok_or(BitStreamError::MissingValue)?;
         if seq_parameter_set.separate_colour_plane_flag {
             pub colour_plane_id: u2;
         }
@@ -48,7 +52,7 @@ cond_bit_field! {
         /// log2_max_frame_num_minus4 + 4 bits in the bitstream.
         ///
         /// § 7.4.3 Slice header semantics
-        pub frame_num: u8[(seq_parameter_set.log2_max_frame_num_minus4.0 + 4) as u8];
+        pub frame_num: u16[(seq_parameter_set.log2_max_frame_num_minus4.0 + 4) as u8];
 
         if !seq_parameter_set.frame_mbs_only_flag {
             /// equal to 1 specifies that the slice is a slice of a coded field.
@@ -71,8 +75,12 @@ cond_bit_field! {
         // ```
         //
         // § 7.4.1 NAL unit semantics
+        //
+        // For coded slice extensions, `IdrPicFlag` is `non_idr_flag` inverted
+        //
+        // § H.7.4.1.1 NAL unit header MVC extension semantics
         #[allow(non_snake_case)]
-        let IdrPicFlag = header.ty == 5;
+        let IdrPicFlag = header.ty == 5 || header.mvc_extension.is_some_and(|x| !x.non_idr_flag);
 
         if IdrPicFlag {
             pub idr_pic_id: UnsignedExpGolombCode;
@@ -86,11 +94,11 @@ cond_bit_field! {
             /// MaxPicOrderCntLsb − 1, inclusive.
             ///
             /// § 7.4.3 Slice header semantics
-            pub pic_order_cnt_lsb: u8[(seq_parameter_set.log2_max_pic_order_cnt_lsb_minus4.unwrap().0 + 4) as u8];
-        }
+            pub pic_order_cnt_lsb: u16[(seq_parameter_set.log2_max_pic_order_cnt_lsb_minus4.unwrap().0 + 4) as u8];
 
-        if pic_parameter_set.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
-            pub delta_pic_order_cnt_bottom: SignedExpGolombCode;
+            if pic_parameter_set.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
+                pub delta_pic_order_cnt_bottom: SignedExpGolombCode;
+            }
         }
 
         if seq_parameter_set.pic_order_cnt_type == 1 &&
@@ -124,10 +132,24 @@ cond_bit_field! {
             }
         }
 
+        // § 7.4.3 Slice header semantics
+        let num_ref_idx_l0_active = num_ref_idx_l0_active_minus1
+            .unwrap_or(pic_parameter_set.num_ref_idx_l0_default_active_minus1).0 + 1;
+        let num_ref_idx_l1_active = num_ref_idx_l1_active_minus1
+            .unwrap_or(pic_parameter_set.num_ref_idx_l1_default_active_minus1).0 + 1;
+
         if header.ty == 20 || header.ty == 21 {
-            // TODO ref_pic_list_mvc_modification()
+            pub ref_pic_list_mvc_modification: RefPicListMvcModification[
+                &slice_type_name,
+                num_ref_idx_l0_active,
+                num_ref_idx_l1_active
+            ];
         } else {
-            pub ref_pic_list_modification: RefPicListModification[&slice_type_name];
+            pub ref_pic_list_modification: RefPicListModification[
+                &slice_type_name,
+                num_ref_idx_l0_active,
+                num_ref_idx_l1_active
+            ];
         }
 
         if (
@@ -135,13 +157,29 @@ cond_bit_field! {
             (slice_type_name == SliceTypeName::P || slice_type_name == SliceTypeName::SP)
         ) || (
             pic_parameter_set.weighted_bipred_idc == 1 &&
-            (slice_type_name == SliceTypeName::P)
+            (slice_type_name == SliceTypeName::B)
         ) {
-            // TODO pred_weight_table
+            // § 7.4.2.1.1 Sequence parameter set data semantics
+            #[allow(non_snake_case)]
+            let ChromaArrayType = if header.ty == 19 {
+                // Auxiliary coded pictures are monochrome
+                //
+                // § 7.4.2.1.2 Sequence parameter set extension RBSP semantics
+                0
+            } else {
+                seq_parameter_set.chroma_array_type()
+            };
+
+            pub pred_weight_table: PredWeightTable[
+                &slice_type_name,
+                ChromaArrayType,
+                num_ref_idx_l0_active,
+                num_ref_idx_l1_active
+            ];
         }
 
         if header.ref_idc != 0 {
-            // TODO dec_ref_pic_marking
+            pub dec_ref_pic_marking: DecRefPicMarking[IdrPicFlag];
         }
 
         if pic_parameter_set.entropy_coding_mode_flag &&
@@ -174,11 +212,7 @@ cond_bit_field! {
             pic_parameter_set.slice_group_map_type.unwrap() >= 3 &&
             pic_parameter_set.slice_group_map_type.unwrap() <= 5 {
             #[allow(non_snake_case)]
-            let PicWidthInMbs = seq_parameter_set.pic_width_in_mbs_minus1.0 + 1;
-            #[allow(non_snake_case)]
-            let PicHeightInMapUnits = seq_parameter_set.pic_height_in_map_units_minus1.0 + 1;
-            #[allow(non_snake_case)]
-            let PicSizeInMapUnits = PicWidthInMbs * PicHeightInMapUnits;
+            let PicSizeInMapUnits = seq_parameter_set.pic_size_in_map_units();
             #[allow(non_snake_case)]
             let SliceGroupChangeRate = pic_parameter_set.slice_group_change_rate_minus1.unwrap().0 + 1;
 
@@ -210,25 +244,405 @@ cond_bit_field! {
     ///
     /// § 7.3.3.1 Reference picture list modification syntax
     #[derive(Clone, Debug, Serialize)]
-    #[extra_args(slice_type_name: &SliceTypeName)]
+    #[extra_args(
+        slice_type_name: &SliceTypeName,
+        num_ref_idx_l0_active: u64,
+        num_ref_idx_l1_active: u64
+    )]
     pub struct RefPicListModification {
         if slice_type_name != &SliceTypeName::I &&
             slice_type_name != &SliceTypeName::SI {
             pub ref_pic_list_modification_flag_l0: bool;
             if ref_pic_list_modification_flag_l0 {
-                // TODO
+                let mut modification_count = 0;
+                loop {
+                    /// Modifications of RefPicList0, in bitstream order.
+                    ///
+                    /// The last one always has `modification_of_pic_nums_idc` equal to 3.
+                    pub ref_pic_list_modification_l0: RefPicListModificationOperation;
+                    if ref_pic_list_modification_l0.modification_of_pic_nums_idc == 3 {
+                        break;
+                    }
+
+                    // § 7.4.3.1: at most num_ref_idx_l0_active_minus1 + 1 modifications
+                    modification_count += 1;
+                    if modification_count > num_ref_idx_l0_active {
+                        return Err(BitStreamError::InvalidValue);
+                    }
+                }
+            }
+        }
+
+        if slice_type_name == &SliceTypeName::B {
+            pub ref_pic_list_modification_flag_l1: bool;
+            if ref_pic_list_modification_flag_l1 {
+                let mut modification_count = 0;
+                loop {
+                    /// Modifications of RefPicList1, in bitstream order.
+                    ///
+                    /// The last one always has `modification_of_pic_nums_idc` equal to 3.
+                    pub ref_pic_list_modification_l1: RefPicListModificationOperation;
+                    if ref_pic_list_modification_l1.modification_of_pic_nums_idc == 3 {
+                        break;
+                    }
+
+                    // § 7.4.3.1: at most num_ref_idx_l1_active_minus1 + 1 modifications
+                    modification_count += 1;
+                    if modification_count > num_ref_idx_l1_active {
+                        return Err(BitStreamError::InvalidValue);
+                    }
+                }
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `modification_of_pic_nums_idc` loop
+    ///
+    /// § 7.3.3.1 Reference picture list modification syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct RefPicListModificationOperation {
+        /// together with abs_diff_pic_num_minus1 or long_term_pic_num specifies which of the
+        /// reference pictures are re-mapped.
+        ///
+        /// § 7.4.3.1 Reference picture list modification semantics
+        ///
+        /// | modification_of_pic_nums_idc | modification specified                                                                  |
+        /// |------------------------------|-----------------------------------------------------------------------------------------|
+        /// | 0                            | abs_diff_pic_num_minus1 is present and corresponds to a difference to subtract from a picture number prediction value |
+        /// | 1                            | abs_diff_pic_num_minus1 is present and corresponds to a difference to add to a picture number prediction value        |
+        /// | 2                            | long_term_pic_num is present and specifies the long-term picture number for a reference picture                       |
+        /// | 3                            | End loop for modification of the initial reference picture list                         |
+        ///
+        /// Table 7-7 – modification_of_pic_nums_idc operations for modification of reference picture lists
+        pub modification_of_pic_nums_idc: UnsignedExpGolombCode;
+        if modification_of_pic_nums_idc.0 > 3 {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        if modification_of_pic_nums_idc == 0 || modification_of_pic_nums_idc == 1 {
+            /// plus 1 specifies the absolute difference between the picture number of the picture
+            /// being moved to the current index in the list and the picture number prediction value.
+            ///
+            /// § 7.4.3.1 Reference picture list modification semantics
+            pub abs_diff_pic_num_minus1: UnsignedExpGolombCode;
+        } else if modification_of_pic_nums_idc == 2 {
+            /// specifies the long-term picture number of the picture being moved to the current
+            /// index in the list.
+            ///
+            /// § 7.4.3.1 Reference picture list modification semantics
+            pub long_term_pic_num: UnsignedExpGolombCode;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// ref_pic_list_mvc_modification
+    ///
+    /// § H.7.3.3.1.1 Reference picture list MVC modification syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(
+        slice_type_name: &SliceTypeName,
+        num_ref_idx_l0_active: u64,
+        num_ref_idx_l1_active: u64
+    )]
+    pub struct RefPicListMvcModification {
+        if slice_type_name != &SliceTypeName::I &&
+            slice_type_name != &SliceTypeName::SI {
+            pub ref_pic_list_modification_flag_l0: bool;
+            if ref_pic_list_modification_flag_l0 {
+                let mut modification_count = 0;
+                loop {
+                    /// Modifications of RefPicList0, in bitstream order.
+                    ///
+                    /// The last one always has `modification_of_pic_nums_idc` equal to 3.
+                    pub ref_pic_list_modification_l0: RefPicListMvcModificationOperation;
+                    if ref_pic_list_modification_l0.modification_of_pic_nums_idc == 3 {
+                        break;
+                    }
+
+                    // § 7.4.3.1: at most num_ref_idx_l0_active_minus1 + 1 modifications
+                    modification_count += 1;
+                    if modification_count > num_ref_idx_l0_active {
+                        return Err(BitStreamError::InvalidValue);
+                    }
+                }
             }
         }
 
         if slice_type_name == &SliceTypeName::B {
             pub ref_pic_list_modification_flag_l1: bool;
             if ref_pic_list_modification_flag_l1 {
-                // TODO
+                let mut modification_count = 0;
+                loop {
+                    /// Modifications of RefPicList1, in bitstream order.
+                    ///
+                    /// The last one always has `modification_of_pic_nums_idc` equal to 3.
+                    pub ref_pic_list_modification_l1: RefPicListMvcModificationOperation;
+                    if ref_pic_list_modification_l1.modification_of_pic_nums_idc == 3 {
+                        break;
+                    }
+
+                    // § 7.4.3.1: at most num_ref_idx_l1_active_minus1 + 1 modifications
+                    modification_count += 1;
+                    if modification_count > num_ref_idx_l1_active {
+                        return Err(BitStreamError::InvalidValue);
+                    }
+                }
             }
         }
     }
 }
 
+cond_bit_field! {
+    /// One iteration of the `modification_of_pic_nums_idc` loop
+    ///
+    /// § H.7.3.3.1.1 Reference picture list MVC modification syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct RefPicListMvcModificationOperation {
+        /// Same as in `RefPicListModificationOperation`, with two more operations
+        /// for inter-view reference components.
+        ///
+        /// | modification_of_pic_nums_idc | modification specified                                                                  |
+        /// |------------------------------|-----------------------------------------------------------------------------------------|
+        /// | 4                            | abs_diff_view_idx_minus1 is present and corresponds to a difference to subtract from a prediction value of the reference view index |
+        /// | 5                            | abs_diff_view_idx_minus1 is present and corresponds to a difference to add to a prediction value of the reference view index        |
+        ///
+        /// Table H-3 – modification_of_pic_nums_idc operations for modification of reference picture lists
+        pub modification_of_pic_nums_idc: UnsignedExpGolombCode;
+        if modification_of_pic_nums_idc.0 > 5 {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        if modification_of_pic_nums_idc == 0 || modification_of_pic_nums_idc == 1 {
+            pub abs_diff_pic_num_minus1: UnsignedExpGolombCode;
+        } else if modification_of_pic_nums_idc == 2 {
+            pub long_term_pic_num: UnsignedExpGolombCode;
+        } else if modification_of_pic_nums_idc == 4 || modification_of_pic_nums_idc == 5 {
+            /// plus 1 specifies the absolute difference between the reference view index to put
+            /// to the current index in the reference picture list and the prediction value of
+            /// the reference view index.
+            ///
+            /// § H.7.4.3.1.1 Reference picture list MVC modification semantics
+            pub abs_diff_view_idx_minus1: UnsignedExpGolombCode;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// pred_weight_table
+    ///
+    /// § 7.3.3.2 Prediction weight table syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(
+        slice_type_name: &SliceTypeName,
+        chroma_array_type: u64,
+        num_ref_idx_l0_active: u64,
+        num_ref_idx_l1_active: u64
+    )]
+    pub struct PredWeightTable {
+        /// is the base 2 logarithm of the denominator for all luma weighting factors.
+        /// The value of luma_log2_weight_denom shall be in the range of 0 to 7, inclusive.
+        ///
+        /// § 7.4.3.2 Prediction weight table semantics
+        pub luma_log2_weight_denom: UnsignedExpGolombCode;
+
+        if chroma_array_type != 0 {
+            /// is the base 2 logarithm of the denominator for all chroma weighting factors.
+            /// The value of chroma_log2_weight_denom shall be in the range of 0 to 7, inclusive.
+            ///
+            /// § 7.4.3.2 Prediction weight table semantics
+            pub chroma_log2_weight_denom: UnsignedExpGolombCode;
+        }
+
+        for _ in 0..num_ref_idx_l0_active {
+            /// Weights and offsets for each entry of RefPicList0
+            pub weights_l0: PredWeight[chroma_array_type];
+        }
+
+        if slice_type_name == &SliceTypeName::B {
+            for _ in 0..num_ref_idx_l1_active {
+                /// Weights and offsets for each entry of RefPicList1
+                pub weights_l1: PredWeight[chroma_array_type];
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// Weights and offsets of one reference picture in `pred_weight_table`
+    ///
+    /// § 7.3.3.2 Prediction weight table syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(chroma_array_type: u64)]
+    pub struct PredWeight {
+        /// luma_weight_l0_flag or luma_weight_l1_flag
+        ///
+        /// equal to 1 specifies that weighting factors for the luma component are present.
+        /// equal to 0 specifies that these weighting factors are not present.
+        ///
+        /// § 7.4.3.2 Prediction weight table semantics
+        pub luma_weight_flag: bool;
+
+        if luma_weight_flag {
+            /// luma_weight_l0 or luma_weight_l1
+            ///
+            /// When luma_weight_flag is equal to 0, it shall be inferred to be equal to
+            /// 2^luma_log2_weight_denom.
+            ///
+            /// § 7.4.3.2 Prediction weight table semantics
+            pub luma_weight: SignedExpGolombCode;
+            /// luma_offset_l0 or luma_offset_l1
+            ///
+            /// When luma_weight_flag is equal to 0, it shall be inferred to be equal to 0.
+            ///
+            /// § 7.4.3.2 Prediction weight table semantics
+            pub luma_offset: SignedExpGolombCode;
+        }
+
+        if chroma_array_type != 0 {
+            /// chroma_weight_l0_flag or chroma_weight_l1_flag
+            ///
+            /// equal to 1 specifies that weighting factors for the chroma prediction values are present.
+            /// equal to 0 specifies that these weighting factors are not present.
+            ///
+            /// § 7.4.3.2 Prediction weight table semantics
+            pub chroma_weight_flag: bool = false;
+
+            if chroma_weight_flag {
+                // Cb and Cr
+                for _ in 0..2 {
+                    /// chroma_weight_l0 or chroma_weight_l1
+                    ///
+                    /// When chroma_weight_flag is equal to 0, it shall be inferred to be equal to
+                    /// 2^chroma_log2_weight_denom.
+                    ///
+                    /// § 7.4.3.2 Prediction weight table semantics
+                    pub chroma_weight: SignedExpGolombCode;
+                    /// chroma_offset_l0 or chroma_offset_l1
+                    ///
+                    /// When chroma_weight_flag is equal to 0, it shall be inferred to be equal to 0.
+                    ///
+                    /// § 7.4.3.2 Prediction weight table semantics
+                    pub chroma_offset: SignedExpGolombCode;
+                }
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// dec_ref_pic_marking
+    ///
+    /// § 7.3.3.3 Decoded reference picture marking syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(idr_pic_flag: bool)]
+    pub struct DecRefPicMarking {
+        if idr_pic_flag {
+            /// specifies how the previously-decoded pictures in the decoded picture buffer are
+            /// treated after decoding of an IDR picture.
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub no_output_of_prior_pics_flag: bool;
+            /// equal to 0 specifies that the MaxLongTermFrameIdx variable is set equal to
+            /// "no long-term frame indices" and that the IDR picture is marked as "used for short-term reference".
+            /// long_term_reference_flag equal to 1 specifies that the MaxLongTermFrameIdx variable is set
+            /// equal to 0 and that the current IDR picture is marked "used for long-term reference" and
+            /// is assigned LongTermFrameIdx equal to 0.
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub long_term_reference_flag: bool;
+        } else {
+            /// selects the reference picture marking mode of the currently decoded picture.
+            ///
+            /// | adaptive_ref_pic_marking_mode_flag | Reference picture marking mode specified                |
+            /// |------------------------------------|---------------------------------------------------------|
+            /// | 0                                  | Sliding window reference picture marking mode           |
+            /// | 1                                  | Adaptive reference picture marking mode                 |
+            ///
+            /// Table 7-8 – Interpretation of adaptive_ref_pic_marking_mode_flag
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub adaptive_ref_pic_marking_mode_flag: bool;
+
+            if adaptive_ref_pic_marking_mode_flag {
+                loop {
+                    /// Memory management control operations, in bitstream order.
+                    ///
+                    /// The last one always has `memory_management_control_operation` equal to 0.
+                    pub memory_management_control_operations: MemoryManagementControlOperation;
+                    if memory_management_control_operations.memory_management_control_operation == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `memory_management_control_operation` loop
+    ///
+    /// § 7.3.3.3 Decoded reference picture marking syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MemoryManagementControlOperation {
+        /// specifies a control operation to be applied to affect the reference picture marking.
+        ///
+        /// § 7.4.3.3 Decoded reference picture marking semantics
+        ///
+        /// | memory_management_control_operation | Memory Management Control Operation                                                   |
+        /// |-------------------------------------|---------------------------------------------------------------------------------------|
+        /// | 0                                   | End memory_management_control_operation syntax element loop                          |
+        /// | 1                                   | Mark a short-term reference picture as "unused for reference"                        |
+        /// | 2                                   | Mark a long-term reference picture as "unused for reference"                         |
+        /// | 3                                   | Mark a short-term reference picture as "used for long-term reference" and assign a long-term frame index to it |
+        /// | 4                                   | Specify the maximum long-term frame index and mark all long-term reference pictures having long-term frame indices greater than the maximum value as "unused for reference" |
+        /// | 5                                   | Mark all reference pictures as "unused for reference" and set the MaxLongTermFrameIdx variable to "no long-term frame indices" |
+        /// | 6                                   | Mark the current picture as "used for long-term reference" and assign a long-term frame index to it |
+        ///
+        /// Table 7-9 – Memory management control operation (memory_management_control_operation) values
+        pub memory_management_control_operation: UnsignedExpGolombCode;
+        if memory_management_control_operation.0 > 6 {
+            return Err(BitStreamError::InvalidValue);
+        }
+
+        if memory_management_control_operation == 1 || memory_management_control_operation == 3 {
+            /// is used (with memory_management_control_operation equal to 3 or 1) to assign a
+            /// long-term frame index to a short-term reference picture or to mark a short-term
+            /// reference picture as "unused for reference".
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub difference_of_pic_nums_minus1: UnsignedExpGolombCode;
+        }
+
+        if memory_management_control_operation == 2 {
+            /// is used (with memory_management_control_operation equal to 2) to mark a
+            /// long-term reference picture as "unused for reference".
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub long_term_pic_num: UnsignedExpGolombCode;
+        }
+
+        if memory_management_control_operation == 3 || memory_management_control_operation == 6 {
+            /// is used (with memory_management_control_operation equal to 3 or 6) to assign a
+            /// long-term frame index to a picture.
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub long_term_frame_idx: UnsignedExpGolombCode;
+        }
+
+        if memory_management_control_operation == 4 {
+            /// minus 1 specifies the maximum value of long-term frame index allowed for
+            /// long-term reference pictures (until receipt of another value of
+            /// max_long_term_frame_idx_plus1).
+            ///
+            /// § 7.4.3.3 Decoded reference picture marking semantics
+            pub max_long_term_frame_idx_plus1: UnsignedExpGolombCode;
+        }
+    }
+}
+
 /// | slice_type | Name of slice_type |
 /// |------------|--------------------|
 /// | 0          | P (P slice)        |
@@ -243,7 +657,7 @@ cond_bit_field! {
 /// | 9          | SI (SI slice)      |
 ///
 /// Table 7-6 – Name association to slice_type
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum SliceTypeName {
     P,
     B,
@@ -273,7 +687,309 @@ impl TryFrom<u64> for SliceTypeName {
 }
 
 impl SliceHeader {
-    pub fn slice_type_name(&self) -> SliceTypeName {
-        self.slice_type.0.try_into().unwrap()
+    /// Returns `InvalidValue` if `slice_type` is not in Table 7-6
+    pub fn slice_type_name(&self) -> bit_stream::Result<SliceTypeName> {
+        self.slice_type
+            .0
+            .try_into()
+            .or(Err(BitStreamError::InvalidValue))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitStreamError, BitWriter};
+
+    use super::{DecRefPicMarking, PredWeightTable, RefPicListModification,
+                RefPicListMvcModification};
+    use crate::{nal_unit::{PictureParameterSet, SignedExpGolombCode, SliceTypeName,
+                           UnsignedExpGolombCode},
+                test::{test_decoder, test_sequence_parameter_set, SliceHeaderBuilder}};
+
+    /// ref_pic_list_modification( ) with the Exp-Golomb codes of each list, a list is only
+    /// signalled when not empty
+    fn ref_pic_list_modification_data(lists: &[&[u64]]) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        for list in lists {
+            writer.write_bit(!list.is_empty()).unwrap();
+            for &value in *list {
+                writer.write(&UnsignedExpGolombCode(value), ()).unwrap();
+            }
+        }
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        writer.into_inner().to_vec()
+    }
+
+    #[test]
+    fn ref_pic_list_modification() {
+        // abs_diff_pic_num_minus1 1 and long_term_pic_num 5 in l0, abs_diff_pic_num_minus1 0
+        // in l1
+        let data = ref_pic_list_modification_data(&[&[0, 1, 2, 5, 3], &[1, 0, 3]]);
+        let args = (&SliceTypeName::B, 2, 2);
+        let modification: RefPicListModification = BitStream::new(&data).read(args).unwrap();
+        let l0 = modification.ref_pic_list_modification_l0.as_ref().unwrap();
+        let idc = l0.iter().map(|x| x.modification_of_pic_nums_idc.0);
+        assert_eq!(idc.collect::<Vec<_>>(), [0, 2, 3]);
+        assert_eq!(
+            l0[0].abs_diff_pic_num_minus1,
+            Some(UnsignedExpGolombCode(1))
+        );
+        assert_eq!(l0[1].long_term_pic_num, Some(UnsignedExpGolombCode(5)));
+        let l1 = modification.ref_pic_list_modification_l1.as_ref().unwrap();
+        let idc = l1.iter().map(|x| x.modification_of_pic_nums_idc.0);
+        assert_eq!(idc.collect::<Vec<_>>(), [1, 3]);
+
+        let mut writer = BitWriter::new();
+        writer.write(&modification, args).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        // More modifications than num_ref_idx_l1_active_minus1 + 1
+        let result = BitWriter::new().write(&modification, (&SliceTypeName::B, 2, 0));
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+        let data = ref_pic_list_modification_data(&[&[0, 0, 0, 0, 3]]);
+        let result: bit_stream::Result<RefPicListModification> =
+            BitStream::new(&data).read((&SliceTypeName::P, 1, 1));
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+
+        // modification_of_pic_nums_idc 4 is only valid in MVC
+        let data = ref_pic_list_modification_data(&[&[4, 0, 3]]);
+        let result: bit_stream::Result<RefPicListModification> =
+            BitStream::new(&data).read((&SliceTypeName::P, 1, 1));
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+
+    #[test]
+    fn ref_pic_list_mvc_modification() {
+        // abs_diff_view_idx_minus1 2 and 0, then abs_diff_pic_num_minus1 1
+        let data = ref_pic_list_modification_data(&[&[4, 2, 5, 0, 0, 1, 3]]);
+        let args = (&SliceTypeName::P, 3, 1);
+        let modification: RefPicListMvcModification = BitStream::new(&data).read(args).unwrap();
+        let l0 = modification.ref_pic_list_modification_l0.as_ref().unwrap();
+        let idc = l0.iter().map(|x| x.modification_of_pic_nums_idc.0);
+        assert_eq!(idc.collect::<Vec<_>>(), [4, 5, 0, 3]);
+        let view_idx = l0.iter().map(|x| x.abs_diff_view_idx_minus1.map(|x| x.0));
+        assert_eq!(view_idx.collect::<Vec<_>>(), [Some(2), Some(0), None, None]);
+        assert_eq!(
+            l0[2].abs_diff_pic_num_minus1,
+            Some(UnsignedExpGolombCode(1))
+        );
+        assert!(modification.ref_pic_list_modification_flag_l1.is_none());
+
+        let mut writer = BitWriter::new();
+        writer.write(&modification, args).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        let result = BitWriter::new().write(&modification, (&SliceTypeName::P, 2, 1));
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+
+        let data = ref_pic_list_modification_data(&[&[6, 0, 3]]);
+        let result: bit_stream::Result<RefPicListMvcModification> =
+            BitStream::new(&data).read(args);
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
+    }
+
+    #[test]
+    fn delta_pic_order_cnt_bottom() {
+        // pic_order_cnt_type 2
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps: PictureParameterSet = decoder
+            .find_picture_parameter_set(UnsignedExpGolombCode(0))
+            .unwrap()
+            .clone();
+        pps.bottom_field_pic_order_in_frame_present_flag = true;
+        decoder.set_picture_parameter_set(pps);
+
+        // Non-reference I slice, delta_pic_order_cnt_bottom is only present for
+        // pic_order_cnt_type 0
+        let mut builder = SliceHeaderBuilder::new(1, 0, 7, 3);
+        builder.slice_qp_delta = -2;
+        builder.disable_deblocking_filter_idc = 1;
+        let (_, slice_header) = builder.read(&decoder);
+        assert_eq!(slice_header.delta_pic_order_cnt_bottom, None);
+        assert_eq!(slice_header.slice_qp_delta, SignedExpGolombCode(-2));
+        assert_eq!(
+            slice_header.disable_deblocking_filter_idc,
+            Some(UnsignedExpGolombCode(1))
+        );
+    }
+
+    #[test]
+    fn slice_type_name() {
+        let decoder = test_decoder(test_sequence_parameter_set());
+        let (_, mut slice_header) = SliceHeaderBuilder::new(5, 3, 7, 0).read(&decoder);
+        assert!(matches!(
+            slice_header.slice_type_name(),
+            Ok(SliceTypeName::I)
+        ));
+
+        slice_header.slice_type = UnsignedExpGolombCode(10);
+        assert!(matches!(
+            slice_header.slice_type_name(),
+            Err(BitStreamError::InvalidValue)
+        ));
+    }
+
+    /// pred_weight_table( ) with luma_log2_weight_denom 5 and chroma_log2_weight_denom 3, where
+    /// the first entry of each list has weights and the others don't
+    fn pred_weight_table_data(chroma_array_type: u64, lists: &[u64]) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        writer.write(&UnsignedExpGolombCode(5), ()).unwrap();
+        if chroma_array_type != 0 {
+            writer.write(&UnsignedExpGolombCode(3), ()).unwrap();
+        }
+        for &num_ref_idx_active in lists {
+            for i in 0..num_ref_idx_active {
+                writer.write_bit(i == 0).unwrap();
+                if i == 0 {
+                    writer.write(&SignedExpGolombCode(33), ()).unwrap();
+                    writer.write(&SignedExpGolombCode(-2), ()).unwrap();
+                }
+                if chroma_array_type != 0 {
+                    writer.write_bit(i == 0).unwrap();
+                    if i == 0 {
+                        for value in [7, 1, 9, -1] {
+                            writer.write(&SignedExpGolombCode(value), ()).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        writer.into_inner().to_vec()
+    }
+
+    #[test]
+    fn pred_weight_table() {
+        for (slice_type_name, chroma_array_type, lists) in [
+            (SliceTypeName::P, 0, &[2][..]),
+            (SliceTypeName::P, 1, &[2]),
+            (SliceTypeName::B, 1, &[1, 2]),
+        ] {
+            let data = pred_weight_table_data(chroma_array_type, lists);
+            let l1 = lists.get(1).copied().unwrap_or(0);
+            let args = (&slice_type_name, chroma_array_type, lists[0], l1);
+            let table: PredWeightTable = BitStream::new(&data).read(args).unwrap();
+
+            assert_eq!(table.luma_log2_weight_denom, UnsignedExpGolombCode(5));
+            let chroma_log2_weight_denom = table.chroma_log2_weight_denom.map(|x| x.0);
+            let weights = match slice_type_name {
+                SliceTypeName::B => vec![&table.weights_l0, table.weights_l1.as_ref().unwrap()],
+                _ => {
+                    assert!(table.weights_l1.is_none());
+                    vec![&table.weights_l0]
+                }
+            };
+            for (weights, &num_ref_idx_active) in weights.into_iter().zip(lists) {
+                assert_eq!(weights.len() as u64, num_ref_idx_active);
+                assert_eq!(weights[0].luma_weight, Some(SignedExpGolombCode(33)));
+                assert_eq!(weights[0].luma_offset, Some(SignedExpGolombCode(-2)));
+                assert!(weights[1..].iter().all(|x| !x.luma_weight_flag));
+            }
+            if chroma_array_type == 0 {
+                assert_eq!(chroma_log2_weight_denom, None);
+                assert!(!table.weights_l0[0].chroma_weight_flag);
+            } else {
+                assert_eq!(chroma_log2_weight_denom, Some(3));
+                let weight = &table.weights_l0[0];
+                let chroma_weight = weight.chroma_weight.iter().flatten().map(|x| x.0);
+                assert_eq!(chroma_weight.collect::<Vec<_>>(), [7, 9]);
+                let chroma_offset = weight.chroma_offset.iter().flatten().map(|x| x.0);
+                assert_eq!(chroma_offset.collect::<Vec<_>>(), [1, -1]);
+            }
+
+            let mut writer = BitWriter::new();
+            writer.write(&table, args).unwrap();
+            writer.write_bit(true).unwrap();
+            writer.byte_align().unwrap();
+            assert_eq!(&*writer.into_inner(), &data[..]);
+        }
+    }
+
+    #[test]
+    fn weighted_bipred_idc() {
+        // weighted_bipred_idc 1 signals pred_weight_table( ) in B slices only
+        let mut decoder = test_decoder(test_sequence_parameter_set());
+        let mut pps: PictureParameterSet = decoder
+            .find_picture_parameter_set(UnsignedExpGolombCode(0))
+            .unwrap()
+            .clone();
+        pps.weighted_bipred_idc = 1;
+        decoder.set_picture_parameter_set(pps);
+
+        let (_, slice_header) = SliceHeaderBuilder::new(1, 2, 5, 1).read(&decoder);
+        assert!(slice_header.pred_weight_table.is_none());
+
+        let data = pred_weight_table_data(1, &[1, 1]);
+        let table: PredWeightTable = BitStream::new(&data)
+            .read((&SliceTypeName::B, 1, 1, 1))
+            .unwrap();
+        let mut builder = SliceHeaderBuilder::new(1, 0, 6, 1);
+        builder.pred_weight_table = Some(table);
+        let (_, slice_header) = builder.read(&decoder);
+        let table = slice_header.pred_weight_table.unwrap();
+        assert_eq!(table.weights_l0.len(), 1);
+        assert_eq!(table.weights_l1.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn dec_ref_pic_marking() {
+        // no_output_of_prior_pics_flag 0 and long_term_reference_flag 1
+        let data = [0b0110_0000];
+        let marking: DecRefPicMarking = BitStream::new(&data).read(true).unwrap();
+        assert_eq!(marking.no_output_of_prior_pics_flag, Some(false));
+        assert_eq!(marking.long_term_reference_flag, Some(true));
+        assert!(marking.adaptive_ref_pic_marking_mode_flag.is_none());
+        let mut writer = BitWriter::new();
+        writer.write(&marking, true).unwrap();
+        writer.write_bit(true).unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        // Operations 1 to 6 with their values, then 0
+        let codes = [1, 3, 2, 4, 3, 2, 5, 4, 1, 5, 6, 6, 0];
+        let mut writer = BitWriter::new();
+        // adaptive_ref_pic_marking_mode_flag
+        writer.write_bit(true).unwrap();
+        for value in codes {
+            writer.write(&UnsignedExpGolombCode(value), ()).unwrap();
+        }
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        let data = writer.into_inner();
+
+        let marking: DecRefPicMarking = BitStream::new(&data).read(false).unwrap();
+        let operations = marking
+            .memory_management_control_operations
+            .as_ref()
+            .unwrap();
+        let operation = operations
+            .iter()
+            .map(|x| x.memory_management_control_operation.0);
+        assert_eq!(operation.collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6, 0]);
+        let value = |x: Option<UnsignedExpGolombCode>| x.map(|x| x.0);
+        assert_eq!(value(operations[0].difference_of_pic_nums_minus1), Some(3));
+        assert_eq!(value(operations[1].long_term_pic_num), Some(4));
+        assert_eq!(value(operations[2].difference_of_pic_nums_minus1), Some(2));
+        assert_eq!(value(operations[2].long_term_frame_idx), Some(5));
+        assert_eq!(value(operations[3].max_long_term_frame_idx_plus1), Some(1));
+        assert_eq!(value(operations[5].long_term_frame_idx), Some(6));
+        assert!(operations[4].difference_of_pic_nums_minus1.is_none());
+        assert!(operations[4].long_term_frame_idx.is_none());
+
+        let mut writer = BitWriter::new();
+        writer.write(&marking, false).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        // memory_management_control_operation 7
+        let data = [0b1000_1000, 0b1000_0000];
+        let result: bit_stream::Result<DecRefPicMarking> = BitStream::new(&data).read(false);
+        assert!(matches!(result, Err(BitStreamError::InvalidValue)));
     }
 }
@@ -0,0 +1,345 @@
+use bit_stream::cond_bit_field;
+use serde::Serialize;
+
+use crate::nal_unit::{ExtensionData, HrdParameters, SequenceParameterSet, SignedExpGolombCode,
+                      UnsignedExpGolombCode};
+
+cond_bit_field! {
+    /// subset_seq_parameter_set_rbsp
+    ///
+    /// Used by scalable (SVC) and multiview (MVC) streams instead of a sequence parameter set.
+    /// Subset sequence parameter sets use the same `seq_parameter_set_id` values as sequence
+    /// parameter sets, but a subset SPS and an SPS with the same id don't replace each other.
+    ///
+    /// § 7.3.2.1.3 Subset sequence parameter set RBSP syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct SubsetSequenceParameterSet {
+        pub seq_parameter_set_data: SequenceParameterSet;
+
+        let profile_idc = seq_parameter_set_data.profile_idc;
+
+        // § 7.4.2.1.1 Sequence parameter set data semantics
+        #[allow(non_snake_case)]
+        let ChromaArrayType = seq_parameter_set_data.chroma_array_type();
+
+        match profile_idc {
+            83 | 86 => {
+                pub seq_parameter_set_svc_extension: SeqParameterSetSvcExtension[ChromaArrayType];
+                pub svc_vui_parameters_present_flag: bool;
+                if svc_vui_parameters_present_flag {
+                    pub svc_vui_parameters_extension: SvcVuiParametersExtension;
+                }
+            },
+            118 | 128 | 134 => {
+                pub bit_equal_to_one: bool;
+                pub seq_parameter_set_mvc_extension: SeqParameterSetMvcExtension[
+                    profile_idc,
+                    seq_parameter_set_data.frame_mbs_only_flag
+                ];
+                pub mvc_vui_parameters_present_flag: bool;
+                if mvc_vui_parameters_present_flag {
+                    pub mvc_vui_parameters_extension: MvcVuiParametersExtension;
+                }
+            },
+            _ => {
+                /// Unparsed remainder of the RBSP, for profiles using
+                /// seq_parameter_set_mvcd_extension( ) or seq_parameter_set_3davc_extension( ).
+                pub unparsed_extension: ExtensionData;
+            }
+        }
+
+        if unparsed_extension.is_none() {
+            pub additional_extension2_flag: bool;
+            if additional_extension2_flag {
+                pub additional_extension2_data: ExtensionData;
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// seq_parameter_set_svc_extension
+    ///
+    /// § G.7.3.2.1.4 Sequence parameter set SVC extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(chroma_array_type: u64)]
+    pub struct SeqParameterSetSvcExtension {
+        pub inter_layer_deblocking_filter_control_present_flag: bool;
+        /// | extended_spatial_scalability_idc | Geometrical parameters for inter-layer prediction |
+        /// |----------------------------------|---------------------------------------------------|
+        /// | 0                                | not present                                       |
+        /// | 1                                | present in the subset sequence parameter set      |
+        /// | 2                                | present in slice headers                          |
+        ///
+        /// § G.7.4.2.1.4 Sequence parameter set SVC extension semantics
+        pub extended_spatial_scalability_idc: u2;
+
+        if chroma_array_type == 1 || chroma_array_type == 2 {
+            pub chroma_phase_x_plus1_flag: bool;
+        }
+
+        if chroma_array_type == 1 {
+            pub chroma_phase_y_plus1: u2;
+        }
+
+        if extended_spatial_scalability_idc == 1 {
+            if chroma_array_type > 0 {
+                pub seq_ref_layer_chroma_phase_x_plus1_flag: bool;
+                pub seq_ref_layer_chroma_phase_y_plus1: u2;
+            }
+
+            pub seq_scaled_ref_layer_left_offset: SignedExpGolombCode;
+            pub seq_scaled_ref_layer_top_offset: SignedExpGolombCode;
+            pub seq_scaled_ref_layer_right_offset: SignedExpGolombCode;
+            pub seq_scaled_ref_layer_bottom_offset: SignedExpGolombCode;
+        }
+
+        pub seq_tcoeff_level_prediction_flag: bool;
+        if seq_tcoeff_level_prediction_flag {
+            pub adaptive_tcoeff_level_prediction_flag: bool;
+        }
+
+        pub slice_header_restriction_flag: bool;
+    }
+}
+
+cond_bit_field! {
+    /// svc_vui_parameters_extension
+    ///
+    /// § G.14.1 SVC VUI parameters extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct SvcVuiParametersExtension {
+        pub vui_ext_num_entries_minus1: UnsignedExpGolombCode;
+        for _ in 0..=vui_ext_num_entries_minus1.0 {
+            pub entries: SvcVuiParametersExtensionEntry;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `vui_ext_num_entries_minus1` loop
+    ///
+    /// § G.14.1 SVC VUI parameters extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct SvcVuiParametersExtensionEntry {
+        pub vui_ext_dependency_id: u3;
+        pub vui_ext_quality_id: u4;
+        pub vui_ext_temporal_id: u3;
+
+        pub vui_ext_timing_info_present_flag: bool;
+        if vui_ext_timing_info_present_flag {
+            pub vui_ext_num_units_in_tick: u32;
+            pub vui_ext_time_scale: u32;
+            pub vui_ext_fixed_frame_rate_flag: bool;
+        }
+
+        pub vui_ext_nal_hrd_parameters_present_flag: bool;
+        if vui_ext_nal_hrd_parameters_present_flag {
+            pub vui_ext_nal_hrd_parameters: HrdParameters;
+        }
+
+        pub vui_ext_vcl_hrd_parameters_present_flag: bool;
+        if vui_ext_vcl_hrd_parameters_present_flag {
+            pub vui_ext_vcl_hrd_parameters: HrdParameters;
+        }
+
+        if vui_ext_nal_hrd_parameters_present_flag || vui_ext_vcl_hrd_parameters_present_flag {
+            pub vui_ext_low_delay_hrd_flag: bool;
+        }
+
+        pub vui_ext_pic_struct_present_flag: bool;
+    }
+}
+
+cond_bit_field! {
+    /// seq_parameter_set_mvc_extension
+    ///
+    /// § H.7.3.2.1.4 Sequence parameter set MVC extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    #[extra_args(profile_idc: u8, frame_mbs_only_flag: bool)]
+    pub struct SeqParameterSetMvcExtension {
+        pub num_views_minus1: UnsignedExpGolombCode;
+        for _ in 0..=num_views_minus1.0 {
+            /// specifies the view_id of the view with view order index `i`.
+            ///
+            /// § H.7.4.2.1.4 Sequence parameter set MVC extension semantics
+            pub view_id: UnsignedExpGolombCode;
+        }
+
+        for _ in 1..=num_views_minus1.0 {
+            /// Inter-view references of anchor view components, for view order index 1 and up.
+            pub anchor_refs: MvcViewRefs;
+        }
+
+        for _ in 1..=num_views_minus1.0 {
+            /// Inter-view references of non-anchor view components, for view order index 1 and up.
+            pub non_anchor_refs: MvcViewRefs;
+        }
+
+        pub num_level_values_signalled_minus1: UnsignedExpGolombCode;
+        for _ in 0..=num_level_values_signalled_minus1.0 {
+            pub level_values: MvcLevelValue;
+        }
+
+        if profile_idc == 134 {
+            pub mfc_format_idc: u6;
+            if mfc_format_idc == 0 || mfc_format_idc == 1 {
+                pub default_grid_position_flag: bool;
+                if !default_grid_position_flag {
+                    pub view0_grid_position_x: u4;
+                    pub view0_grid_position_y: u4;
+                    pub view1_grid_position_x: u4;
+                    pub view1_grid_position_y: u4;
+                }
+            }
+            pub rpu_filter_enabled_flag: bool;
+            if !frame_mbs_only_flag {
+                pub rpu_field_processing_flag: bool;
+            }
+        }
+    }
+}
+
+cond_bit_field! {
+    /// Inter-view references of one view, in either the anchor
+    /// (`num_anchor_refs_lX`, `anchor_ref_lX`) or the non-anchor
+    /// (`num_non_anchor_refs_lX`, `non_anchor_ref_lX`) loop
+    ///
+    /// § H.7.3.2.1.4 Sequence parameter set MVC extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MvcViewRefs {
+        pub num_refs_l0: UnsignedExpGolombCode;
+        for _ in 0..num_refs_l0.0 {
+            /// view_id of the view component used for inter-view prediction in RefPicList0
+            pub ref_l0: UnsignedExpGolombCode;
+        }
+
+        pub num_refs_l1: UnsignedExpGolombCode;
+        for _ in 0..num_refs_l1.0 {
+            /// view_id of the view component used for inter-view prediction in RefPicList1
+            pub ref_l1: UnsignedExpGolombCode;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `num_level_values_signalled_minus1` loop
+    ///
+    /// § H.7.3.2.1.4 Sequence parameter set MVC extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MvcLevelValue {
+        pub level_idc: u8;
+        pub num_applicable_ops_minus1: UnsignedExpGolombCode;
+        for _ in 0..=num_applicable_ops_minus1.0 {
+            pub applicable_ops: MvcApplicableOperationPoint;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `num_applicable_ops_minus1` loop
+    ///
+    /// § H.7.3.2.1.4 Sequence parameter set MVC extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MvcApplicableOperationPoint {
+        pub applicable_op_temporal_id: u3;
+        pub applicable_op_num_target_views_minus1: UnsignedExpGolombCode;
+        for _ in 0..=applicable_op_num_target_views_minus1.0 {
+            pub applicable_op_target_view_id: UnsignedExpGolombCode;
+        }
+        pub applicable_op_num_views_minus1: UnsignedExpGolombCode;
+    }
+}
+
+cond_bit_field! {
+    /// mvc_vui_parameters_extension
+    ///
+    /// § H.14.1 MVC VUI parameters extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MvcVuiParametersExtension {
+        pub vui_mvc_num_ops_minus1: UnsignedExpGolombCode;
+        for _ in 0..=vui_mvc_num_ops_minus1.0 {
+            pub operation_points: MvcVuiOperationPoint;
+        }
+    }
+}
+
+cond_bit_field! {
+    /// One iteration of the `vui_mvc_num_ops_minus1` loop
+    ///
+    /// § H.14.1 MVC VUI parameters extension syntax
+    #[derive(Clone, Debug, Serialize)]
+    pub struct MvcVuiOperationPoint {
+        pub vui_mvc_temporal_id: u3;
+        pub vui_mvc_num_target_output_views_minus1: UnsignedExpGolombCode;
+        for _ in 0..=vui_mvc_num_target_output_views_minus1.0 {
+            pub vui_mvc_view_id: UnsignedExpGolombCode;
+        }
+
+        pub vui_mvc_timing_info_present_flag: bool;
+        if vui_mvc_timing_info_present_flag {
+            pub vui_mvc_num_units_in_tick: u32;
+            pub vui_mvc_time_scale: u32;
+            pub vui_mvc_fixed_frame_rate_flag: bool;
+        }
+
+        pub vui_mvc_nal_hrd_parameters_present_flag: bool;
+        if vui_mvc_nal_hrd_parameters_present_flag {
+            pub vui_mvc_nal_hrd_parameters: HrdParameters;
+        }
+
+        pub vui_mvc_vcl_hrd_parameters_present_flag: bool;
+        if vui_mvc_vcl_hrd_parameters_present_flag {
+            pub vui_mvc_vcl_hrd_parameters: HrdParameters;
+        }
+
+        if vui_mvc_nal_hrd_parameters_present_flag || vui_mvc_vcl_hrd_parameters_present_flag {
+            pub vui_mvc_low_delay_hrd_flag: bool;
+        }
+
+        pub vui_mvc_pic_struct_present_flag: bool;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bit_stream::{BitStream, BitWriter};
+
+    use super::SubsetSequenceParameterSet;
+    use crate::{nal_unit::UnsignedExpGolombCode, test::MVC_SUBSET_SPS_DATA, Decoder};
+
+    #[test]
+    fn subset_seq_param_set_mvc() {
+        let data = MVC_SUBSET_SPS_DATA;
+
+        let subset: SubsetSequenceParameterSet = BitStream::new(&data).read(()).unwrap();
+        let mvc_extension = subset.seq_parameter_set_mvc_extension.as_ref().unwrap();
+        assert_eq!(mvc_extension.num_views_minus1.0, 1);
+        assert_eq!(
+            mvc_extension.view_id,
+            [UnsignedExpGolombCode(0), UnsignedExpGolombCode(1)]
+        );
+        assert_eq!(
+            mvc_extension.anchor_refs[0].ref_l0,
+            [UnsignedExpGolombCode(0)]
+        );
+        assert_eq!(mvc_extension.level_values[0].level_idc, 40);
+        assert_eq!(subset.additional_extension2_flag, Some(false));
+
+        let mut writer = BitWriter::new();
+        writer.write(&subset, ()).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        assert_eq!(&*writer.into_inner(), &data[..]);
+
+        // Subset sequence parameter sets don't replace sequence parameter sets
+        let mut decoder = Decoder::new();
+        decoder.set_subset_sequence_parameter_set(subset);
+        assert!(decoder
+            .find_subset_sequence_parameter_set(UnsignedExpGolombCode(0))
+            .is_some());
+        assert!(decoder
+            .find_sequence_parameter_set(UnsignedExpGolombCode(0))
+            .is_none());
+    }
+}
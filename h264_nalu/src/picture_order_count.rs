@@ -0,0 +1,318 @@
+use bit_stream::{BitStreamError, Result};
+use serde::Serialize;
+
+use crate::nal_unit::{NalUnitHeader, SequenceParameterSet, SliceHeader};
+
+/// TopFieldOrderCnt and BottomFieldOrderCnt of a picture
+///
+/// § 8.2.1 Decoding process for picture order count
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct PictureOrderCount {
+    /// `None` for a bottom field
+    pub top_field_order_cnt: Option<i64>,
+    /// `None` for a top field
+    pub bottom_field_order_cnt: Option<i64>,
+}
+
+impl PictureOrderCount {
+    /// PicOrderCnt( picX ), the smaller of the field order counts of a frame or complementary
+    /// field pair, or the field order count of a field
+    ///
+    /// § 8.2.1 Decoding process for picture order count
+    pub fn pic_order_cnt(&self) -> i64 {
+        match (self.top_field_order_cnt, self.bottom_field_order_cnt) {
+            (Some(top), Some(bottom)) => top.min(bottom),
+            (top, bottom) => top.or(bottom).unwrap_or(0),
+        }
+    }
+}
+
+/// Variables of the previous pictures in decoding order used to derive the picture order count
+/// of the current picture
+///
+/// § 8.2.1 Decoding process for picture order count
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PictureOrderCountDecoder {
+    /// prevPicOrderCntMsb, only for pic_order_cnt_type equal to 0
+    prev_pic_order_cnt_msb: i64,
+    /// prevPicOrderCntLsb, only for pic_order_cnt_type equal to 0
+    prev_pic_order_cnt_lsb: i64,
+    /// prevFrameNumOffset, only for pic_order_cnt_type equal to 1 or 2
+    prev_frame_num_offset: i64,
+    /// prevFrameNum, only for pic_order_cnt_type equal to 1 or 2
+    prev_frame_num: i64,
+}
+
+impl PictureOrderCountDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Derives the picture order count of the picture containing the slice with `header`, and
+    /// updates the state for the next picture. It must be called once per picture, in decoding
+    /// order, with any slice of the picture.
+    ///
+    /// For a picture with memory_management_control_operation equal to 5, the returned values
+    /// are the ones after its decoding, with PicOrderCnt( CurrPic ) equal to 0.
+    pub fn decode(
+        &mut self,
+        seq_parameter_set: &SequenceParameterSet,
+        nal_unit_header: &NalUnitHeader,
+        header: &SliceHeader,
+    ) -> Result<PictureOrderCount> {
+        let idr_pic_flag = header.idr_pic_id.is_some();
+        let reference = nal_unit_header.ref_idc != 0;
+        let bottom_field = header.bottom_field_flag == Some(true);
+        let memory_management_control_operation_5 = header
+            .dec_ref_pic_marking
+            .as_ref()
+            .and_then(|x| x.memory_management_control_operations.as_ref())
+            .is_some_and(|x| x.iter().any(|x| x.memory_management_control_operation == 5));
+
+        // The variables of an IDR picture don't depend on previous pictures
+        if idr_pic_flag {
+            *self = Self::default();
+        }
+
+        // (8-6) and (8-11)
+        let frame_num = header.frame_num as i64;
+        let frame_num_offset = if self.prev_frame_num > frame_num {
+            self.prev_frame_num_offset + seq_parameter_set.max_frame_num() as i64
+        } else {
+            self.prev_frame_num_offset
+        };
+
+        let (top_field_order_cnt, bottom_field_order_cnt) =
+            match seq_parameter_set.pic_order_cnt_type.0 {
+                0 => self.decode_type_0(seq_parameter_set, header, reference)?,
+                1 => Self::decode_type_1(seq_parameter_set, header, reference, frame_num_offset)?,
+                2 => {
+                    // § 8.2.1.3 Decoding process for picture order count type 2
+                    //
+                    // (8-12)
+                    let temp_pic_order_cnt = if idr_pic_flag {
+                        0
+                    } else if reference {
+                        2 * (frame_num_offset + frame_num)
+                    } else {
+                        2 * (frame_num_offset + frame_num) - 1
+                    };
+                    (temp_pic_order_cnt, temp_pic_order_cnt)
+                }
+                _ => return Err(BitStreamError::InvalidValue),
+            };
+
+        let mut picture_order_count = PictureOrderCount {
+            top_field_order_cnt: (!bottom_field).then_some(top_field_order_cnt),
+            bottom_field_order_cnt: (!header.field_pic_flag || bottom_field)
+                .then_some(bottom_field_order_cnt),
+        };
+
+        if memory_management_control_operation_5 {
+            // § 8.2.1 Decoding process for picture order count
+            let temp_pic_order_cnt = picture_order_count.pic_order_cnt();
+            picture_order_count.top_field_order_cnt = picture_order_count
+                .top_field_order_cnt
+                .map(|x| x - temp_pic_order_cnt);
+            picture_order_count.bottom_field_order_cnt = picture_order_count
+                .bottom_field_order_cnt
+                .map(|x| x - temp_pic_order_cnt);
+
+            // § 8.2.1.1 Decoding process for picture order count type 0
+            self.prev_pic_order_cnt_msb = 0;
+            self.prev_pic_order_cnt_lsb = picture_order_count.top_field_order_cnt.unwrap_or(0);
+
+            // § 7.4.3 Slice header semantics, the picture is inferred to have had frame_num
+            // equal to 0
+            self.prev_frame_num_offset = 0;
+            self.prev_frame_num = 0;
+        } else {
+            self.prev_frame_num_offset = frame_num_offset;
+            self.prev_frame_num = frame_num;
+        }
+
+        Ok(picture_order_count)
+    }
+
+    /// § 8.2.1.1 Decoding process for picture order count type 0
+    fn decode_type_0(
+        &mut self,
+        seq_parameter_set: &SequenceParameterSet,
+        header: &SliceHeader,
+        reference: bool,
+    ) -> Result<(i64, i64)> {
+        let max_pic_order_cnt_lsb = seq_parameter_set
+            .max_pic_order_cnt_lsb()
+            .ok_or(BitStreamError::MissingValue)? as i64;
+        let pic_order_cnt_lsb = header
+            .pic_order_cnt_lsb
+            .ok_or(BitStreamError::MissingValue)? as i64;
+
+        // (8-3)
+        let pic_order_cnt_msb = if pic_order_cnt_lsb < self.prev_pic_order_cnt_lsb
+            && self.prev_pic_order_cnt_lsb - pic_order_cnt_lsb >= max_pic_order_cnt_lsb / 2
+        {
+            self.prev_pic_order_cnt_msb + max_pic_order_cnt_lsb
+        } else if pic_order_cnt_lsb > self.prev_pic_order_cnt_lsb
+            && pic_order_cnt_lsb - self.prev_pic_order_cnt_lsb > max_pic_order_cnt_lsb / 2
+        {
+            self.prev_pic_order_cnt_msb - max_pic_order_cnt_lsb
+        } else {
+            self.prev_pic_order_cnt_msb
+        };
+
+        // Only reference pictures are previous reference pictures for the next ones, the values
+        // after memory_management_control_operation equal to 5 are set by the caller
+        if reference {
+            self.prev_pic_order_cnt_msb = pic_order_cnt_msb;
+            self.prev_pic_order_cnt_lsb = pic_order_cnt_lsb;
+        }
+
+        // (8-4) and (8-5), a field has only one of the two values
+        let top_field_order_cnt = pic_order_cnt_msb + pic_order_cnt_lsb;
+        let bottom_field_order_cnt = if header.field_pic_flag {
+            pic_order_cnt_msb + pic_order_cnt_lsb
+        } else {
+            top_field_order_cnt + header.delta_pic_order_cnt_bottom.map_or(0, |x| x.0)
+        };
+        Ok((top_field_order_cnt, bottom_field_order_cnt))
+    }
+
+    /// § 8.2.1.2 Decoding process for picture order count type 1
+    fn decode_type_1(
+        seq_parameter_set: &SequenceParameterSet,
+        header: &SliceHeader,
+        reference: bool,
+        frame_num_offset: i64,
+    ) -> Result<(i64, i64)> {
+        let offset_for_ref_frame = seq_parameter_set
+            .offset_for_ref_frame
+            .as_deref()
+            .unwrap_or_default();
+        let num_ref_frames_in_pic_order_cnt_cycle = offset_for_ref_frame.len() as i64;
+        let offset_for_non_ref_pic = seq_parameter_set
+            .offset_for_non_ref_pic
+            .ok_or(BitStreamError::MissingValue)?
+            .0;
+        let offset_for_top_to_bottom_field = seq_parameter_set
+            .offset_for_top_to_bottom_field
+            .ok_or(BitStreamError::MissingValue)?
+            .0;
+
+        // (8-7)
+        let mut abs_frame_num = if num_ref_frames_in_pic_order_cnt_cycle != 0 {
+            frame_num_offset + header.frame_num as i64
+        } else {
+            0
+        };
+        if !reference && abs_frame_num > 0 {
+            abs_frame_num -= 1;
+        }
+
+        // (8-8), (8-9) and (8-10)
+        let mut expected_pic_order_cnt = 0;
+        if abs_frame_num > 0 {
+            let pic_order_cnt_cycle_cnt =
+                (abs_frame_num - 1) / num_ref_frames_in_pic_order_cnt_cycle;
+            let frame_num_in_pic_order_cnt_cycle =
+                (abs_frame_num - 1) % num_ref_frames_in_pic_order_cnt_cycle;
+            let expected_delta_per_pic_order_cnt_cycle: i64 =
+                offset_for_ref_frame.iter().map(|x| x.0).sum();
+            expected_pic_order_cnt = pic_order_cnt_cycle_cnt
+                * expected_delta_per_pic_order_cnt_cycle
+                + offset_for_ref_frame[..=frame_num_in_pic_order_cnt_cycle as usize]
+                    .iter()
+                    .map(|x| x.0)
+                    .sum::<i64>();
+        }
+        if !reference {
+            expected_pic_order_cnt += offset_for_non_ref_pic;
+        }
+
+        // (8-10), a field has only one of the two values
+        let delta_pic_order_cnt_0 = header.delta_pic_order_cnt_0.map_or(0, |x| x.0);
+        let delta_pic_order_cnt_1 = header.delta_pic_order_cnt_1.map_or(0, |x| x.0);
+        let top_field_order_cnt = expected_pic_order_cnt + delta_pic_order_cnt_0;
+        let bottom_field_order_cnt = if header.field_pic_flag {
+            expected_pic_order_cnt + offset_for_top_to_bottom_field + delta_pic_order_cnt_0
+        } else {
+            top_field_order_cnt + offset_for_top_to_bottom_field + delta_pic_order_cnt_1
+        };
+        Ok((top_field_order_cnt, bottom_field_order_cnt))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{nal_unit::UnsignedExpGolombCode,
+                test::{test_decoder, test_sequence_parameter_set, SliceHeaderBuilder},
+                Decoder, PictureOrderCount};
+
+    /// Derives the picture order count of an I frame, with `pic_order_cnt_lsb` when
+    /// pic_order_cnt_type is 0
+    fn decode(
+        decoder: &mut Decoder,
+        (ty, ref_idc): (u8, u8),
+        frame_num: u8,
+        pic_order_cnt_lsb: Option<u8>,
+        memory_management_control_operation_5: bool,
+    ) -> PictureOrderCount {
+        let mut builder = SliceHeaderBuilder::new(ty, ref_idc, 7, frame_num);
+        builder.pic_order_cnt_lsb = pic_order_cnt_lsb;
+        if memory_management_control_operation_5 {
+            builder.memory_management_control_operations = vec![5, 0];
+        }
+        let (header, slice_header) = builder.read(decoder);
+        decoder
+            .decode_picture_order_count(&header, &slice_header)
+            .unwrap()
+    }
+
+    #[test]
+    fn picture_order_count() {
+        // pic_order_cnt_type 2, MaxFrameNum 16
+        let mut sps = test_sequence_parameter_set();
+        let mut decoder = test_decoder(sps.clone());
+        let pics = [
+            ((5, 3), 0, false, 0),
+            ((1, 2), 1, false, 2),
+            ((1, 0), 2, false, 3),
+            ((1, 2), 15, false, 30),
+            // FrameNumOffset is MaxFrameNum after frame_num wraps
+            ((1, 2), 0, false, 32),
+            ((1, 2), 1, true, 0),
+            // The previous picture is inferred to have had frame_num equal to 0
+            ((1, 2), 1, false, 2),
+        ];
+        for (header, frame_num, mmco5, pic_order_cnt) in pics {
+            let count = decode(&mut decoder, header, frame_num, None, mmco5);
+            assert_eq!(count.pic_order_cnt(), pic_order_cnt);
+            assert_eq!(count.top_field_order_cnt, count.bottom_field_order_cnt);
+        }
+
+        // pic_order_cnt_type 0, MaxPicOrderCntLsb 16
+        sps.pic_order_cnt_type = UnsignedExpGolombCode(0);
+        sps.log2_max_pic_order_cnt_lsb_minus4 = Some(UnsignedExpGolombCode(0));
+        let mut decoder = test_decoder(sps);
+        let pics = [
+            ((5, 3), 0, 0),
+            ((1, 2), 8, 8),
+            ((1, 2), 14, 14),
+            // pic_order_cnt_lsb wraps, PicOrderCntMsb is MaxPicOrderCntLsb
+            ((1, 2), 2, 18),
+            // Non-reference pictures aren't previous reference pictures
+            ((1, 0), 12, 12),
+            ((1, 2), 6, 22),
+        ];
+        for (frame_num, &(header, pic_order_cnt_lsb, pic_order_cnt)) in pics.iter().enumerate() {
+            let count = decode(
+                &mut decoder,
+                header,
+                frame_num as u8,
+                Some(pic_order_cnt_lsb),
+                false,
+            );
+            assert_eq!(count.pic_order_cnt(), pic_order_cnt);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::nal_unit::{HrdParameters, SequenceParameterSet};
+
+/// Profile indicated by profile_idc and the constraint_set flags
+///
+/// § A.2 Profiles
+///
+/// § G.10.1 Profiles
+///
+/// § H.10.1 Profiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Profile {
+    ConstrainedBaseline,
+    Baseline,
+    Main,
+    Extended,
+    ConstrainedHigh,
+    ProgressiveHigh,
+    High,
+    ProgressiveHigh10,
+    High10Intra,
+    High10,
+    High422Intra,
+    High422,
+    High444Intra,
+    High444Predictive,
+    Cavlc444Intra,
+    ScalableConstrainedBaseline,
+    ScalableBaseline,
+    ScalableConstrainedHigh,
+    ScalableHighIntra,
+    ScalableHigh,
+    MultiviewHigh,
+    StereoHigh,
+    MfcHigh,
+    MfcDepthHigh,
+    MultiviewDepthHigh,
+    EnhancedMultiviewDepthHigh,
+    Unknown(u8),
+}
+
+impl Profile {
+    pub fn new(seq_parameter_set: &SequenceParameterSet) -> Self {
+        let constraint_set1_flag = seq_parameter_set.constraint_set1_flag;
+        let constraint_set3_flag = seq_parameter_set.constraint_set3_flag;
+        let constraint_set4_flag = seq_parameter_set.constraint_set4_flag;
+        let constraint_set5_flag = seq_parameter_set.constraint_set5_flag;
+
+        match seq_parameter_set.profile_idc {
+            66 if constraint_set1_flag => Self::ConstrainedBaseline,
+            66 => Self::Baseline,
+            77 => Self::Main,
+            88 => Self::Extended,
+            100 if constraint_set4_flag && constraint_set5_flag => Self::ConstrainedHigh,
+            100 if constraint_set4_flag => Self::ProgressiveHigh,
+            100 => Self::High,
+            110 if constraint_set3_flag => Self::High10Intra,
+            110 if constraint_set4_flag => Self::ProgressiveHigh10,
+            110 => Self::High10,
+            122 if constraint_set3_flag => Self::High422Intra,
+            122 => Self::High422,
+            244 if constraint_set3_flag => Self::High444Intra,
+            244 => Self::High444Predictive,
+            44 => Self::Cavlc444Intra,
+            83 if constraint_set5_flag => Self::ScalableConstrainedBaseline,
+            83 => Self::ScalableBaseline,
+            86 if constraint_set5_flag => Self::ScalableConstrainedHigh,
+            86 if constraint_set3_flag => Self::ScalableHighIntra,
+            86 => Self::ScalableHigh,
+            118 => Self::MultiviewHigh,
+            128 => Self::StereoHigh,
+            134 => Self::MfcHigh,
+            135 => Self::MfcDepthHigh,
+            138 => Self::MultiviewDepthHigh,
+            139 => Self::EnhancedMultiviewDepthHigh,
+            profile_idc => Self::Unknown(profile_idc),
+        }
+    }
+
+    /// `(cpbBrVclFactor, cpbBrNalFactor)`, `None` for unknown profiles
+    ///
+    /// § A.3.3 Profile-specific level limits
+    ///
+    /// Table A-2 – Specification of cpbBrVclFactor and cpbBrNalFactor
+    pub fn cpb_br_factors(&self) -> Option<(u64, u64)> {
+        match self {
+            Self::ConstrainedBaseline
+            | Self::Baseline
+            | Self::Main
+            | Self::Extended
+            | Self::ScalableConstrainedBaseline
+            | Self::ScalableBaseline => Some((1000, 1200)),
+            Self::ProgressiveHigh10 | Self::High10Intra | Self::High10 => Some((3000, 3600)),
+            Self::High422Intra
+            | Self::High422
+            | Self::High444Intra
+            | Self::High444Predictive
+            | Self::Cavlc444Intra => Some((4000, 4800)),
+            Self::Unknown(_) => None,
+            _ => Some((1250, 1500)),
+        }
+    }
+}
+
+/// Level indicated by level_idc, and constraint_set3_flag for level 1b
+///
+/// § A.3 Levels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Level {
+    Level1,
+    Level1b,
+    Level1_1,
+    Level1_2,
+    Level1_3,
+    Level2,
+    Level2_1,
+    Level2_2,
+    Level3,
+    Level3_1,
+    Level3_2,
+    Level4,
+    Level4_1,
+    Level4_2,
+    Level5,
+    Level5_1,
+    Level5_2,
+    Level6,
+    Level6_1,
+    Level6_2,
+}
+
+/// Limits of a level
+///
+/// § A.3.1 Level limits common to the Baseline, Constrained Baseline, Main, and Extended profiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LevelLimits {
+    /// MaxMBPS, maximum macroblock processing rate in MB/s
+    pub max_mbps: u64,
+    /// MaxFS, maximum frame size in MBs
+    pub max_fs: u64,
+    /// MaxDpbMbs, maximum decoded picture buffer size in MBs
+    pub max_dpb_mbs: u64,
+    /// MaxBR, maximum video bit rate in units of cpbBrVclFactor or cpbBrNalFactor bits/s
+    pub max_br: u64,
+    /// MaxCPB, maximum CPB size in units of cpbBrVclFactor or cpbBrNalFactor bits
+    pub max_cpb: u64,
+}
+
+impl Level {
+    /// Returns `None` for unknown level_idc values
+    pub fn new(seq_parameter_set: &SequenceParameterSet) -> Option<Self> {
+        Some(match seq_parameter_set.level_idc {
+            9 => Self::Level1b,
+            10 => Self::Level1,
+            // Level 1b of the Baseline, Constrained Baseline, Main and Extended profiles
+            11 if seq_parameter_set.constraint_set3_flag
+                && matches!(seq_parameter_set.profile_idc, 66 | 77 | 88) =>
+            {
+                Self::Level1b
+            }
+            11 => Self::Level1_1,
+            12 => Self::Level1_2,
+            13 => Self::Level1_3,
+            20 => Self::Level2,
+            21 => Self::Level2_1,
+            22 => Self::Level2_2,
+            30 => Self::Level3,
+            31 => Self::Level3_1,
+            32 => Self::Level3_2,
+            40 => Self::Level4,
+            41 => Self::Level4_1,
+            42 => Self::Level4_2,
+            50 => Self::Level5,
+            51 => Self::Level5_1,
+            52 => Self::Level5_2,
+            60 => Self::Level6,
+            61 => Self::Level6_1,
+            62 => Self::Level6_2,
+            _ => return None,
+        })
+    }
+
+    /// Table A-1 – Level limits
+    pub fn limits(&self) -> LevelLimits {
+        let (max_mbps, max_fs, max_dpb_mbs, max_br, max_cpb) = match self {
+            Self::Level1 => (1485, 99, 396, 64, 175),
+            Self::Level1b => (1485, 99, 396, 128, 350),
+            Self::Level1_1 => (3000, 396, 900, 192, 500),
+            Self::Level1_2 => (6000, 396, 2376, 384, 1000),
+            Self::Level1_3 => (11880, 396, 2376, 768, 2000),
+            Self::Level2 => (11880, 396, 2376, 2000, 2000),
+            Self::Level2_1 => (19800, 792, 4752, 4000, 4000),
+            Self::Level2_2 => (20250, 1620, 8100, 4000, 4000),
+            Self::Level3 => (40500, 1620, 8100, 10000, 10000),
+            Self::Level3_1 => (108000, 3600, 18000, 14000, 14000),
+            Self::Level3_2 => (216000, 5120, 20480, 20000, 20000),
+            Self::Level4 => (245760, 8192, 32768, 20000, 25000),
+            Self::Level4_1 => (245760, 8192, 32768, 50000, 62500),
+            Self::Level4_2 => (522240, 8704, 34816, 50000, 62500),
+            Self::Level5 => (589824, 22080, 110400, 135000, 135000),
+            Self::Level5_1 => (983040, 36864, 184320, 240000, 240000),
+            Self::Level5_2 => (2073600, 36864, 184320, 240000, 240000),
+            Self::Level6 => (4177920, 139264, 696320, 240000, 240000),
+            Self::Level6_1 => (8355840, 139264, 696320, 480000, 480000),
+            Self::Level6_2 => (16711680, 139264, 696320, 800000, 800000),
+        };
+        LevelLimits {
+            max_mbps,
+            max_fs,
+            max_dpb_mbs,
+            max_br,
+            max_cpb,
+        }
+    }
+}
+
+/// The type of hypothetical reference decoder parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum HrdType {
+    Nal,
+    Vcl,
+}
+
+/// A level limit exceeded by a sequence parameter set
+#[derive(Error, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum LevelLimitViolation {
+    #[error("Unknown level_idc {level_idc}")]
+    UnknownLevel { level_idc: u8 },
+    #[error("Frame size of {frame_size_in_mbs} macroblocks exceeds MaxFS {max_fs}")]
+    FrameSize { frame_size_in_mbs: u64, max_fs: u64 },
+    #[error("PicWidthInMbs {pic_width_in_mbs} exceeds Sqrt( MaxFS * 8 ) {max}")]
+    PicWidth { pic_width_in_mbs: u64, max: u64 },
+    #[error("FrameHeightInMbs {frame_height_in_mbs} exceeds Sqrt( MaxFS * 8 ) {max}")]
+    FrameHeight { frame_height_in_mbs: u64, max: u64 },
+    #[error("max_num_ref_frames {max_num_ref_frames} exceeds MaxDpbFrames {max_dpb_frames}")]
+    MaxNumRefFrames {
+        max_num_ref_frames: u64,
+        max_dpb_frames: u64,
+    },
+    #[error(
+        "max_dec_frame_buffering {max_dec_frame_buffering} exceeds MaxDpbFrames {max_dpb_frames}"
+    )]
+    MaxDecFrameBuffering {
+        max_dec_frame_buffering: u64,
+        max_dpb_frames: u64,
+    },
+    #[error("Macroblock rate of {macroblocks_per_second} MB/s exceeds MaxMBPS {max_mbps}")]
+    MacroblockRate {
+        macroblocks_per_second: u64,
+        max_mbps: u64,
+    },
+    #[error(
+        "{hrd:?} HRD bit rate {bit_rate} of SchedSelIdx {sched_sel_idx} exceeds {max_bit_rate}"
+    )]
+    BitRate {
+        hrd: HrdType,
+        sched_sel_idx: usize,
+        bit_rate: u64,
+        max_bit_rate: u64,
+    },
+    #[error(
+        "{hrd:?} HRD CPB size {cpb_size} of SchedSelIdx {sched_sel_idx} exceeds {max_cpb_size}"
+    )]
+    CpbSize {
+        hrd: HrdType,
+        sched_sel_idx: usize,
+        cpb_size: u64,
+        max_cpb_size: u64,
+    },
+}
+
+/// Checks the limits of the level of `seq_parameter_set` on the picture size, the decoded
+/// picture buffer size, and on the frame rate and the bit rates in its VUI parameters.
+///
+/// Returns an empty `Vec` if all checked limits are met.
+///
+/// § A.3.1 Level limits common to the Baseline, Constrained Baseline, Main, and Extended profiles
+///
+/// § A.3.2 Level limits common to the High, Progressive High, Constrained High, High 10,
+/// Progressive High 10, High 4:2:2, High 4:4:4 Predictive, High 10 Intra, High 4:2:2 Intra,
+/// High 4:4:4 Intra, and CAVLC 4:4:4 Intra profiles
+pub fn check_level_limits(seq_parameter_set: &SequenceParameterSet) -> Vec<LevelLimitViolation> {
+    let level = match Level::new(seq_parameter_set) {
+        Some(level) => level,
+        None => {
+            return vec![LevelLimitViolation::UnknownLevel {
+                level_idc: seq_parameter_set.level_idc,
+            }]
+        }
+    };
+    let limits = level.limits();
+    let mut violations = vec![];
+
+    let pic_width_in_mbs = seq_parameter_set.pic_width_in_mbs();
+    let frame_height_in_mbs = seq_parameter_set.frame_height_in_mbs();
+    let frame_size_in_mbs = pic_width_in_mbs * frame_height_in_mbs;
+    if frame_size_in_mbs > limits.max_fs {
+        violations.push(LevelLimitViolation::FrameSize {
+            frame_size_in_mbs,
+            max_fs: limits.max_fs,
+        });
+    }
+
+    // Sqrt( MaxFS * 8 ), rounded down as both sides are integers
+    let max = (1..)
+        .take_while(|x| x * x <= limits.max_fs * 8)
+        .last()
+        .unwrap_or(0);
+    if pic_width_in_mbs > max {
+        violations.push(LevelLimitViolation::PicWidth {
+            pic_width_in_mbs,
+            max,
+        });
+    }
+    if frame_height_in_mbs > max {
+        violations.push(LevelLimitViolation::FrameHeight {
+            frame_height_in_mbs,
+            max,
+        });
+    }
+
+    // MaxDpbFrames = Min( MaxDpbMbs / ( PicWidthInMbs * FrameHeightInMbs ), 16 )
+    let max_dpb_frames = (limits.max_dpb_mbs / frame_size_in_mbs).min(16);
+    let max_num_ref_frames = seq_parameter_set.max_num_ref_frames.0;
+    if max_num_ref_frames > max_dpb_frames {
+        violations.push(LevelLimitViolation::MaxNumRefFrames {
+            max_num_ref_frames,
+            max_dpb_frames,
+        });
+    }
+
+    let vui_parameters = match &seq_parameter_set.yuv_parameters {
+        Some(vui_parameters) => vui_parameters,
+        None => return violations,
+    };
+
+    if let Some(max_dec_frame_buffering) = vui_parameters.max_dec_frame_buffering {
+        if max_dec_frame_buffering.0 > max_dpb_frames {
+            violations.push(LevelLimitViolation::MaxDecFrameBuffering {
+                max_dec_frame_buffering: max_dec_frame_buffering.0,
+                max_dpb_frames,
+            });
+        }
+    }
+
+    // With a fixed frame rate, a frame is 2 clock ticks
+    //
+    // § E.2.1 VUI parameters semantics
+    if let (Some(true), Some(num_units_in_tick), Some(time_scale)) = (
+        vui_parameters.fixed_frame_rate_flag,
+        vui_parameters.num_units_in_tick,
+        vui_parameters.time_scale,
+    ) {
+        let frame_duration = 2 * num_units_in_tick as u64;
+        let macroblocks_per_frame_duration = frame_size_in_mbs * time_scale as u64;
+        if frame_duration > 0 && macroblocks_per_frame_duration > limits.max_mbps * frame_duration {
+            violations.push(LevelLimitViolation::MacroblockRate {
+                macroblocks_per_second: macroblocks_per_frame_duration.div_ceil(frame_duration),
+                max_mbps: limits.max_mbps,
+            });
+        }
+    }
+
+    if let Some((cpb_br_vcl_factor, cpb_br_nal_factor)) =
+        Profile::new(seq_parameter_set).cpb_br_factors()
+    {
+        if let Some(hrd_parameters) = &vui_parameters.nal_hrd_parameters {
+            check_hrd_parameters(
+                &mut violations,
+                HrdType::Nal,
+                hrd_parameters,
+                cpb_br_nal_factor * limits.max_br,
+                cpb_br_nal_factor * limits.max_cpb,
+            );
+        }
+        if let Some(hrd_parameters) = &vui_parameters.vcl_hrd_parameters {
+            check_hrd_parameters(
+                &mut violations,
+                HrdType::Vcl,
+                hrd_parameters,
+                cpb_br_vcl_factor * limits.max_br,
+                cpb_br_vcl_factor * limits.max_cpb,
+            );
+        }
+    }
+
+    violations
+}
+
+/// § E.2.2 HRD parameters semantics
+fn check_hrd_parameters(
+    violations: &mut Vec<LevelLimitViolation>,
+    hrd: HrdType,
+    hrd_parameters: &HrdParameters,
+    max_bit_rate: u64,
+    max_cpb_size: u64,
+) {
+    for (sched_sel_idx, (bit_rate_value_minus1, cpb_size_value_minus1)) in hrd_parameters
+        .bit_rate_value_minus1
+        .iter()
+        .zip(&hrd_parameters.cpb_size_value_minus1)
+        .enumerate()
+    {
+        // BitRate[ SchedSelIdx ] = ( bit_rate_value_minus1[ SchedSelIdx ] + 1 ) * 2^( 6 + bit_rate_scale )
+        let bit_rate = (bit_rate_value_minus1.0.saturating_add(1))
+            .saturating_mul(1 << (6 + hrd_parameters.bit_rate_scale));
+        if bit_rate > max_bit_rate {
+            violations.push(LevelLimitViolation::BitRate {
+                hrd,
+                sched_sel_idx,
+                bit_rate,
+                max_bit_rate,
+            });
+        }
+
+        // CpbSize[ SchedSelIdx ] = ( cpb_size_value_minus1[ SchedSelIdx ] + 1 ) * 2^( 4 + cpb_size_scale )
+        let cpb_size = (cpb_size_value_minus1.0.saturating_add(1))
+            .saturating_mul(1 << (4 + hrd_parameters.cpb_size_scale));
+        if cpb_size > max_cpb_size {
+            violations.push(LevelLimitViolation::CpbSize {
+                hrd,
+                sched_sel_idx,
+                cpb_size,
+                max_cpb_size,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_level_limits, Level, LevelLimitViolation, Profile};
+    use crate::test::test_sequence_parameter_set;
+
+    #[test]
+    fn level_limits() {
+        let mut sps = test_sequence_parameter_set();
+        assert_eq!(sps.profile(), Profile::Baseline);
+        assert_eq!(sps.level(), Some(Level::Level4));
+        assert!(check_level_limits(&sps).is_empty());
+
+        sps.level_idc = 11;
+        sps.constraint_set3_flag = true;
+        assert_eq!(sps.level(), Some(Level::Level1b));
+        assert_eq!(
+            check_level_limits(&sps),
+            [
+                LevelLimitViolation::FrameSize {
+                    frame_size_in_mbs: 31 * 68,
+                    max_fs: 99
+                },
+                LevelLimitViolation::PicWidth {
+                    pic_width_in_mbs: 31,
+                    max: 28
+                },
+                LevelLimitViolation::FrameHeight {
+                    frame_height_in_mbs: 68,
+                    max: 28
+                },
+                LevelLimitViolation::MaxNumRefFrames {
+                    max_num_ref_frames: 1,
+                    max_dpb_frames: 0
+                },
+                LevelLimitViolation::MaxDecFrameBuffering {
+                    max_dec_frame_buffering: 1,
+                    max_dpb_frames: 0
+                },
+            ]
+        );
+
+        sps.level_idc = 0;
+        assert_eq!(
+            check_level_limits(&sps),
+            [LevelLimitViolation::UnknownLevel { level_idc: 0 }]
+        );
+    }
+}
@@ -118,7 +118,10 @@ impl NalUnitStream {
             return Self::create_error(NalUnitStreamError::InvalidEmulation);
         }
 
-        self.extract_nalu(self.byte_stream.len())
+        // Trailing zero bytes aren't part of the last NAL unit
+        let result = self.extract_nalu(write_index - zero_count);
+        self.start = self.byte_stream.len();
+        result
     }
 }
 
@@ -146,6 +149,24 @@ impl NalUnitStream {
             NalUnitPayload::SequenceParameterSet(sequence_parameter_set) => {
                 decoder.set_sequence_parameter_set(sequence_parameter_set.clone());
             }
+            NalUnitPayload::SequenceParameterSetExtension(sequence_parameter_set_extension) => {
+                decoder
+                    .set_sequence_parameter_set_extension(sequence_parameter_set_extension.clone());
+            }
+            NalUnitPayload::SubsetSequenceParameterSet(subset_sequence_parameter_set) => {
+                decoder.set_subset_sequence_parameter_set(subset_sequence_parameter_set.clone());
+            }
+            NalUnitPayload::Slice(slice)
+            | NalUnitPayload::IdrSlice(slice)
+            | NalUnitPayload::AuxiliarySlice(slice)
+            | NalUnitPayload::SliceExtension(slice) => {
+                decoder.activate_picture_parameter_set(slice.slice_header.pic_parameter_set_id);
+            }
+            NalUnitPayload::SliceDataPartitionA(partition) => {
+                let pic_parameter_set_id = partition.slice_header.pic_parameter_set_id;
+                decoder.activate_picture_parameter_set(pic_parameter_set_id);
+                decoder.set_slice_data_partition(partition.slice_id, pic_parameter_set_id);
+            }
             _ => {}
         }
         Ok(Some(unit))
@@ -224,13 +245,20 @@ impl NalUnitStream {
             return Err(NalUnitStreamError::InvalidEmulation);
         }
 
-        self.extract_nalu(self.byte_stream.len(), decoder)
+        // Trailing zero bytes aren't part of the last NAL unit
+        let result = self.extract_nalu(write_index - zero_count, decoder);
+        self.start = self.byte_stream.len();
+        result
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{Decoder, NalUnitStream};
+    use bit_stream::BitWriter;
+
+    use crate::{nal_unit::UnsignedExpGolombCode,
+                test::{SliceHeaderBuilder, MVC_SUBSET_SPS_DATA, PPS_DATA, SPS_DATA},
+                Decoder, NalUnitPayload, NalUnitStream};
 
     #[test]
     fn test() {
@@ -240,9 +268,95 @@ mod test {
         ];
         let mut stream = NalUnitStream::new(data.into_boxed_slice());
         let mut decoder = Decoder::new();
-        println!("{:?}", stream.next(&mut decoder));
-        println!("{:?}", stream.next(&mut decoder));
 
-        assert_eq!(true, false);
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert!(matches!(
+            unit.payload,
+            NalUnitPayload::SequenceParameterSet(_)
+        ));
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert!(matches!(
+            unit.payload,
+            NalUnitPayload::PictureParameterSet(_)
+        ));
+    }
+
+    #[test]
+    fn last_nal_unit() {
+        // An access unit delimiter, then a reserved NAL unit with an emulation prevention byte
+        // and trailing zero bytes
+        let data = vec![0, 0, 0, 1, 9, 240, 0, 0, 1, 16, 171, 0, 0, 3, 1, 0, 0];
+        let mut stream = NalUnitStream::new(data.into_boxed_slice());
+        let mut decoder = Decoder::new();
+
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert_eq!(unit.header.ty, 9);
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert_eq!(unit.header.ty, 16);
+        match &unit.payload {
+            NalUnitPayload::Unknown(data) => assert_eq!(&**data, &[171, 0, 0, 1]),
+            payload => panic!("unexpected payload {:?}", payload),
+        }
+        assert!(stream.next(&mut decoder).unwrap().is_none());
+    }
+
+    #[test]
+    fn auxiliary_slice() {
+        let mut data = vec![0, 0, 0, 1, 103];
+        data.extend(SPS_DATA);
+        data.extend([0, 0, 0, 1, 104]);
+        data.extend(PPS_DATA);
+        // SPS extension with aux_format_idc 1
+        data.extend([0, 0, 0, 1, 109, 0xa9, 0xfe, 0x00, 0x40]);
+
+        // Auxiliary P slice with all 31x68 macroblocks skipped
+        let builder = SliceHeaderBuilder::new(19, 3, 5, 1);
+        let mut writer = BitWriter::new();
+        writer.write(&builder.nal_unit_header_byte(), 8).unwrap();
+        builder.write(&mut writer);
+        // mb_skip_run
+        writer.write(&UnsignedExpGolombCode(31 * 68), ()).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        let slice = writer.into_inner();
+        // No emulation prevention bytes are needed
+        assert!(!slice.windows(2).any(|x| x == [0, 0]));
+        data.extend([0, 0, 0, 1]);
+        data.extend(&*slice);
+
+        let mut stream = NalUnitStream::new(data.into_boxed_slice());
+        let mut decoder = Decoder::new();
+        for _ in 0..3 {
+            stream.next(&mut decoder).unwrap().unwrap();
+        }
+        assert!(decoder.active_picture_parameter_set().is_none());
+
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert!(matches!(unit.payload, NalUnitPayload::AuxiliarySlice(_)));
+        assert!(decoder.active_picture_parameter_set().is_some());
+        assert!(decoder.active_sequence_parameter_set().is_some());
+    }
+
+    #[test]
+    fn slice_extension() {
+        let mut data = vec![0, 0, 0, 1, 111];
+        data.extend(MVC_SUBSET_SPS_DATA);
+        data.extend([0, 0, 0, 1, 104]);
+        data.extend(PPS_DATA);
+        // P slice of view 1 with all macroblocks skipped, see `slice_extension_mvc`
+        data.extend([
+            0, 0, 0, 1, 0x74, 0x40, 0x00, 0x43, 0x9a, 0x29, 0xa4, 0x50, 0x00, 0x7f, 0x86,
+        ]);
+
+        let mut stream = NalUnitStream::new(data.into_boxed_slice());
+        let mut decoder = Decoder::new();
+        for _ in 0..2 {
+            stream.next(&mut decoder).unwrap().unwrap();
+        }
+        assert!(decoder.active_picture_parameter_set().is_none());
+
+        let unit = stream.next(&mut decoder).unwrap().unwrap();
+        assert!(matches!(unit.payload, NalUnitPayload::SliceExtension(_)));
+        assert!(decoder.active_picture_parameter_set().is_some());
     }
 }